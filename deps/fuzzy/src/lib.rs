@@ -1,5 +1,7 @@
 extern crate regex;
 
+use std::error;
+use std::fmt;
 use std::vec::Vec;
 
 use regex::{Regex, RegexBuilder};
@@ -19,8 +21,20 @@ pub struct Finder {
     re: Regex
 }
 
+/// A query that is not a valid pattern
+#[derive(Debug)]
+pub struct QueryError(regex::Error);
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl error::Error for QueryError {}
+
 impl Finder {
-    pub fn new(s: &str) -> Result<Self, ()> {
+    pub fn new(s: &str) -> Result<Self, QueryError> {
         let re = RegexBuilder::new(&s)
             .case_insensitive(true)
             .build();
@@ -29,7 +43,7 @@ impl Finder {
             Ok(re) => Ok(Finder {
                 re: re
             }),
-            Err(_) => Err(())
+            Err(err) => Err(QueryError(err))
         }
     }
 
@@ -0,0 +1,334 @@
+use std::cmp;
+
+use {Rect, Size, Thickness};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HorizontalAlignment {
+    Left, Center, Right
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VerticalAlignment {
+    Top, Center, Bottom
+}
+
+/// Describes how a window is sized and positioned inside its parent
+///
+/// All sizes are outer sizes, matching the window rectangles Win32 works with:
+///
+/// * `margin` is the space kept free between the parent edges and the window.
+///   It shrinks the area the window is placed in; it never moves the window
+///   outside of the parent.
+/// * `width` and `height` are the requested size. `None` stretches the window to
+///   fill the area left by the margin.
+/// * `min_size` and `max_size` clamp the requested (or stretched) size. The
+///   available area always wins, so a window never grows past the margin even if
+///   `min_size` asks for more.
+/// * `horizontal` and `vertical` align the window inside the available area when it
+///   is smaller than that area. Centering rounds towards the left and top.
+/// * `padding` is the space inside the window around its content. It does not
+///   change the window size, only the content rectangle.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub min_size: Size,
+    pub max_size: Size,
+    pub margin: Thickness,
+    pub padding: Thickness,
+    pub horizontal: HorizontalAlignment,
+    pub vertical: VerticalAlignment,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            width: None,
+            height: None,
+            min_size: (0, 0),
+            max_size: (i32::MAX, i32::MAX),
+            margin: Thickness::default(),
+            padding: Thickness::default(),
+            horizontal: HorizontalAlignment::Center,
+            vertical: VerticalAlignment::Center,
+        }
+    }
+}
+
+/// Result of a layout pass, both in the coordinates of the parent rectangle
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Arrangement {
+    /// Outer bounds of the window
+    pub bounds: Rect,
+    /// Bounds of the window minus its padding
+    pub content: Rect,
+}
+
+/// Positions a window inside `parent` according to `layout`
+///
+/// The result is in the same coordinate space as `parent`, so passing a monitor
+/// work area yields screen coordinates and passing a client rectangle yields
+/// child window coordinates.
+pub fn calc_window_pos(parent: Rect, layout: &Layout) -> Arrangement {
+    let available = parent.inset(&layout.margin);
+
+    let (min_w, min_h) = layout.min_size;
+    let (max_w, max_h) = layout.max_size;
+    let w = fit(layout.width, available.width(), min_w, max_w);
+    let h = fit(layout.height, available.height(), min_h, max_h);
+
+    let x = match layout.horizontal {
+        HorizontalAlignment::Left => available.left,
+        HorizontalAlignment::Center => available.left + (available.width() - w) / 2,
+        HorizontalAlignment::Right => available.right - w,
+    };
+    let y = match layout.vertical {
+        VerticalAlignment::Top => available.top,
+        VerticalAlignment::Center => available.top + (available.height() - h) / 2,
+        VerticalAlignment::Bottom => available.bottom - h,
+    };
+
+    let bounds = Rect::from_origin_size(x, y, w, h);
+
+    Arrangement {
+        bounds,
+        content: bounds.inset(&layout.padding),
+    }
+}
+
+fn fit(requested: Option<i32>, available: i32, min: i32, max: i32) -> i32 {
+    let size = requested.unwrap_or(available);
+    let size = cmp::max(cmp::min(size, max), min);
+
+    cmp::max(cmp::min(size, available), 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use {Rect, Thickness};
+    use super::{calc_window_pos, Layout, HorizontalAlignment, VerticalAlignment};
+
+    #[test]
+    fn stretch() {
+        let parent = Rect::from_origin_size(0, 0, 340, 50);
+        let layout = Layout {
+            height: Some(22),
+            margin: Thickness::symmetric(15, 0),
+            ..Layout::default()
+        };
+
+        assert_eq!(Rect::from_origin_size(15, 14, 310, 22), calc_window_pos(parent, &layout).bounds);
+    }
+
+    #[test]
+    fn centered_on_secondary_monitor() {
+        let work_area = Rect::from_edges(-2560, -360, 0, 1040);
+        let layout = Layout {
+            width: Some(340),
+            height: Some(50),
+            ..Layout::default()
+        };
+
+        assert_eq!(Rect::from_origin_size(-1450, 315, 340, 50), calc_window_pos(work_area, &layout).bounds);
+    }
+
+    #[test]
+    fn margin_shrinks() {
+        let parent = Rect::from_edges(0, 0, 100, 100);
+        let layout = Layout {
+            margin: Thickness::new(10, 20, 30, 40),
+            ..Layout::default()
+        };
+
+        assert_eq!(Rect::from_edges(10, 20, 70, 60), calc_window_pos(parent, &layout).bounds);
+    }
+
+    #[test]
+    fn alignment() {
+        let parent = Rect::from_edges(100, 100, 200, 200);
+        let layout = |horizontal, vertical| Layout {
+            width: Some(10),
+            height: Some(20),
+            margin: Thickness::uniform(5),
+            horizontal,
+            vertical,
+            ..Layout::default()
+        };
+
+        let bounds = calc_window_pos(parent, &layout(HorizontalAlignment::Left, VerticalAlignment::Top)).bounds;
+        assert_eq!(Rect::from_origin_size(105, 105, 10, 20), bounds);
+
+        let bounds = calc_window_pos(parent, &layout(HorizontalAlignment::Right, VerticalAlignment::Bottom)).bounds;
+        assert_eq!(Rect::from_origin_size(185, 175, 10, 20), bounds);
+
+        let bounds = calc_window_pos(parent, &layout(HorizontalAlignment::Center, VerticalAlignment::Center)).bounds;
+        assert_eq!(Rect::from_origin_size(145, 140, 10, 20), bounds);
+    }
+
+    #[test]
+    fn min_max() {
+        let parent = Rect::from_edges(0, 0, 1000, 1000);
+        let layout = Layout {
+            width: Some(50),
+            min_size: (100, 100),
+            max_size: (800, 600),
+            ..Layout::default()
+        };
+
+        let bounds = calc_window_pos(parent, &layout).bounds;
+        assert_eq!((100, 600), (bounds.width(), bounds.height()));
+    }
+
+    #[test]
+    fn min_larger_than_parent() {
+        let parent = Rect::from_edges(0, 0, 300, 200);
+        let layout = Layout {
+            min_size: (400, 400),
+            ..Layout::default()
+        };
+
+        assert_eq!(parent, calc_window_pos(parent, &layout).bounds);
+    }
+
+    #[test]
+    fn padding() {
+        let parent = Rect::from_edges(0, 0, 100, 100);
+        let layout = Layout {
+            width: Some(50),
+            height: Some(50),
+            padding: Thickness::new(5, 2, 5, 0),
+            ..Layout::default()
+        };
+
+        let arrangement = calc_window_pos(parent, &layout);
+        assert_eq!(Rect::from_edges(25, 25, 75, 75), arrangement.bounds);
+        assert_eq!(Rect::from_edges(30, 27, 70, 75), arrangement.content);
+    }
+
+    fn rect() -> BoxedStrategy<Rect> {
+        (-5000..5000i32, -5000..5000i32, 0..4000i32, 0..4000i32)
+            .prop_map(|(x, y, w, h)| Rect::from_origin_size(x, y, w, h))
+            .boxed()
+    }
+
+    fn thickness() -> BoxedStrategy<Thickness> {
+        (0..500i32, 0..500i32, 0..500i32, 0..500i32)
+            .prop_map(|(l, t, r, b)| Thickness::new(l, t, r, b))
+            .boxed()
+    }
+
+    fn layout() -> BoxedStrategy<Layout> {
+        let size = || prop::option::of(0..5000i32);
+        let horizontal = prop_oneof![
+            Just(HorizontalAlignment::Left),
+            Just(HorizontalAlignment::Center),
+            Just(HorizontalAlignment::Right),
+        ];
+        let vertical = prop_oneof![
+            Just(VerticalAlignment::Top),
+            Just(VerticalAlignment::Center),
+            Just(VerticalAlignment::Bottom),
+        ];
+
+        (size(), size(), (0..3000i32, 0..3000i32), (0..5000i32, 0..5000i32), thickness(), thickness(), horizontal, vertical)
+            .prop_map(|(width, height, min_size, max_size, margin, padding, horizontal, vertical)| Layout {
+                width,
+                height,
+                min_size,
+                max_size,
+                margin,
+                padding,
+                horizontal,
+                vertical,
+            })
+            .boxed()
+    }
+
+    proptest! {
+        #[test]
+        fn stays_inside_margin(parent in rect(), layout in layout()) {
+            let arrangement = calc_window_pos(parent, &layout);
+
+            prop_assert!(parent.contains(&arrangement.bounds));
+            prop_assert!(parent.inset(&layout.margin).contains(&arrangement.bounds));
+            prop_assert!(arrangement.bounds.contains(&arrangement.content));
+        }
+
+        #[test]
+        fn follows_parent_origin(parent in rect(), layout in layout(), dx in -5000..5000i32, dy in -5000..5000i32) {
+            let moved = calc_window_pos(parent.offset(dx, dy), &layout);
+            let arrangement = calc_window_pos(parent, &layout);
+
+            prop_assert_eq!(arrangement.bounds.offset(dx, dy), moved.bounds);
+            prop_assert_eq!(arrangement.content.offset(dx, dy), moved.content);
+        }
+
+        #[test]
+        fn keeps_requested_size_when_it_fits(parent in rect(), layout in layout()) {
+            let available = parent.inset(&layout.margin);
+            let bounds = calc_window_pos(parent, &layout).bounds;
+            let (min_w, _) = layout.min_size;
+            let (max_w, _) = layout.max_size;
+
+            if let Some(width) = layout.width {
+                if width >= min_w && width <= max_w && width <= available.width() {
+                    prop_assert_eq!(width, bounds.width());
+                }
+            }
+        }
+
+        #[test]
+        fn respects_min_max(parent in rect(), layout in layout()) {
+            let available = parent.inset(&layout.margin);
+            let bounds = calc_window_pos(parent, &layout).bounds;
+            let (min_w, min_h) = layout.min_size;
+            let (max_w, max_h) = layout.max_size;
+
+            if min_w <= max_w && min_w <= available.width() {
+                prop_assert!(bounds.width() >= min_w);
+            }
+            if min_h <= max_h && min_h <= available.height() {
+                prop_assert!(bounds.height() >= min_h);
+            }
+            if min_w <= max_w {
+                prop_assert!(bounds.width() <= max_w);
+            }
+            if min_h <= max_h {
+                prop_assert!(bounds.height() <= max_h);
+            }
+        }
+
+        #[test]
+        fn stretches_without_size(parent in rect(), margin in thickness()) {
+            let layout = Layout { margin, ..Layout::default() };
+
+            prop_assert_eq!(parent.inset(&margin), calc_window_pos(parent, &layout).bounds);
+        }
+
+        #[test]
+        fn aligns_to_edges(parent in rect(), layout in layout()) {
+            let available = parent.inset(&layout.margin);
+            let bounds = calc_window_pos(parent, &layout).bounds;
+
+            match layout.horizontal {
+                HorizontalAlignment::Left => prop_assert_eq!(available.left, bounds.left),
+                HorizontalAlignment::Right => prop_assert_eq!(available.right, bounds.right),
+                HorizontalAlignment::Center => {
+                    let slack = (bounds.left - available.left) - (available.right - bounds.right);
+                    prop_assert!(slack == 0 || slack == -1);
+                }
+            }
+
+            match layout.vertical {
+                VerticalAlignment::Top => prop_assert_eq!(available.top, bounds.top),
+                VerticalAlignment::Bottom => prop_assert_eq!(available.bottom, bounds.bottom),
+                VerticalAlignment::Center => {
+                    let slack = (bounds.top - available.top) - (available.bottom - bounds.bottom);
+                    prop_assert!(slack == 0 || slack == -1);
+                }
+            }
+        }
+    }
+}
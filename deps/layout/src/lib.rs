@@ -0,0 +1,16 @@
+#[cfg(test)]
+extern crate proptest;
+
+pub mod arrange;
+pub mod monitor;
+pub mod tiling;
+mod rect;
+
+pub use arrange::{calc_window_pos, Arrangement, HorizontalAlignment, Layout, VerticalAlignment};
+pub use rect::{Rect, Thickness};
+
+/// Screen coordinate as `(x, y)`
+pub type Point = (i32, i32);
+
+/// Dimensions as `(width, height)`
+pub type Size = (i32, i32);
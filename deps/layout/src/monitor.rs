@@ -0,0 +1,232 @@
+use std::cmp;
+use std::str::FromStr;
+
+use {Point, Rect};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    /// Full monitor area in virtual screen coordinates
+    pub bounds: Rect,
+    /// Monitor area without the taskbar and docked toolbars
+    pub work_area: Rect,
+    pub dpi: u32,
+    pub primary: bool,
+}
+
+impl Monitor {
+    /// Squared distance from `point` to the nearest edge, zero when inside
+    fn distance_sq(&self, point: Point) -> i64 {
+        let (x, y) = point;
+        let bounds = &self.bounds;
+
+        let dx = cmp::max(cmp::max(bounds.left - x, x - (bounds.right - 1)), 0) as i64;
+        let dy = cmp::max(cmp::max(bounds.top - y, y - (bounds.bottom - 1)), 0) as i64;
+
+        dx * dx + dy * dy
+    }
+}
+
+/// Which monitor a window should appear on
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Placement {
+    /// The monitor under the mouse cursor
+    Cursor,
+    /// The monitor showing most of the foreground window
+    #[default]
+    ForegroundWindow,
+    /// The primary monitor
+    Primary,
+}
+
+impl FromStr for Placement {
+    type Err = ();
+
+    /// `cursor`, `foreground` or `primary`
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "cursor" => Ok(Placement::Cursor),
+            "foreground" => Ok(Placement::ForegroundWindow),
+            "primary" => Ok(Placement::Primary),
+            _ => Err(())
+        }
+    }
+}
+
+/// Picks a monitor according to `placement`
+///
+/// Falls back to the primary monitor when the cursor position or foreground window
+/// is not known. Returns `None` only when `monitors` is empty.
+pub fn select_monitor(
+    monitors: &[Monitor],
+    placement: Placement,
+    cursor: Option<Point>,
+    foreground: Option<Rect>) -> Option<&Monitor> {
+
+    let monitor = match placement {
+        Placement::Cursor => cursor.and_then(|p| monitor_from_point(monitors, p)),
+        Placement::ForegroundWindow => foreground.and_then(|b| monitor_from_bounds(monitors, b)),
+        Placement::Primary => None,
+    };
+
+    monitor.or_else(|| primary_monitor(monitors))
+}
+
+/// The primary monitor, or the first one if none is flagged as primary
+pub fn primary_monitor(monitors: &[Monitor]) -> Option<&Monitor> {
+    monitors
+        .iter()
+        .find(|m| m.primary)
+        .or_else(|| monitors.first())
+}
+
+/// The monitor containing `point`, or the nearest one if it is in a gap between monitors
+pub fn monitor_from_point(monitors: &[Monitor], point: Point) -> Option<&Monitor> {
+    monitors
+        .iter()
+        .min_by_key(|m| m.distance_sq(point))
+}
+
+/// The monitor with the largest overlap with `bounds`, or the one nearest to its center
+pub fn monitor_from_bounds(monitors: &[Monitor], bounds: Rect) -> Option<&Monitor> {
+    let overlapping = monitors
+        .iter()
+        .map(|m| (m, m.bounds.intersection(&bounds).map_or(0, |r| r.area())))
+        .filter(|&(_, area)| area > 0)
+        .fold(None, |best: Option<(&Monitor, i64)>, (m, area)| match best {
+            Some((_, best_area)) if best_area >= area => best,
+            _ => Some((m, area)),
+        });
+
+    match overlapping {
+        Some((monitor, _)) => Some(monitor),
+        None => monitor_from_point(monitors, bounds.center()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Rect;
+    use super::{Monitor, Placement, select_monitor, monitor_from_point, monitor_from_bounds};
+
+    fn monitor(bounds: (i32, i32, i32, i32), primary: bool) -> Monitor {
+        let (l, t, r, b) = bounds;
+
+        Monitor {
+            bounds: Rect::from_edges(l, t, r, b),
+            work_area: Rect::from_edges(l, t, r, b - 40),
+            dpi: 96,
+            primary,
+        }
+    }
+
+    /// Secondary monitor to the left of the primary, with negative coordinates
+    fn side_by_side() -> Vec<Monitor> {
+        vec![
+            monitor((0, 0, 1920, 1080), true),
+            monitor((-2560, -360, 0, 1080), false),
+        ]
+    }
+
+    /// Laptop below a larger external monitor, offset horizontally
+    fn stacked() -> Vec<Monitor> {
+        vec![
+            monitor((0, 0, 1366, 768), true),
+            monitor((-277, -1440, 2283, 0), false),
+        ]
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(None, select_monitor(&[], Placement::Primary, None, None));
+        assert_eq!(None, select_monitor(&[], Placement::Cursor, Some((0, 0)), None));
+        assert_eq!(None, select_monitor(&[], Placement::ForegroundWindow, None, Some(Rect::from_edges(0, 0, 10, 10))));
+    }
+
+    #[test]
+    fn primary() {
+        let monitors = side_by_side();
+
+        assert_eq!(Some(&monitors[0]), select_monitor(&monitors, Placement::Primary, Some((-100, 0)), None));
+    }
+
+    #[test]
+    fn primary_without_flag() {
+        let monitors = vec![
+            monitor((1920, 0, 3840, 1080), false),
+            monitor((0, 0, 1920, 1080), false),
+        ];
+
+        assert_eq!(Some(&monitors[0]), select_monitor(&monitors, Placement::Primary, None, None));
+    }
+
+    #[test]
+    fn cursor() {
+        let monitors = side_by_side();
+
+        assert_eq!(Some(&monitors[1]), select_monitor(&monitors, Placement::Cursor, Some((-1, 500)), None));
+        assert_eq!(Some(&monitors[0]), select_monitor(&monitors, Placement::Cursor, Some((0, 500)), None));
+        assert_eq!(Some(&monitors[1]), select_monitor(&monitors, Placement::Cursor, Some((-2560, -360)), None));
+
+        let monitors = stacked();
+
+        assert_eq!(Some(&monitors[1]), select_monitor(&monitors, Placement::Cursor, Some((2000, -1)), None));
+        assert_eq!(Some(&monitors[0]), select_monitor(&monitors, Placement::Cursor, Some((1365, 767)), None));
+    }
+
+    #[test]
+    fn cursor_unknown() {
+        let monitors = side_by_side();
+
+        assert_eq!(Some(&monitors[0]), select_monitor(&monitors, Placement::Cursor, None, None));
+    }
+
+    #[test]
+    fn cursor_outside_monitors() {
+        let monitors = side_by_side();
+
+        // Dead zone above the primary monitor, right next to the secondary
+        assert_eq!(Some(&monitors[1]), monitor_from_point(&monitors, (10, -200)));
+        assert_eq!(Some(&monitors[0]), monitor_from_point(&monitors, (300, -200)));
+        assert_eq!(Some(&monitors[0]), monitor_from_point(&monitors, (5000, 500)));
+    }
+
+    #[test]
+    fn foreground_window() {
+        let monitors = side_by_side();
+
+        let window = Rect::from_edges(-1200, 100, -200, 800);
+        assert_eq!(Some(&monitors[1]), select_monitor(&monitors, Placement::ForegroundWindow, None, Some(window)));
+
+        let window = Rect::from_edges(100, 100, 800, 800);
+        assert_eq!(Some(&monitors[0]), select_monitor(&monitors, Placement::ForegroundWindow, None, Some(window)));
+    }
+
+    #[test]
+    fn foreground_window_spanning() {
+        let monitors = side_by_side();
+
+        // Mostly on the secondary monitor
+        let window = Rect::from_edges(-600, 100, 200, 800);
+        assert_eq!(Some(&monitors[1]), monitor_from_bounds(&monitors, window));
+
+        // Mostly on the primary monitor
+        let window = Rect::from_edges(-200, 100, 600, 800);
+        assert_eq!(Some(&monitors[0]), monitor_from_bounds(&monitors, window));
+    }
+
+    #[test]
+    fn foreground_window_offscreen() {
+        let monitors = stacked();
+
+        // Minimized windows are parked at (-32000, -32000)
+        let window = Rect::from_edges(-32000, -32000, -31840, -31972);
+        assert_eq!(Some(&monitors[1]), monitor_from_bounds(&monitors, window));
+    }
+
+    #[test]
+    fn foreground_window_unknown() {
+        let monitors = stacked();
+
+        assert_eq!(Some(&monitors[0]), select_monitor(&monitors, Placement::ForegroundWindow, Some((0, -500)), None));
+    }
+}
@@ -0,0 +1,190 @@
+use std::cmp;
+
+use Point;
+
+/// Screen rectangle with exclusive right and bottom edges, like a Win32 `RECT`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Rect {
+    pub fn from_edges(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Rect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    pub fn from_origin_size(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Rect {
+            left: x,
+            top: y,
+            right: x + width,
+            bottom: y + height,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> i32 {
+        self.bottom - self.top
+    }
+
+    pub fn origin(&self) -> Point {
+        (self.left, self.top)
+    }
+
+    pub fn center(&self) -> Point {
+        (self.left + self.width() / 2, self.top + self.height() / 2)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width() <= 0 || self.height() <= 0
+    }
+
+    pub fn area(&self) -> i64 {
+        if self.is_empty() {
+            0
+        } else {
+            self.width() as i64 * self.height() as i64
+        }
+    }
+
+    pub fn contains_point(&self, point: Point) -> bool {
+        let (x, y) = point;
+
+        x >= self.left && x < self.right && y >= self.top && y < self.bottom
+    }
+
+    pub fn contains(&self, other: &Rect) -> bool {
+        other.left >= self.left
+            && other.top >= self.top
+            && other.right <= self.right
+            && other.bottom <= self.bottom
+    }
+
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let rect = Rect {
+            left: cmp::max(self.left, other.left),
+            top: cmp::max(self.top, other.top),
+            right: cmp::min(self.right, other.right),
+            bottom: cmp::min(self.bottom, other.bottom),
+        };
+
+        if rect.is_empty() {
+            None
+        } else {
+            Some(rect)
+        }
+    }
+
+    pub fn offset(&self, dx: i32, dy: i32) -> Rect {
+        Rect::from_edges(self.left + dx, self.top + dy, self.right + dx, self.bottom + dy)
+    }
+
+    /// Moves every edge inwards by `thickness`
+    ///
+    /// Opposite edges never cross: when the insets are larger than the rectangle it
+    /// collapses to zero width or height inside the original bounds.
+    pub fn inset(&self, thickness: &Thickness) -> Rect {
+        let left = cmp::min(self.left + thickness.left, self.right);
+        let top = cmp::min(self.top + thickness.top, self.bottom);
+
+        Rect {
+            left,
+            top,
+            right: cmp::max(self.right - thickness.right, left),
+            bottom: cmp::max(self.bottom - thickness.bottom, top),
+        }
+    }
+}
+
+/// Spacing along each edge of a rectangle, used for margins and padding
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Thickness {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Thickness {
+    pub fn new(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Thickness {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    pub fn uniform(size: i32) -> Self {
+        Thickness::new(size, size, size, size)
+    }
+
+    pub fn symmetric(horizontal: i32, vertical: i32) -> Self {
+        Thickness::new(horizontal, vertical, horizontal, vertical)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rect, Thickness};
+
+    #[test]
+    fn constructors() {
+        let rect = Rect::from_origin_size(-100, 50, 300, 200);
+
+        assert_eq!(Rect::from_edges(-100, 50, 200, 250), rect);
+        assert_eq!(300, rect.width());
+        assert_eq!(200, rect.height());
+        assert_eq!((-100, 50), rect.origin());
+        assert_eq!((50, 150), rect.center());
+    }
+
+    #[test]
+    fn contains_point() {
+        let rect = Rect::from_edges(0, 0, 10, 10);
+
+        assert!(rect.contains_point((0, 0)));
+        assert!(rect.contains_point((9, 9)));
+        assert!(!rect.contains_point((10, 9)));
+        assert!(!rect.contains_point((-1, 0)));
+    }
+
+    #[test]
+    fn intersection() {
+        let a = Rect::from_edges(0, 0, 10, 10);
+        let b = Rect::from_edges(5, -5, 20, 5);
+
+        assert_eq!(Some(Rect::from_edges(5, 0, 10, 5)), a.intersection(&b));
+        assert_eq!(None, a.intersection(&Rect::from_edges(10, 0, 20, 10)));
+        assert_eq!(25, a.intersection(&b).unwrap().area());
+    }
+
+    #[test]
+    fn inset() {
+        let rect = Rect::from_edges(0, 0, 100, 50);
+
+        assert_eq!(Rect::from_edges(10, 5, 90, 45), rect.inset(&Thickness::symmetric(10, 5)));
+        assert_eq!(Rect::from_edges(1, 2, 97, 46), rect.inset(&Thickness::new(1, 2, 3, 4)));
+    }
+
+    #[test]
+    fn inset_collapses() {
+        let rect = Rect::from_edges(0, 0, 100, 50);
+        let inset = rect.inset(&Thickness::uniform(60));
+
+        assert!(inset.is_empty());
+        assert!(rect.contains(&inset));
+        assert_eq!(0, inset.area());
+    }
+}
@@ -0,0 +1,215 @@
+use {calc_window_pos, Layout, HorizontalAlignment, Rect, Size, Thickness, VerticalAlignment};
+
+/// Predefined window positions within a monitor work area
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Tile {
+    LeftHalf,
+    RightHalf,
+    LeftThird,
+    CenterThird,
+    RightThird,
+    TopLeftQuarter,
+    TopRightQuarter,
+    BottomLeftQuarter,
+    BottomRightQuarter,
+    /// Centered at `TilingOptions::center_size`
+    Center,
+    /// Fills the work area without changing the window's maximized state
+    Maximize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TilingOptions {
+    /// Size of `Tile::Center`, shrunk to fit smaller work areas
+    pub center_size: Size,
+}
+
+impl Default for TilingOptions {
+    fn default() -> Self {
+        TilingOptions {
+            center_size: (1280, 800),
+        }
+    }
+}
+
+impl Tile {
+    pub fn layout(&self, work_area: Rect, options: &TilingOptions) -> Layout {
+        match *self {
+            Tile::LeftHalf => cell(work_area, (2, 1), (0, 0)),
+            Tile::RightHalf => cell(work_area, (2, 1), (1, 0)),
+            Tile::LeftThird => cell(work_area, (3, 1), (0, 0)),
+            Tile::CenterThird => cell(work_area, (3, 1), (1, 0)),
+            Tile::RightThird => cell(work_area, (3, 1), (2, 0)),
+            Tile::TopLeftQuarter => cell(work_area, (2, 2), (0, 0)),
+            Tile::TopRightQuarter => cell(work_area, (2, 2), (1, 0)),
+            Tile::BottomLeftQuarter => cell(work_area, (2, 2), (0, 1)),
+            Tile::BottomRightQuarter => cell(work_area, (2, 2), (1, 1)),
+            Tile::Center => {
+                let (width, height) = options.center_size;
+
+                Layout {
+                    width: Some(width),
+                    height: Some(height),
+                    horizontal: HorizontalAlignment::Center,
+                    vertical: VerticalAlignment::Center,
+                    ..Layout::default()
+                }
+            },
+            Tile::Maximize => Layout::default(),
+        }
+    }
+}
+
+/// Screen bounds of `tile` on a monitor with the given work area
+pub fn tile_bounds(tile: Tile, work_area: Rect, options: &TilingOptions) -> Rect {
+    calc_window_pos(work_area, &tile.layout(work_area, options)).bounds
+}
+
+/// Layout for one cell of a `columns` x `rows` grid
+///
+/// Cell edges are rounded down so that neighbouring cells share an edge and the
+/// last cell absorbs the remainder, leaving no gaps on odd-sized work areas.
+fn cell(work_area: Rect, grid: (i32, i32), position: (i32, i32)) -> Layout {
+    let (columns, rows) = grid;
+    let (column, row) = position;
+    let (w, h) = (work_area.width(), work_area.height());
+
+    let edge = |size: i32, count: i32, index: i32| (size as i64 * index as i64 / count as i64) as i32;
+
+    let margin = Thickness::new(
+        edge(w, columns, column),
+        edge(h, rows, row),
+        w - edge(w, columns, column + 1),
+        h - edge(h, rows, row + 1));
+
+    Layout {
+        margin,
+        horizontal: HorizontalAlignment::Left,
+        vertical: VerticalAlignment::Top,
+        ..Layout::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Rect;
+    use super::{tile_bounds, Tile, TilingOptions};
+
+    const ALL: [Tile; 11] = [
+        Tile::LeftHalf,
+        Tile::RightHalf,
+        Tile::LeftThird,
+        Tile::CenterThird,
+        Tile::RightThird,
+        Tile::TopLeftQuarter,
+        Tile::TopRightQuarter,
+        Tile::BottomLeftQuarter,
+        Tile::BottomRightQuarter,
+        Tile::Center,
+        Tile::Maximize,
+    ];
+
+    /// Work areas of common monitor setups, each with the taskbar docked somewhere
+    fn work_areas() -> Vec<Rect> {
+        vec![
+            // 1080p primary, taskbar at the bottom
+            Rect::from_edges(0, 0, 1920, 1040),
+            // Secondary 1440p left of the primary
+            Rect::from_edges(-2560, -360, 0, 1080),
+            // Portrait monitor above the primary, taskbar on the left
+            Rect::from_edges(62, -1920, 1080, 0),
+            // Odd-sized laptop panel to the right, with scaling
+            Rect::from_edges(1920, 0, 3287, 727),
+        ]
+    }
+
+    fn tile(tile: Tile, work_area: Rect) -> Rect {
+        tile_bounds(tile, work_area, &TilingOptions::default())
+    }
+
+    #[test]
+    fn halves() {
+        let work_area = Rect::from_edges(0, 0, 1920, 1040);
+
+        assert_eq!(Rect::from_edges(0, 0, 960, 1040), tile(Tile::LeftHalf, work_area));
+        assert_eq!(Rect::from_edges(960, 0, 1920, 1040), tile(Tile::RightHalf, work_area));
+    }
+
+    #[test]
+    fn thirds() {
+        let work_area = Rect::from_edges(-2560, -360, 0, 1080);
+
+        assert_eq!(Rect::from_edges(-2560, -360, -1707, 1080), tile(Tile::LeftThird, work_area));
+        assert_eq!(Rect::from_edges(-1707, -360, -854, 1080), tile(Tile::CenterThird, work_area));
+        assert_eq!(Rect::from_edges(-854, -360, 0, 1080), tile(Tile::RightThird, work_area));
+    }
+
+    #[test]
+    fn quarters() {
+        let work_area = Rect::from_edges(62, -1920, 1080, 0);
+
+        assert_eq!(Rect::from_edges(62, -1920, 571, -960), tile(Tile::TopLeftQuarter, work_area));
+        assert_eq!(Rect::from_edges(571, -1920, 1080, -960), tile(Tile::TopRightQuarter, work_area));
+        assert_eq!(Rect::from_edges(62, -960, 571, 0), tile(Tile::BottomLeftQuarter, work_area));
+        assert_eq!(Rect::from_edges(571, -960, 1080, 0), tile(Tile::BottomRightQuarter, work_area));
+    }
+
+    #[test]
+    fn center() {
+        let work_area = Rect::from_edges(0, 0, 1920, 1040);
+        let options = TilingOptions { center_size: (1000, 600) };
+
+        assert_eq!(Rect::from_edges(460, 220, 1460, 820), tile_bounds(Tile::Center, work_area, &options));
+    }
+
+    #[test]
+    fn center_larger_than_work_area() {
+        let work_area = Rect::from_edges(1920, 0, 3287, 727);
+
+        assert_eq!(Rect::from_edges(1963, 0, 3243, 727), tile(Tile::Center, work_area));
+    }
+
+    #[test]
+    fn maximize() {
+        for work_area in work_areas() {
+            assert_eq!(work_area, tile(Tile::Maximize, work_area));
+        }
+    }
+
+    #[test]
+    fn inside_work_area() {
+        for work_area in work_areas() {
+            for &t in ALL.iter() {
+                let bounds = tile(t, work_area);
+
+                assert!(work_area.contains(&bounds), "{:?} {:?} {:?}", t, work_area, bounds);
+                assert!(!bounds.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn cells_cover_work_area() {
+        let grids: [&[Tile]; 3] = [
+            &[Tile::LeftHalf, Tile::RightHalf],
+            &[Tile::LeftThird, Tile::CenterThird, Tile::RightThird],
+            &[Tile::TopLeftQuarter, Tile::TopRightQuarter, Tile::BottomLeftQuarter, Tile::BottomRightQuarter],
+        ];
+
+        for work_area in work_areas() {
+            for grid in grids.iter() {
+                let cells: Vec<Rect> = grid.iter().map(|&t| tile(t, work_area)).collect();
+
+                // No overlap and no gaps
+                let area: i64 = cells.iter().map(|c| c.area()).sum();
+                assert_eq!(work_area.area(), area);
+
+                for (i, a) in cells.iter().enumerate() {
+                    for b in cells.iter().skip(i + 1) {
+                        assert_eq!(None, a.intersection(b));
+                    }
+                }
+            }
+        }
+    }
+}
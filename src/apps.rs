@@ -0,0 +1,294 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use fuzzy;
+
+use sources::{self, ResultAction, ResultSource, SearchResult, SourceOptions};
+
+/// Files that start a program when opened, `.desktop` entries are read separately
+const EXTENSIONS: [&'static str; 4] = ["lnk", "url", "exe", "appref-ms"];
+
+/// Folders nested deeper than this below an app directory are skipped
+const MAX_DEPTH: usize = 4;
+
+/// Directories scanned longer ago than this are scanned again when asked, to
+/// find programs installed since
+const MAX_INDEX_AGE_SECS: u64 = 300;
+
+/// Where to look for programs, from the `[apps]` section of `winman.ini`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppOptions {
+    /// Index the Start Menu of the user and of all users
+    pub start_menu: bool,
+    pub dirs: Vec<PathBuf>,
+}
+
+impl Default for AppOptions {
+    fn default() -> Self {
+        AppOptions {
+            start_menu: true,
+            dirs: Vec::new(),
+        }
+    }
+}
+
+impl AppOptions {
+    /// Directories to index, Start Menu folders first
+    pub fn dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = if self.start_menu { start_menu_dirs() } else { Vec::new() };
+        dirs.extend(self.dirs.iter().cloned());
+        dirs
+    }
+}
+
+/// A program the popup can start
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct App {
+    pub name: String,
+    /// Shortcut, executable or desktop entry, opened to start the program
+    pub path: PathBuf,
+}
+
+/// Programs found in a list of directories and their subdirectories
+///
+/// Shortcuts and executables are named after their file, desktop entries after
+/// their `Name` key. Uninstallers are left out.
+pub struct AppIndex {
+    dirs: Vec<PathBuf>,
+    apps: Vec<App>,
+    /// When `dirs` were last scanned
+    scanned: Instant,
+}
+
+impl AppIndex {
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        let mut index = AppIndex {
+            dirs: dirs,
+            apps: Vec::new(),
+            scanned: Instant::now(),
+        };
+
+        index.refresh(Instant::now());
+        index
+    }
+
+    /// Scans `dirs`, which may be the same ones again after programs were installed
+    pub fn set_dirs(&mut self, dirs: Vec<PathBuf>) {
+        self.dirs = dirs;
+        self.refresh(Instant::now());
+    }
+
+    /// Scans the directories again if they were scanned `MAX_INDEX_AGE_SECS` or
+    /// longer ago, returns whether it did
+    pub fn refresh_if_stale(&mut self, now: Instant) -> bool {
+        if now.duration_since(self.scanned) < Duration::from_secs(MAX_INDEX_AGE_SECS) {
+            return false;
+        }
+
+        self.refresh(now);
+        true
+    }
+
+    fn refresh(&mut self, now: Instant) {
+        let mut apps = Vec::new();
+        for dir in &self.dirs {
+            scan(dir, 0, &mut apps);
+        }
+
+        // The same shortcut is often in the Start Menu of the user and of all users
+        apps.sort_by_key(|app| app.name.to_lowercase());
+        apps.dedup_by_key(|app| app.name.to_lowercase());
+
+        self.apps = apps;
+        self.scanned = now;
+    }
+
+    pub fn apps(&self) -> &[App] {
+        &self.apps
+    }
+}
+
+impl ResultSource for AppIndex {
+    fn name(&self) -> &'static str {
+        "apps"
+    }
+
+    fn default_options(&self) -> SourceOptions {
+        SourceOptions::new(1, "")
+    }
+
+    fn search(&self, query: &str) -> Vec<SearchResult> {
+        // Names are matched without their spaces
+        let query: String = query.split_whitespace().collect();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        self.apps.iter()
+            .map(|app| (sources::score(fuzzy::fuzzy_match(&query, &app.name)), app))
+            .filter(|&(score, _)| score > 0)
+            .map(|(score, app)| SearchResult {
+                label: app.name.clone(),
+                subtitle: app.path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+                icon: format!("file:{}", app.path.display()),
+                score: score,
+                action: ResultAction::Open(app.path.clone()),
+            })
+            .collect()
+    }
+}
+
+/// Programs folders of the Start Menu
+pub fn start_menu_dirs() -> Vec<PathBuf> {
+    ["APPDATA", "ProgramData"].iter()
+        .filter_map(|var| env::var_os(var))
+        .map(|dir| PathBuf::from(dir).join("Microsoft").join("Windows").join("Start Menu").join("Programs"))
+        .collect()
+}
+
+fn scan(dir: &Path, depth: usize, apps: &mut Vec<App>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            debug!("Could not read {}: {}", dir.display(), err);
+            return;
+        }
+    };
+
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+
+        if path.is_dir() {
+            if depth < MAX_DEPTH {
+                scan(&path, depth + 1, apps);
+            }
+            continue;
+        }
+
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+
+        let name = match extension.as_ref().map(String::as_str) {
+            Some("desktop") => fs::read_to_string(&path).ok().and_then(|text| desktop_entry_name(&text)),
+            Some(extension) if EXTENSIONS.contains(&extension) => {
+                path.file_stem().and_then(|stem| stem.to_str()).map(String::from)
+            },
+            _ => None
+        };
+
+        match name {
+            Some(ref name) if name.to_lowercase().starts_with("uninstall") => {},
+            Some(name) => apps.push(App { name: name, path: path }),
+            None => {}
+        }
+    }
+}
+
+/// Name of a freedesktop.org desktop entry that is shown in menus
+fn desktop_entry_name(text: &str) -> Option<String> {
+    let mut in_entry = false;
+    let mut name = None;
+    let mut application = false;
+
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+            continue;
+        }
+
+        if !in_entry {
+            continue;
+        }
+
+        let mut parts = line.splitn(2, '=');
+        match (parts.next().map(str::trim), parts.next().map(str::trim)) {
+            (Some("Name"), Some(value)) => name = Some(value.to_string()),
+            (Some("Type"), Some(value)) => application = value == "Application",
+            (Some("NoDisplay"), Some("true")) | (Some("Hidden"), Some("true")) => return None,
+            _ => {}
+        }
+    }
+
+    if application { name } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use std::time::{Duration, Instant};
+
+    use sources::{ResultAction, ResultSource};
+    use super::{desktop_entry_name, AppIndex, MAX_INDEX_AGE_SECS};
+
+    fn best(index: &AppIndex, query: &str) -> Option<String> {
+        let mut results = index.search(query);
+        results.sort_by(|a, b| b.score.cmp(&a.score));
+        results.into_iter().next().map(|result| result.label)
+    }
+
+    fn fixture() -> PathBuf {
+        let dir = env::temp_dir().join(format!("winman-apps-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        for &(path, text) in [
+            ("Code.lnk", ""),
+            ("Tools/Git Bash.lnk", ""),
+            ("Tools/Uninstall Git.lnk", ""),
+            ("Tools/readme.txt", ""),
+            ("Games/Deep/Deeper/Deepest/Too Deep/Hidden.lnk", ""),
+            ("gimp.desktop", "[Desktop Entry]\nType=Application\nName=GNU Image Manipulation Program\n"),
+            ("helper.desktop", "[Desktop Entry]\nType=Application\nName=Helper\nNoDisplay=true\n"),
+        ].iter() {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn index() {
+        let dir = fixture();
+        let mut index = AppIndex::new(vec![dir.clone(), dir.join("Tools"), dir.join("missing")]);
+
+        let names: Vec<&str> = index.apps().iter().map(|app| app.name.as_str()).collect();
+        assert_eq!(vec!["Code", "Git Bash", "GNU Image Manipulation Program"], names);
+        assert_eq!(dir.join("Code.lnk"), index.apps()[0].path);
+
+        assert_eq!(Some("Git Bash".to_string()), best(&index, "git"));
+        assert_eq!(Some("GNU Image Manipulation Program".to_string()), best(&index, "gnu image"));
+        assert_eq!(None, best(&index, ""));
+        assert_eq!(None, best(&index, "xyzzy"));
+        assert_eq!(ResultAction::Open(dir.join("Code.lnk")), index.search("code")[0].action);
+        assert_eq!("Code.lnk", index.search("code")[0].subtitle);
+
+        fs::write(dir.join("Notepad.exe"), "").unwrap();
+        index.set_dirs(vec![dir.clone()]);
+        assert_eq!(Some("Notepad".to_string()), best(&index, "note"));
+
+        // Scanned again only once the index is stale
+        fs::write(dir.join("Paint.exe"), "").unwrap();
+        assert!(!index.refresh_if_stale(Instant::now()));
+        assert_eq!(None, best(&index, "paint"));
+        assert!(index.refresh_if_stale(Instant::now() + Duration::from_secs(MAX_INDEX_AGE_SECS)));
+        assert_eq!(Some("Paint".to_string()), best(&index, "paint"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn desktop_entries() {
+        assert_eq!(Some("Files".to_string()), desktop_entry_name("[Desktop Entry]\nName=Files\nType=Application"));
+        assert_eq!(None, desktop_entry_name("[Desktop Entry]\nName=Docs\nType=Link"));
+        assert_eq!(None, desktop_entry_name("[Desktop Entry]\nType=Application\nName=Tray\nHidden=true"));
+        assert_eq!(Some("Files".to_string()),
+            desktop_entry_name("[Desktop Entry]\nType=Application\nName=Files\n[Desktop Action New]\nName=New Window"));
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::sync::mpsc;
+
+use winapi::windef::HWND;
+use winapi::winerror::{ERROR_FILE_NOT_FOUND, ERROR_INVALID_WINDOW_HANDLE, ERROR_NOT_FOUND};
+
+use backend::{DesktopBackend, DesktopId, ShowState, WindowBackend, WindowEvent, WindowIdentity, WindowPlacement};
+use eligibility::WindowAttributes;
+use error::Error;
+use launcher::LaunchCommand;
+use layout::Rect;
+use layout::monitor::Monitor;
+use titles::WindowTitle;
+use utils::Win32Result;
+
+pub fn hwnd(id: usize) -> HWND {
+    id as HWND
+}
+
+pub fn desktop(id: u8) -> DesktopId {
+    DesktopId([id; 16])
+}
+
+pub fn monitor(left: i32, top: i32, right: i32, bottom: i32, primary: bool) -> Monitor {
+    Monitor {
+        bounds: Rect::from_edges(left, top, right, bottom),
+        work_area: Rect::from_edges(left, top, right, bottom - 40),
+        dpi: 96,
+        primary: primary,
+    }
+}
+
+pub fn identity(exe: &str, class: &str, title: &str) -> WindowIdentity {
+    WindowIdentity {
+        exe: exe.to_string(),
+        class: class.to_string(),
+        title: WindowTitle::new(title.to_string()),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FakeWindow {
+    pub hwnd: HWND,
+    pub identity: WindowIdentity,
+    pub bounds: Rect,
+    pub state: ShowState,
+    pub pid: u32,
+    pub visible: bool,
+    pub cloaked: bool,
+    pub owned: bool,
+    pub tool_window: bool,
+}
+
+/// In-memory desktop for tests
+///
+/// Windows are kept in z-order, topmost first. Once `watch_windows` was called,
+/// changes made through the helpers below are reported as events, and tests can
+/// `emit` any other sequence.
+#[derive(Default)]
+pub struct FakeBackend {
+    pub monitors: Vec<Monitor>,
+    pub windows: Vec<FakeWindow>,
+    pub foreground: Option<HWND>,
+    /// Commands passed to `spawn`, in order
+    pub spawned: Vec<LaunchCommand>,
+    pub last_pid: u32,
+    pub fail_spawn: bool,
+    events: Option<mpsc::Sender<WindowEvent>>,
+}
+
+impl FakeBackend {
+    pub fn new(monitors: Vec<Monitor>) -> Self {
+        FakeBackend {
+            monitors: monitors,
+            ..FakeBackend::default()
+        }
+    }
+
+    pub fn add_window(&mut self, id: usize, title: &str, bounds: Rect) -> HWND {
+        self.add_identified_window(id, identity("app.exe", "AppWindow", title), bounds)
+    }
+
+    pub fn add_identified_window(&mut self, id: usize, identity: WindowIdentity, bounds: Rect) -> HWND {
+        let hwnd = hwnd(id);
+
+        self.windows.push(FakeWindow {
+            hwnd: hwnd,
+            identity: identity,
+            bounds: bounds,
+            state: ShowState::Normal,
+            pid: 0,
+            visible: true,
+            cloaked: false,
+            owned: false,
+            tool_window: false,
+        });
+
+        self.emit(WindowEvent::Created(hwnd));
+        hwnd
+    }
+
+    pub fn close_window(&mut self, hwnd: HWND) {
+        self.windows.retain(|w| w.hwnd != hwnd);
+
+        if self.foreground == Some(hwnd) {
+            self.foreground = None;
+        }
+
+        self.emit(WindowEvent::Destroyed(hwnd));
+    }
+
+    pub fn set_title(&mut self, hwnd: HWND, title: &str) {
+        if let Ok(window) = self.window_mut(hwnd) {
+            window.identity.title = WindowTitle::new(title.to_string());
+        }
+
+        self.emit(WindowEvent::TitleChanged(hwnd));
+    }
+
+    /// Shows or hides a window, reporting it like Windows does
+    pub fn set_visible(&mut self, hwnd: HWND, visible: bool) {
+        if let Ok(window) = self.window_mut(hwnd) {
+            window.visible = visible;
+        }
+
+        self.emit(if visible { WindowEvent::Created(hwnd) } else { WindowEvent::Hidden(hwnd) });
+    }
+
+    /// Sends `event` to the subscriber, if any
+    pub fn emit(&self, event: WindowEvent) {
+        if let Some(ref tx) = self.events {
+            let _ = tx.send(event);
+        }
+    }
+
+    pub fn window(&self, hwnd: HWND) -> Option<&FakeWindow> {
+        self.windows.iter().find(|w| w.hwnd == hwnd)
+    }
+
+    pub fn window_mut(&mut self, hwnd: HWND) -> Win32Result<&mut FakeWindow> {
+        self.windows
+            .iter_mut()
+            .find(|w| w.hwnd == hwnd)
+            .ok_or(Error::Win32(ERROR_INVALID_WINDOW_HANDLE))
+    }
+}
+
+impl WindowBackend for FakeBackend {
+    fn monitors(&self) -> Vec<Monitor> {
+        self.monitors.clone()
+    }
+
+    fn foreground_window(&self) -> Option<HWND> {
+        self.foreground
+    }
+
+    fn windows(&self) -> Vec<HWND> {
+        self.windows.iter().map(|w| w.hwnd).collect()
+    }
+
+    fn window_attributes(&self, hwnd: HWND) -> Win32Result<WindowAttributes> {
+        self.window(hwnd)
+            .map(|w| WindowAttributes {
+                title: w.identity.title.clone(),
+                class: w.identity.class.clone(),
+                process_id: w.pid,
+                visible: w.visible,
+                cloaked: w.cloaked,
+                owned: w.owned,
+                tool_window: w.tool_window,
+                app_window: false,
+            })
+            .ok_or(Error::Win32(ERROR_INVALID_WINDOW_HANDLE))
+    }
+
+    fn window_identity(&self, hwnd: HWND) -> Win32Result<WindowIdentity> {
+        self.window(hwnd)
+            .map(|w| w.identity.clone())
+            .ok_or(Error::Win32(ERROR_INVALID_WINDOW_HANDLE))
+    }
+
+    fn window_process_id(&self, hwnd: HWND) -> Win32Result<u32> {
+        self.window(hwnd)
+            .map(|w| w.pid)
+            .ok_or(Error::Win32(ERROR_INVALID_WINDOW_HANDLE))
+    }
+
+    fn focus_window(&mut self, hwnd: HWND) -> Win32Result<()> {
+        let window = try!{ self.window_mut(hwnd) };
+        if window.state == ShowState::Minimized {
+            window.state = ShowState::Normal;
+        }
+
+        self.foreground = Some(hwnd);
+        self.emit(WindowEvent::Foreground(hwnd));
+        Ok(())
+    }
+
+    fn window_bounds(&self, hwnd: HWND) -> Win32Result<Rect> {
+        self.window(hwnd)
+            .map(|w| w.bounds)
+            .ok_or(Error::Win32(ERROR_INVALID_WINDOW_HANDLE))
+    }
+
+    fn set_window_bounds(&mut self, hwnd: HWND, bounds: Rect) -> Win32Result<()> {
+        let window = try!{ self.window_mut(hwnd) };
+        window.bounds = bounds;
+        window.state = ShowState::Normal;
+
+        Ok(())
+    }
+
+    fn window_placement(&self, hwnd: HWND) -> Win32Result<WindowPlacement> {
+        self.window(hwnd)
+            .map(|w| WindowPlacement { bounds: w.bounds, state: w.state })
+            .ok_or(Error::Win32(ERROR_INVALID_WINDOW_HANDLE))
+    }
+
+    fn set_window_placement(&mut self, hwnd: HWND, placement: &WindowPlacement) -> Win32Result<()> {
+        let window = try!{ self.window_mut(hwnd) };
+        window.bounds = placement.bounds;
+        window.state = placement.state;
+
+        Ok(())
+    }
+
+    fn spawn(&mut self, command: &LaunchCommand) -> Win32Result<u32> {
+        if self.fail_spawn {
+            return Err(Error::Win32(ERROR_FILE_NOT_FOUND));
+        }
+
+        self.spawned.push(command.clone());
+        self.last_pid += 1000;
+
+        Ok(self.last_pid)
+    }
+
+    fn watch_windows(&mut self) -> Win32Result<mpsc::Receiver<WindowEvent>> {
+        let (tx, rx) = mpsc::channel();
+        self.events = Some(tx);
+
+        Ok(rx)
+    }
+}
+
+/// Virtual desktops for tests
+///
+/// Starts on the first desktop. Windows are on the desktop given to `add_window`.
+/// The current desktop can not be told while `unknown` is set.
+pub struct FakeDesktops {
+    pub desktops: Vec<DesktopId>,
+    pub current: DesktopId,
+    pub unknown: bool,
+    pub windows: HashMap<HWND, DesktopId>,
+}
+
+impl FakeDesktops {
+    /// Desktops `desktop(1)` to `desktop(count)`
+    pub fn new(count: u8) -> Self {
+        FakeDesktops {
+            desktops: (1..count + 1).map(desktop).collect(),
+            current: desktop(1),
+            unknown: false,
+            windows: HashMap::new(),
+        }
+    }
+
+    pub fn add_window(&mut self, hwnd: HWND, desktop: DesktopId) {
+        self.windows.insert(hwnd, desktop);
+    }
+}
+
+impl DesktopBackend for FakeDesktops {
+    fn desktops(&self) -> Win32Result<Vec<DesktopId>> {
+        Ok(self.desktops.clone())
+    }
+
+    fn current_desktop(&self) -> Win32Result<DesktopId> {
+        if self.unknown {
+            return Err(Error::Win32(ERROR_NOT_FOUND));
+        }
+
+        Ok(self.current)
+    }
+
+    fn window_desktop(&self, hwnd: HWND) -> Win32Result<DesktopId> {
+        self.windows.get(&hwnd).cloned().ok_or(Error::Win32(ERROR_INVALID_WINDOW_HANDLE))
+    }
+
+    fn switch_desktop(&mut self, desktop: DesktopId) -> Win32Result<()> {
+        if !self.desktops.contains(&desktop) {
+            return Err(Error::Win32(ERROR_NOT_FOUND));
+        }
+
+        self.current = desktop;
+        Ok(())
+    }
+}
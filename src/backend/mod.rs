@@ -0,0 +1,132 @@
+use std::path::Path;
+use std::sync::mpsc;
+
+use winapi::windef::HWND;
+
+use eligibility::WindowAttributes;
+use launcher::LaunchCommand;
+use layout::Rect;
+use layout::monitor::Monitor;
+use titles::WindowTitle;
+use tray::{MenuItem, TrayCommand};
+use utils::Win32Result;
+
+pub use self::win32::{Win32Backend, Win32Desktops, Win32Tray};
+
+#[cfg(test)]
+pub mod fake;
+mod win32;
+
+/// Attributes that recognize a window across restarts of its application
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WindowIdentity {
+    /// File name of the owning executable, e.g. `Code.exe`
+    pub exe: String,
+    pub class: String,
+    pub title: WindowTitle,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ShowState {
+    Normal,
+    Minimized,
+    Maximized,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WindowPlacement {
+    /// Bounds of the window when it is neither minimized nor maximized,
+    /// in screen coordinates
+    pub bounds: Rect,
+    pub state: ShowState,
+}
+
+/// A virtual desktop, the bytes of its GUID
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct DesktopId(pub [u8; 16]);
+
+/// Changes to top-level windows reported by `WindowBackend::watch_windows`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WindowEvent {
+    /// Sent when a window is created and again when it is shown, since most
+    /// windows get their title and styles in between
+    Created(HWND),
+    /// The window may be shown again, which is reported as `Created`
+    Hidden(HWND),
+    Destroyed(HWND),
+    Foreground(HWND),
+    TitleChanged(HWND),
+}
+
+/// Operations WinMan performs on other applications' windows
+///
+/// Everything that queries or moves top-level windows goes through this trait so
+/// that window management logic can be tested without a desktop.
+pub trait WindowBackend {
+    fn monitors(&self) -> Vec<Monitor>;
+
+    fn foreground_window(&self) -> Option<HWND>;
+
+    /// All top-level windows in z-order, topmost first, including hidden ones
+    ///
+    /// Use `eligibility::switchable_windows` for the windows a user can switch to.
+    fn windows(&self) -> Vec<HWND>;
+
+    fn window_attributes(&self, hwnd: HWND) -> Win32Result<WindowAttributes>;
+
+    fn window_identity(&self, hwnd: HWND) -> Win32Result<WindowIdentity>;
+
+    fn window_process_id(&self, hwnd: HWND) -> Win32Result<u32>;
+
+    /// Restores a window if needed and brings it to the foreground
+    fn focus_window(&mut self, hwnd: HWND) -> Win32Result<()>;
+
+    /// Outer bounds of a window in screen coordinates
+    fn window_bounds(&self, hwnd: HWND) -> Win32Result<Rect>;
+
+    /// Moves and resizes a window, restoring it first if it is minimized or maximized
+    fn set_window_bounds(&mut self, hwnd: HWND, bounds: Rect) -> Win32Result<()>;
+
+    fn window_placement(&self, hwnd: HWND) -> Win32Result<WindowPlacement>;
+
+    fn set_window_placement(&mut self, hwnd: HWND, placement: &WindowPlacement) -> Win32Result<()>;
+
+    /// Starts a program and returns its process id
+    fn spawn(&mut self, command: &LaunchCommand) -> Win32Result<u32>;
+
+    /// Subscribes to window events of other processes
+    ///
+    /// Events are sent while the thread that subscribed pumps messages. Handles
+    /// are not `Send`, so unlike the UI messages this uses a std channel.
+    fn watch_windows(&mut self) -> Win32Result<mpsc::Receiver<WindowEvent>>;
+}
+
+/// Virtual desktops of Windows 10 and later
+pub trait DesktopBackend {
+    /// All desktops in the order of the task view
+    fn desktops(&self) -> Win32Result<Vec<DesktopId>>;
+
+    /// Desktop shown right now
+    fn current_desktop(&self) -> Win32Result<DesktopId>;
+
+    fn window_desktop(&self, hwnd: HWND) -> Win32Result<DesktopId>;
+
+    fn switch_desktop(&mut self, desktop: DesktopId) -> Win32Result<()>;
+}
+
+/// WinMan's own presence in the notification area
+pub trait TrayBackend {
+    /// Adds the tray icon, or updates its tooltip when it is already shown
+    fn show_icon(&mut self, tooltip: &str) -> Win32Result<()>;
+
+    fn remove_icon(&mut self);
+
+    /// Shows an error balloon next to the icon
+    fn notify_error(&mut self, title: &str, message: &str) -> Win32Result<()>;
+
+    /// Shows `menu` at the mouse cursor and waits for the user to pick an item
+    fn show_menu(&mut self, menu: &[MenuItem]) -> Option<TrayCommand>;
+
+    /// Opens a file in the program registered for its type
+    fn open_file(&mut self, path: &Path) -> Win32Result<()>;
+}
@@ -0,0 +1,693 @@
+use kernel32;
+use shell32;
+use user32;
+use winapi::*;
+
+use std::cell::RefCell;
+use std::mem;
+use std::ffi::OsStr;
+use std::os::windows::ffi::OsStrExt;
+use std::os::windows::process::CommandExt;
+use std::path::Path;
+use std::process::Command;
+use std::ptr;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use backend::{DesktopBackend, DesktopId, ShowState, TrayBackend, WindowBackend, WindowEvent, WindowIdentity, WindowPlacement};
+use eligibility::WindowAttributes;
+use error::Error;
+use launcher::LaunchCommand;
+use layout::Rect;
+use layout::monitor::{self, Monitor};
+use tray::{MenuItem, TrayCommand};
+use utils;
+use utils::api_wrappers;
+use utils::Win32Result;
+use titles;
+use window_tracking;
+use windows;
+
+// Not covered by winapi 0.2
+const EVENT_SYSTEM_FOREGROUND: DWORD = 0x0003;
+const EVENT_OBJECT_CREATE: DWORD = 0x8000;
+const EVENT_OBJECT_DESTROY: DWORD = 0x8001;
+const EVENT_OBJECT_SHOW: DWORD = 0x8002;
+const EVENT_OBJECT_HIDE: DWORD = 0x8003;
+const EVENT_OBJECT_NAMECHANGE: DWORD = 0x800C;
+const WINEVENT_OUTOFCONTEXT: DWORD = 0x0000;
+const WINEVENT_SKIPOWNPROCESS: DWORD = 0x0002;
+const OBJID_WINDOW: LONG = 0;
+const CHILDID_SELF: LONG = 0;
+const GA_ROOT: UINT = 2;
+const NIM_ADD: DWORD = 0x0000;
+const NIM_MODIFY: DWORD = 0x0001;
+const NIM_DELETE: DWORD = 0x0002;
+const NIF_MESSAGE: UINT = 0x0001;
+const NIF_ICON: UINT = 0x0002;
+const NIF_TIP: UINT = 0x0004;
+const NIF_INFO: UINT = 0x0010;
+const NIIF_ERROR: DWORD = 0x0003;
+const MF_STRING: UINT = 0x0000;
+const MF_GRAYED: UINT = 0x0001;
+const MF_CHECKED: UINT = 0x0008;
+const MF_POPUP: UINT = 0x0010;
+const MF_SEPARATOR: UINT = 0x0800;
+const TPM_RIGHTBUTTON: UINT = 0x0002;
+const TPM_NONOTIFY: UINT = 0x0080;
+const TPM_RETURNCMD: UINT = 0x0100;
+const IDI_APPLICATION: usize = 32512;
+
+// IVirtualDesktopManager from shobjidl_core.h
+const CLSID_VIRTUAL_DESKTOP_MANAGER: GUID = GUID {
+    Data1: 0xAA509086, Data2: 0x5CA9, Data3: 0x4C25,
+    Data4: [0x8F, 0x95, 0x58, 0x9D, 0x3C, 0x07, 0xB4, 0x8A],
+};
+const IID_IVIRTUAL_DESKTOP_MANAGER: GUID = GUID {
+    Data1: 0xA5CD92FF, Data2: 0x29BE, Data3: 0x454C,
+    Data4: [0x8D, 0x04, 0xD8, 0x28, 0x79, 0xFB, 0x3F, 0x1B],
+};
+
+// Explorer lists the desktops here once a second one was created. Windows 11
+// also keeps the shown one here, Windows 10 under the key of the session.
+const VIRTUAL_DESKTOPS_KEY: &'static str = "Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\VirtualDesktops";
+const SESSION_INFO_KEY: &'static str = "Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\SessionInfo";
+
+// Icon resource in res/winman.rc
+const IDI_ICON: usize = 0x101;
+const TRAY_ICON_ID: UINT = 1;
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct IVirtualDesktopManagerVtbl {
+    QueryInterface: unsafe extern "system" fn(*mut IVirtualDesktopManager, REFIID, *mut LPVOID) -> HRESULT,
+    AddRef: unsafe extern "system" fn(*mut IVirtualDesktopManager) -> ULONG,
+    Release: unsafe extern "system" fn(*mut IVirtualDesktopManager) -> ULONG,
+    IsWindowOnCurrentVirtualDesktop: unsafe extern "system" fn(*mut IVirtualDesktopManager, HWND, *mut BOOL) -> HRESULT,
+    GetWindowDesktopId: unsafe extern "system" fn(*mut IVirtualDesktopManager, HWND, *mut GUID) -> HRESULT,
+    MoveWindowToDesktop: unsafe extern "system" fn(*mut IVirtualDesktopManager, HWND, REFGUID) -> HRESULT,
+}
+
+#[repr(C)]
+struct IVirtualDesktopManager {
+    vtbl: *const IVirtualDesktopManagerVtbl,
+}
+
+#[link(name = "ole32")]
+extern "system" {
+    fn CoInitializeEx(pvReserved: LPVOID, dwCoInit: DWORD) -> HRESULT;
+    fn CoCreateInstance(rclsid: REFCLSID, pUnkOuter: LPVOID, dwClsContext: DWORD, riid: REFIID, ppv: *mut LPVOID) -> HRESULT;
+}
+
+#[link(name = "advapi32")]
+extern "system" {
+    fn RegGetValueW(hkey: HKEY, lpSubKey: LPCWSTR, lpValue: LPCWSTR, dwFlags: DWORD, pdwType: LPDWORD, pvData: LPVOID, pcbData: LPDWORD) -> LONG;
+}
+
+thread_local! {
+    // Out-of-context hooks call back on the thread that installed them
+    static EVENT_SENDER: RefCell<Option<mpsc::Sender<WindowEvent>>> = RefCell::new(None);
+}
+
+pub struct Win32Backend;
+
+impl WindowBackend for Win32Backend {
+    fn monitors(&self) -> Vec<Monitor> {
+        windows::get_monitors().unwrap_or(Vec::new())
+    }
+
+    fn foreground_window(&self) -> Option<HWND> {
+        match unsafe { user32::GetForegroundWindow() } {
+            hwnd if hwnd == 0 as HWND => None,
+            hwnd => Some(hwnd),
+        }
+    }
+
+    fn windows(&self) -> Vec<HWND> {
+        let mut windows = Vec::new();
+
+        let _ = api_wrappers::enum_windows(|hwnd| {
+            windows.push(hwnd);
+            TRUE
+        });
+
+        windows
+    }
+
+    fn window_attributes(&self, hwnd: HWND) -> Win32Result<WindowAttributes> {
+        let class = try!{ api_wrappers::get_class_name(hwnd) };
+        let process_id = try!{ self.window_process_id(hwnd) };
+        let ex_style = unsafe { user32::GetWindowLongW(hwnd, GWL_EXSTYLE) } as DWORD;
+
+        Ok(WindowAttributes {
+            title: window_title(hwnd),
+            class: class,
+            process_id: process_id,
+            visible: unsafe { user32::IsWindowVisible(hwnd) } != FALSE,
+            cloaked: api_wrappers::is_cloaked(hwnd).unwrap_or(false),
+            owned: unsafe { user32::GetWindow(hwnd, GW_OWNER) } != 0 as HWND,
+            tool_window: ex_style & WS_EX_TOOLWINDOW != 0,
+            app_window: ex_style & WS_EX_APPWINDOW != 0,
+        })
+    }
+
+    fn window_identity(&self, hwnd: HWND) -> Win32Result<WindowIdentity> {
+        let class = try!{ api_wrappers::get_class_name(hwnd) };
+        let exe = api_wrappers::get_process_path(hwnd)
+            .ok()
+            .and_then(|path| Path::new(&path).file_name().map(|name| name.to_string_lossy().into_owned()))
+            .unwrap_or(String::new());
+
+        Ok(WindowIdentity {
+            exe: exe,
+            class: class,
+            title: window_title(hwnd),
+        })
+    }
+
+    fn window_process_id(&self, hwnd: HWND) -> Win32Result<u32> {
+        let mut pid: DWORD = 0;
+
+        match unsafe { user32::GetWindowThreadProcessId(hwnd, &mut pid) } {
+            0 => Err(Error::last_win32()),
+            _ => Ok(pid)
+        }
+    }
+
+    fn focus_window(&mut self, hwnd: HWND) -> Win32Result<()> {
+        window_tracking::set_foreground_window(hwnd)
+    }
+
+    fn window_bounds(&self, hwnd: HWND) -> Win32Result<Rect> {
+        if unsafe { user32::IsWindow(hwnd) } == FALSE {
+            return Err(Error::Win32(ERROR_INVALID_WINDOW_HANDLE));
+        }
+
+        Ok(windows::get_window_bounds(hwnd))
+    }
+
+    fn set_window_bounds(&mut self, hwnd: HWND, bounds: Rect) -> Win32Result<()> {
+        unsafe {
+            if user32::IsZoomed(hwnd) != FALSE || user32::IsIconic(hwnd) != FALSE {
+                user32::ShowWindow(hwnd, SW_RESTORE);
+            }
+        }
+
+        // On Windows 10 the window rectangle includes an invisible resize border,
+        // grow the target by its size so the visible frame lands on `bounds`
+        let window = windows::get_window_bounds(hwnd);
+        let bounds = match api_wrappers::get_extended_frame_bounds(hwnd) {
+            Ok(frame) => Rect::from_edges(
+                bounds.left - (frame.left - window.left),
+                bounds.top - (frame.top - window.top),
+                bounds.right + (window.right - frame.right),
+                bounds.bottom + (window.bottom - frame.bottom)),
+            Err(_) => bounds,
+        };
+
+        let result = unsafe {
+            user32::SetWindowPos(
+                hwnd,
+                0 as HWND,
+                bounds.left,
+                bounds.top,
+                bounds.width(),
+                bounds.height(),
+                SWP_NOZORDER | SWP_NOACTIVATE)
+        };
+
+        match result {
+            FALSE => Err(Error::last_win32()),
+            _ => Ok(())
+        }
+    }
+
+    fn window_placement(&self, hwnd: HWND) -> Win32Result<WindowPlacement> {
+        let mut placement: WINDOWPLACEMENT = unsafe { ::std::mem::zeroed() };
+        placement.length = ::std::mem::size_of::<WINDOWPLACEMENT>() as u32;
+
+        if unsafe { user32::GetWindowPlacement(hwnd, &mut placement) } == FALSE {
+            return Err(Error::last_win32());
+        }
+
+        let state = match placement.showCmd as i32 {
+            SW_SHOWMAXIMIZED => ShowState::Maximized,
+            SW_SHOWMINIMIZED | SW_MINIMIZE | SW_SHOWMINNOACTIVE => ShowState::Minimized,
+            _ => ShowState::Normal,
+        };
+
+        let bounds = match state {
+            ShowState::Normal => windows::get_window_bounds(hwnd),
+
+            // The restore position is in workspace coordinates, which are offset
+            // from screen coordinates by any taskbar docked to the left or top
+            _ => {
+                let rect = placement.rcNormalPosition;
+                let bounds = Rect::from_edges(rect.left, rect.top, rect.right, rect.bottom);
+                let monitors = self.monitors();
+
+                match monitor::monitor_from_bounds(&monitors, bounds) {
+                    Some(m) => bounds.offset(m.work_area.left - m.bounds.left, m.work_area.top - m.bounds.top),
+                    None => bounds,
+                }
+            }
+        };
+
+        Ok(WindowPlacement {
+            bounds: bounds,
+            state: state,
+        })
+    }
+
+    fn set_window_placement(&mut self, hwnd: HWND, placement: &WindowPlacement) -> Win32Result<()> {
+        try!{ self.set_window_bounds(hwnd, placement.bounds) };
+
+        let sw = match placement.state {
+            ShowState::Normal => return Ok(()),
+            ShowState::Maximized => SW_MAXIMIZE,
+            ShowState::Minimized => SW_SHOWMINNOACTIVE,
+        };
+
+        unsafe { user32::ShowWindow(hwnd, sw) };
+
+        Ok(())
+    }
+
+    fn spawn(&mut self, command: &LaunchCommand) -> Win32Result<u32> {
+        let mut process = Command::new(&command.exe);
+        process.raw_arg(&command.args);
+
+        if let Some(ref dir) = command.working_dir {
+            process.current_dir(dir);
+        }
+
+        match process.spawn() {
+            Ok(child) => Ok(child.id()),
+            Err(err) => Err(Error::Win32(err.raw_os_error().unwrap_or(0) as DWORD))
+        }
+    }
+
+    fn watch_windows(&mut self) -> Win32Result<mpsc::Receiver<WindowEvent>> {
+        let (tx, rx) = mpsc::channel();
+
+        // One hook per handled event, a range would also wake WinMan for the
+        // location, focus and selection changes of every window
+        let ranges = [
+            (EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_FOREGROUND),
+            (EVENT_OBJECT_CREATE, EVENT_OBJECT_CREATE),
+            (EVENT_OBJECT_DESTROY, EVENT_OBJECT_DESTROY),
+            (EVENT_OBJECT_SHOW, EVENT_OBJECT_HIDE),
+            (EVENT_OBJECT_NAMECHANGE, EVENT_OBJECT_NAMECHANGE),
+        ];
+
+        for &(min, max) in ranges.iter() {
+            let hook = unsafe {
+                user32::SetWinEventHook(
+                    min, max,
+                    0 as HMODULE,
+                    Some(win_event_proc),
+                    0, 0,
+                    WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS)
+            };
+
+            if hook.is_null() {
+                return Err(Error::last_win32());
+            }
+        }
+
+        EVENT_SENDER.with(|sender| *sender.borrow_mut() = Some(tx));
+
+        Ok(rx)
+    }
+}
+
+unsafe extern "system" fn win_event_proc(
+    _hook: HWINEVENTHOOK, event: DWORD, hwnd: HWND, id_object: LONG, id_child: LONG,
+    _thread: DWORD, _time: DWORD) {
+
+    if hwnd.is_null() || id_object != OBJID_WINDOW || id_child != CHILDID_SELF {
+        return;
+    }
+
+    // Destroyed windows can no longer be checked for being top-level
+    let event = match event {
+        EVENT_OBJECT_DESTROY => WindowEvent::Destroyed(hwnd),
+        _ if user32::GetAncestor(hwnd, GA_ROOT) != hwnd => return,
+        EVENT_OBJECT_CREATE | EVENT_OBJECT_SHOW => WindowEvent::Created(hwnd),
+        EVENT_OBJECT_HIDE => WindowEvent::Hidden(hwnd),
+        EVENT_SYSTEM_FOREGROUND => WindowEvent::Foreground(hwnd),
+        EVENT_OBJECT_NAMECHANGE => WindowEvent::TitleChanged(hwnd),
+        _ => return,
+    };
+
+    EVENT_SENDER.with(|sender| {
+        if let Some(ref tx) = *sender.borrow() {
+            let _ = tx.send(event);
+        }
+    });
+}
+
+/// Tray icon of the main window
+///
+/// Clicks on the icon are sent to the window as `callback_msg` with the mouse
+/// message in `lparam`.
+pub struct Win32Tray {
+    hwnd: HWND,
+    callback_msg: UINT,
+    shown: bool,
+}
+
+impl Win32Tray {
+    pub fn new(hwnd: HWND, callback_msg: UINT) -> Self {
+        Win32Tray {
+            hwnd: hwnd,
+            callback_msg: callback_msg,
+            shown: false,
+        }
+    }
+
+    fn icon_data(&self) -> NOTIFYICONDATAW {
+        let mut data: NOTIFYICONDATAW = unsafe { ::std::mem::zeroed() };
+        data.cbSize = ::std::mem::size_of::<NOTIFYICONDATAW>() as DWORD;
+        data.hWnd = self.hwnd;
+        data.uID = TRAY_ICON_ID;
+        data
+    }
+}
+
+impl TrayBackend for Win32Tray {
+    fn show_icon(&mut self, tooltip: &str) -> Win32Result<()> {
+        let mut data = self.icon_data();
+        data.uFlags = NIF_MESSAGE | NIF_ICON | NIF_TIP;
+        data.uCallbackMessage = self.callback_msg;
+        data.hIcon = load_icon();
+
+        copy_truncated(&mut data.szTip, tooltip);
+
+        let message = if self.shown { NIM_MODIFY } else { NIM_ADD };
+
+        match unsafe { shell32::Shell_NotifyIconW(message, &mut data) } {
+            FALSE => Err(Error::last_win32()),
+            _ => {
+                self.shown = true;
+                Ok(())
+            }
+        }
+    }
+
+    fn remove_icon(&mut self) {
+        if self.shown {
+            let mut data = self.icon_data();
+            unsafe { shell32::Shell_NotifyIconW(NIM_DELETE, &mut data) };
+            self.shown = false;
+        }
+    }
+
+    fn notify_error(&mut self, title: &str, message: &str) -> Win32Result<()> {
+        let mut data = self.icon_data();
+        data.uFlags = NIF_INFO;
+        data.dwInfoFlags = NIIF_ERROR;
+        copy_truncated(&mut data.szInfoTitle, title);
+        copy_truncated(&mut data.szInfo, message);
+
+        match unsafe { shell32::Shell_NotifyIconW(NIM_MODIFY, &mut data) } {
+            FALSE => Err(Error::last_win32()),
+            _ => Ok(())
+        }
+    }
+
+    fn show_menu(&mut self, menu: &[MenuItem]) -> Option<TrayCommand> {
+        unsafe {
+            let hmenu = build_menu(menu);
+            let mut cursor: POINT = ::std::mem::zeroed();
+            user32::GetCursorPos(&mut cursor);
+
+            // The menu only closes when clicking elsewhere if its owner is in the
+            // foreground, and only reopens reliably after a message was processed
+            user32::SetForegroundWindow(self.hwnd);
+            let id = user32::TrackPopupMenu(
+                hmenu,
+                TPM_RIGHTBUTTON | TPM_NONOTIFY | TPM_RETURNCMD,
+                cursor.x, cursor.y,
+                0,
+                self.hwnd,
+                ptr::null());
+            user32::PostMessageW(self.hwnd, WM_NULL, 0, 0);
+            user32::DestroyMenu(hmenu);
+
+            TrayCommand::from_id(id as UINT)
+        }
+    }
+
+    fn open_file(&mut self, path: &Path) -> Win32Result<()> {
+        let verb = utils::to_wide_chars("open");
+        let file: Vec<u16> = OsStr::new(path).encode_wide().chain(Some(0)).collect();
+
+        let result = unsafe {
+            shell32::ShellExecuteW(self.hwnd, verb.as_ptr(), file.as_ptr(), ptr::null(), ptr::null(), SW_SHOWNORMAL)
+        };
+
+        // Values up to 32 are errors
+        match result as usize {
+            0..=32 => Err(Error::last_win32()),
+            _ => Ok(())
+        }
+    }
+}
+
+impl Drop for Win32Tray {
+    fn drop(&mut self) {
+        self.remove_icon();
+    }
+}
+
+/// Virtual desktops through `IVirtualDesktopManager`
+///
+/// That interface only tells which desktop a window is on. It can not move the
+/// windows of other processes, so WinMan does not offer moving windows between
+/// desktops. The desktops and the one shown are read from Explorer's registry
+/// keys, and switching presses Ctrl+Win+Left or Right once for every desktop in
+/// between.
+pub struct Win32Desktops {
+    manager: *mut IVirtualDesktopManager,
+}
+
+impl Win32Desktops {
+    /// Fails on Windows versions without virtual desktops
+    pub fn new() -> Win32Result<Self> {
+        unsafe {
+            // S_FALSE when COM is already initialized on this thread
+            let hr = CoInitializeEx(ptr::null_mut(), COINIT_APARTMENTTHREADED);
+            if hr != RPC_E_CHANGED_MODE {
+                try!{ check_hresult(hr) };
+            }
+
+            let mut manager: LPVOID = ptr::null_mut();
+            try!{ check_hresult(CoCreateInstance(
+                &CLSID_VIRTUAL_DESKTOP_MANAGER,
+                ptr::null_mut(),
+                CLSCTX_ALL,
+                &IID_IVIRTUAL_DESKTOP_MANAGER,
+                &mut manager)) };
+
+            Ok(Win32Desktops { manager: manager as *mut IVirtualDesktopManager })
+        }
+    }
+
+    unsafe fn vtbl(&self) -> &IVirtualDesktopManagerVtbl {
+        &*(*self.manager).vtbl
+    }
+}
+
+impl DesktopBackend for Win32Desktops {
+    fn desktops(&self) -> Win32Result<Vec<DesktopId>> {
+        match try!{ read_binary_value(VIRTUAL_DESKTOPS_KEY, "VirtualDesktopIDs") } {
+            Some(ids) => Ok(ids.chunks(16).filter_map(desktop_id).collect()),
+            None => self.current_desktop().map(|desktop| vec![desktop])
+        }
+    }
+
+    fn current_desktop(&self) -> Win32Result<DesktopId> {
+        let mut session: DWORD = 0;
+        let mut keys = vec![VIRTUAL_DESKTOPS_KEY.to_string()];
+        if unsafe { kernel32::ProcessIdToSessionId(kernel32::GetCurrentProcessId(), &mut session) } != 0 {
+            keys.push(format!("{}\\{}\\VirtualDesktops", SESSION_INFO_KEY, session));
+        }
+
+        for key in &keys {
+            let id = try!{ read_binary_value(key, "CurrentVirtualDesktop") };
+            if let Some(desktop) = id.as_ref().and_then(|id| desktop_id(id)) {
+                return Ok(desktop);
+            }
+        }
+
+        // Before a second desktop was created the foreground window tells, unless it is shown on all of them
+        match unsafe { user32::GetForegroundWindow() } {
+            hwnd if hwnd == 0 as HWND => Err(Error::Win32(ERROR_NOT_FOUND)),
+            hwnd => self.window_desktop(hwnd)
+        }
+    }
+
+    fn window_desktop(&self, hwnd: HWND) -> Win32Result<DesktopId> {
+        let mut guid: GUID = unsafe { mem::zeroed() };
+        try!{ check_hresult(unsafe { (self.vtbl().GetWindowDesktopId)(self.manager, hwnd, &mut guid) }) };
+
+        // Pinned windows and ones like the taskbar have no desktop
+        match unsafe { mem::transmute::<GUID, [u8; 16]>(guid) } {
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] => Err(Error::Win32(ERROR_NOT_FOUND)),
+            id => Ok(DesktopId(id))
+        }
+    }
+
+    fn switch_desktop(&mut self, desktop: DesktopId) -> Win32Result<()> {
+        let desktops = try!{ self.desktops() };
+        let current = try!{ self.current_desktop() };
+        let position = |id| desktops.iter().position(|&other| other == id);
+
+        let (from, to) = match (position(current), position(desktop)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return Err(Error::Win32(ERROR_NOT_FOUND))
+        };
+
+        let (arrow, steps) = if to > from { (VK_RIGHT, to - from) } else { (VK_LEFT, from - to) };
+        for _ in 0..steps {
+            try!{ press_keys(&[VK_LCONTROL, VK_LWIN, arrow]) };
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Win32Desktops {
+    fn drop(&mut self) {
+        unsafe { (self.vtbl().Release)(self.manager) };
+    }
+}
+
+/// Title for attributes and identities, `Unavailable` if it can not be read
+fn window_title(hwnd: HWND) -> titles::WindowTitle {
+    api_wrappers::get_window_title(hwnd, Duration::from_millis(titles::DEFAULT_TIMEOUT_MS))
+        .unwrap_or(titles::WindowTitle::Unavailable)
+}
+
+fn check_hresult(hr: HRESULT) -> Win32Result<()> {
+    match hr {
+        hr if hr < 0 => Err(Error::Win32(hr as DWORD)),
+        _ => Ok(())
+    }
+}
+
+/// Presses `keys` down in order and releases them in reverse
+///
+/// All of it goes out in one `SendInput` so keys the user presses meanwhile can
+/// not end up in between.
+fn press_keys(keys: &[c_int]) -> Win32Result<()> {
+    let key_input = |vk: c_int, flags: DWORD| {
+        let mut input: INPUT = unsafe { mem::zeroed() };
+        input.type_ = INPUT_KEYBOARD;
+        unsafe {
+            let key = input.ki_mut();
+            key.wVk = vk as WORD;
+            key.dwFlags = flags;
+        }
+        input
+    };
+
+    let mut inputs: Vec<INPUT> = keys.iter().map(|&vk| key_input(vk, 0))
+        .chain(keys.iter().rev().map(|&vk| key_input(vk, KEYEVENTF_KEYUP)))
+        .collect();
+
+    let sent = unsafe { user32::SendInput(inputs.len() as UINT, inputs.as_mut_ptr(), mem::size_of::<INPUT>() as c_int) };
+    if sent as usize != inputs.len() {
+        return Err(Error::last_win32());
+    }
+
+    Ok(())
+}
+
+/// Binary value under `HKEY_CURRENT_USER`, `None` if the key or value does not exist
+fn read_binary_value(key: &str, value: &str) -> Win32Result<Option<Vec<u8>>> {
+    let key = utils::to_wide_chars(key);
+    let value = utils::to_wide_chars(value);
+    let mut size: DWORD = 0;
+
+    let status = unsafe {
+        RegGetValueW(HKEY_CURRENT_USER, key.as_ptr(), value.as_ptr(), RRF_RT_REG_BINARY, ptr::null_mut(), ptr::null_mut(), &mut size)
+    };
+
+    match status as DWORD {
+        ERROR_SUCCESS => {},
+        ERROR_FILE_NOT_FOUND => return Ok(None),
+        code => return Err(Error::Win32(code))
+    }
+
+    let mut data = vec![0u8; size as usize];
+    let status = unsafe {
+        RegGetValueW(HKEY_CURRENT_USER, key.as_ptr(), value.as_ptr(), RRF_RT_REG_BINARY, ptr::null_mut(), data.as_mut_ptr() as LPVOID, &mut size)
+    };
+
+    if status as DWORD != ERROR_SUCCESS {
+        return Err(Error::Win32(status as DWORD));
+    }
+
+    data.truncate(size as usize);
+    Ok(Some(data))
+}
+
+/// Desktop with the GUID in `bytes`, `None` unless they are exactly 16
+fn desktop_id(bytes: &[u8]) -> Option<DesktopId> {
+    if bytes.len() != 16 {
+        return None;
+    }
+
+    let mut id = [0; 16];
+    id.copy_from_slice(bytes);
+    Some(DesktopId(id))
+}
+
+/// Copies `text` into a fixed size buffer, cutting it off to leave room for the terminator
+fn copy_truncated(buffer: &mut [u16], text: &str) {
+    let text = utils::to_wide_chars(text);
+    let len = text.len().min(buffer.len()) - 1;
+    buffer[..len].copy_from_slice(&text[..len]);
+}
+
+fn load_icon() -> HICON {
+    unsafe {
+        let icon = user32::LoadIconW(kernel32::GetModuleHandleW(ptr::null()), IDI_ICON as LPCWSTR);
+
+        if icon.is_null() {
+            user32::LoadIconW(0 as HINSTANCE, IDI_APPLICATION as LPCWSTR)
+        } else {
+            icon
+        }
+    }
+}
+
+/// Destroying the returned menu also destroys its submenus
+unsafe fn build_menu(items: &[MenuItem]) -> HMENU {
+    let hmenu = user32::CreatePopupMenu();
+
+    for item in items {
+        match *item {
+            MenuItem::Command { command, ref label, checked } => {
+                let flags = if checked { MF_STRING | MF_CHECKED } else { MF_STRING };
+                let label = utils::to_wide_chars(label);
+                user32::AppendMenuW(hmenu, flags, command.id() as UINT_PTR, label.as_ptr());
+            },
+            MenuItem::Label(ref label) => {
+                let label = utils::to_wide_chars(label);
+                user32::AppendMenuW(hmenu, MF_STRING | MF_GRAYED, 0, label.as_ptr());
+            },
+            MenuItem::Submenu(ref label, ref items) => {
+                let label = utils::to_wide_chars(label);
+                user32::AppendMenuW(hmenu, MF_POPUP, build_menu(items) as UINT_PTR, label.as_ptr());
+            },
+            MenuItem::Separator => {
+                user32::AppendMenuW(hmenu, MF_SEPARATOR, 0, ptr::null());
+            },
+        }
+    }
+
+    hmenu
+}
@@ -0,0 +1,326 @@
+use std::str::FromStr;
+
+use fuzzy;
+use winapi::minwindef::UINT;
+
+use hotkeys::{Action, TILE_NAMES};
+use settings;
+use sources::{self, ResultAction, ResultSource, SearchResult, SourceOptions};
+
+/// Something WinMan can be told to do, written like `grab 3` or `save work`
+///
+/// Hotkeys, leader sequences, the `>` palette in the popup and other instances
+/// started with `--run` all name commands the same way, see `COMMANDS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Anything that can also be bound to a hotkey
+    Action(Action),
+    /// Move the foreground window to another slot
+    Move(UINT),
+    SaveLayout(String),
+    RestoreLayout(String),
+    ReloadConfig,
+    /// Activate the named profile, or list them all
+    Profile(Option<String>),
+    /// Log the windows in one slot, or in all of them
+    ListSlots(Option<UINT>),
+    ListHotkeys,
+    LogFilter(String),
+}
+
+/// Entry of the command registry
+pub struct CommandInfo {
+    pub name: &'static str,
+    /// Placeholder for the argument, empty if there is none
+    pub usage: &'static str,
+    pub description: &'static str,
+    parse: fn(Option<&str>) -> Option<Command>,
+}
+
+impl CommandInfo {
+    /// Name and usage, like `grab <slot>`
+    pub fn synopsis(&self) -> String {
+        if self.usage.is_empty() {
+            self.name.to_string()
+        } else {
+            format!("{} {}", self.name, self.usage)
+        }
+    }
+}
+
+/// All commands, in the order the palette lists them
+pub static COMMANDS: &'static [CommandInfo] = &[
+    CommandInfo { name: "switch", usage: "<slot>", description: "Focus the next window in a slot", parse: switch },
+    CommandInfo { name: "switch_back", usage: "<slot>", description: "Focus the previous window in a slot", parse: switch_back },
+    CommandInfo { name: "grab", usage: "<slot>", description: "Add the window to a slot", parse: grab },
+    CommandInfo { name: "clear", usage: "<slot>", description: "Empty a slot", parse: clear },
+    CommandInfo { name: "move", usage: "<slot>", description: "Move the window to another slot", parse: move_to },
+    CommandInfo { name: "release", usage: "", description: "Remove the window from its slots", parse: release },
+    CommandInfo { name: "remove", usage: "", description: "Same as release", parse: release },
+    CommandInfo { name: "shift_up", usage: "", description: "Move the window towards the front of its slots", parse: shift_up },
+    CommandInfo { name: "up", usage: "", description: "Same as shift_up", parse: shift_up },
+    CommandInfo { name: "shift_down", usage: "", description: "Move the window towards the back of its slots", parse: shift_down },
+    CommandInfo { name: "down", usage: "", description: "Same as shift_down", parse: shift_down },
+    CommandInfo { name: "tile", usage: "<tile>", description: "Place the window in part of the screen", parse: tile },
+    CommandInfo { name: "desktop", usage: "<number>", description: "Show another virtual desktop", parse: desktop },
+    CommandInfo { name: "save", usage: "<layout>", description: "Save where all windows are", parse: save },
+    CommandInfo { name: "restore", usage: "<layout>", description: "Put windows back where they were saved", parse: restore },
+    CommandInfo { name: "profile", usage: "[name]", description: "Activate a profile, or list them", parse: profile },
+    CommandInfo { name: "next_profile", usage: "", description: "Activate the profile after the active one", parse: next_profile },
+    CommandInfo { name: "list", usage: "[slot]", description: "Log the windows in the slots", parse: list },
+    CommandInfo { name: "hotkeys", usage: "", description: "Log the registered hotkeys", parse: hotkeys },
+    CommandInfo { name: "log", usage: "<filter>", description: "Set the log level, e.g. debug, rules=trace", parse: log },
+    CommandInfo { name: "log_level", usage: "", description: "Step the log level through info, debug and trace", parse: log_level },
+    CommandInfo { name: "reload", usage: "", description: "Load winman.ini again", parse: reload },
+    CommandInfo { name: "popup", usage: "", description: "Show the switcher", parse: popup },
+    CommandInfo { name: "leader", usage: "", description: "Wait for a key sequence", parse: leader },
+    CommandInfo { name: "quit", usage: "", description: "Exit WinMan", parse: quit },
+];
+
+/// Commands WinMan can not offer, with the reason
+static UNSUPPORTED: &'static [(&'static str, &'static str)] = &[
+    ("move_to_desktop", "Windows only lets a program move its own windows to another virtual desktop"),
+];
+
+impl FromStr for Command {
+    type Err = String;
+
+    /// Parses a command with its exact name
+    fn from_str(text: &str) -> Result<Self, String> {
+        let (name, arg) = split(text);
+
+        if let Some(&(_, reason)) = UNSUPPORTED.iter().find(|&&(unsupported, _)| unsupported == name) {
+            return Err(format!("'{}' is not supported, {}", name, reason));
+        }
+
+        match COMMANDS.iter().find(|info| info.name == name) {
+            Some(info) => parse(info, arg),
+            None => Err(format!("unknown command '{}'", name))
+        }
+    }
+}
+
+/// The command palette, searched by typing `>` and a command like `gr 3`
+///
+/// Every command whose name matches the first word is listed. Accepting one
+/// runs it with the rest of the query, or explains its usage.
+pub struct CommandSource;
+
+impl ResultSource for CommandSource {
+    fn name(&self) -> &'static str {
+        "commands"
+    }
+
+    fn default_options(&self) -> SourceOptions {
+        SourceOptions::new(0, ">")
+    }
+
+    fn search(&self, query: &str) -> Vec<SearchResult> {
+        let (name, arg) = split(query);
+
+        COMMANDS.iter()
+            .filter_map(|info| {
+                // All commands are listed before a name is typed
+                let score = if name.is_empty() { 1 } else { sources::score(fuzzy::fuzzy_match(name, info.name)) };
+                if score == 0 {
+                    return None;
+                }
+
+                let action = match parse(info, arg) {
+                    Ok(command) => ResultAction::Run(command),
+                    Err(usage) => ResultAction::Usage(usage)
+                };
+
+                Some(SearchResult {
+                    label: info.synopsis(),
+                    subtitle: info.description.to_string(),
+                    icon: "command".to_string(),
+                    score: score,
+                    action: action,
+                })
+            })
+            .collect()
+    }
+}
+
+fn parse(info: &CommandInfo, arg: Option<&str>) -> Result<Command, String> {
+    (info.parse)(arg).ok_or_else(|| format!("usage: {}", info.synopsis()))
+}
+
+/// Name and the trimmed rest of a command
+fn split(text: &str) -> (&str, Option<&str>) {
+    let text = text.trim();
+
+    match text.find(char::is_whitespace) {
+        Some(index) => (&text[..index], Some(text[index..].trim())),
+        None => (text, None)
+    }
+}
+
+fn none(arg: Option<&str>, command: Command) -> Option<Command> {
+    match arg {
+        None => Some(command),
+        Some(_) => None
+    }
+}
+
+fn slot(arg: Option<&str>) -> Option<UINT> {
+    arg.and_then(settings::slot_key)
+}
+
+fn number(arg: Option<&str>) -> Option<usize> {
+    match arg.map(str::parse::<usize>) {
+        Some(Ok(number)) if number >= 1 => Some(number),
+        _ => None
+    }
+}
+
+fn name(arg: Option<&str>) -> Option<String> {
+    arg.map(String::from)
+}
+
+fn switch(arg: Option<&str>) -> Option<Command> {
+    slot(arg).map(|vk| Command::Action(Action::Switch(vk)))
+}
+
+fn switch_back(arg: Option<&str>) -> Option<Command> {
+    slot(arg).map(|vk| Command::Action(Action::SwitchBack(vk)))
+}
+
+fn grab(arg: Option<&str>) -> Option<Command> {
+    slot(arg).map(|vk| Command::Action(Action::Grab(vk)))
+}
+
+fn clear(arg: Option<&str>) -> Option<Command> {
+    slot(arg).map(|vk| Command::Action(Action::Clear(vk)))
+}
+
+fn move_to(arg: Option<&str>) -> Option<Command> {
+    slot(arg).map(Command::Move)
+}
+
+fn release(arg: Option<&str>) -> Option<Command> {
+    none(arg, Command::Action(Action::Release))
+}
+
+fn shift_up(arg: Option<&str>) -> Option<Command> {
+    none(arg, Command::Action(Action::ShiftUp))
+}
+
+fn shift_down(arg: Option<&str>) -> Option<Command> {
+    none(arg, Command::Action(Action::ShiftDown))
+}
+
+fn tile(arg: Option<&str>) -> Option<Command> {
+    TILE_NAMES.iter()
+        .find(|&&(name, _)| Some(name) == arg)
+        .map(|&(_, tile)| Command::Action(Action::Tile(tile)))
+}
+
+fn desktop(arg: Option<&str>) -> Option<Command> {
+    number(arg).map(|number| Command::Action(Action::SwitchDesktop(number)))
+}
+
+fn save(arg: Option<&str>) -> Option<Command> {
+    name(arg).map(Command::SaveLayout)
+}
+
+fn restore(arg: Option<&str>) -> Option<Command> {
+    name(arg).map(Command::RestoreLayout)
+}
+
+fn profile(arg: Option<&str>) -> Option<Command> {
+    Some(Command::Profile(name(arg)))
+}
+
+fn next_profile(arg: Option<&str>) -> Option<Command> {
+    none(arg, Command::Action(Action::NextProfile))
+}
+
+fn list(arg: Option<&str>) -> Option<Command> {
+    match arg {
+        Some(_) => slot(arg).map(|vk| Command::ListSlots(Some(vk))),
+        None => Some(Command::ListSlots(None))
+    }
+}
+
+fn hotkeys(arg: Option<&str>) -> Option<Command> {
+    none(arg, Command::ListHotkeys)
+}
+
+fn log(arg: Option<&str>) -> Option<Command> {
+    name(arg).map(Command::LogFilter)
+}
+
+fn log_level(arg: Option<&str>) -> Option<Command> {
+    none(arg, Command::Action(Action::CycleLogLevel))
+}
+
+fn reload(arg: Option<&str>) -> Option<Command> {
+    none(arg, Command::ReloadConfig)
+}
+
+fn popup(arg: Option<&str>) -> Option<Command> {
+    none(arg, Command::Action(Action::ShowPopup))
+}
+
+fn leader(arg: Option<&str>) -> Option<Command> {
+    none(arg, Command::Action(Action::Leader))
+}
+
+fn quit(arg: Option<&str>) -> Option<Command> {
+    none(arg, Command::Action(Action::Quit))
+}
+
+#[cfg(test)]
+mod tests {
+    use constants::{VK_0, VK_3};
+    use hotkeys::Action;
+    use layout::tiling::Tile;
+    use sources::{self, ResultAction, ResultSource};
+    use super::{Command, CommandSource, COMMANDS};
+
+    #[test]
+    fn parse() {
+        assert_eq!(Ok(Command::Action(Action::Grab(VK_3))), "grab 3".parse());
+        assert_eq!(Ok(Command::Action(Action::Tile(Tile::Center))), " tile  center ".parse());
+        assert_eq!(Ok(Command::SaveLayout("two screens".to_string())), "save two screens".parse());
+        assert_eq!(Ok(Command::Profile(None)), "profile".parse());
+        assert_eq!(Ok(Command::ListSlots(Some(VK_0))), "list 0".parse());
+
+        assert_eq!(Err("usage: grab <slot>".to_string()), "grab".parse::<Command>());
+        assert_eq!(Err("usage: quit".to_string()), "quit now".parse::<Command>());
+        assert_eq!(Err("usage: list [slot]".to_string()), "list x".parse::<Command>());
+        assert_eq!(Err("unknown command 'gr'".to_string()), "gr 3".parse::<Command>());
+
+        let err = "move_to_desktop 2".parse::<Command>().unwrap_err();
+        assert!(err.starts_with("'move_to_desktop' is not supported"), "{}", err);
+    }
+
+    #[test]
+    fn unique_names() {
+        for (index, info) in COMMANDS.iter().enumerate() {
+            assert!(COMMANDS[index + 1..].iter().all(|other| other.name != info.name), "{}", info.name);
+        }
+    }
+
+    #[test]
+    fn palette() {
+        let search = |query| sources::search(&[(&CommandSource as &dyn ResultSource, CommandSource.default_options())], query);
+        let labels = |query| search(query).into_iter().map(|result| result.label).collect::<Vec<_>>();
+
+        assert_eq!(COMMANDS.len(), search(">").len());
+        assert!(search("switch").is_empty());
+        assert_eq!(vec!["switch <slot>", "switch_back <slot>"], labels(">swi"));
+        assert_eq!("save <layout>", labels(">save 1")[0]);
+        assert_eq!("desktop <number>", labels(">deskt")[0]);
+        assert!(labels(">xyzzy").is_empty());
+
+        let action = |query| search(query).into_iter().next().map(|result| result.action);
+        assert_eq!(Some(ResultAction::Run(Command::Action(Action::Grab(VK_3)))), action(">gr 3"));
+        assert_eq!(Some(ResultAction::Run(Command::Action(Action::Release))), action(">rel"));
+        assert_eq!(Some(ResultAction::Run(Command::ReloadConfig)), action("> relo"));
+        assert_eq!(Some(ResultAction::Usage("usage: clear <slot>".to_string())), action(">clear"));
+        assert_eq!(None, action(">xyzzy 3"));
+    }
+}
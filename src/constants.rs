@@ -24,4 +24,22 @@ pub const VK_6: UINT = 0x36;
 pub const VK_7: UINT = 0x37;
 pub const VK_8: UINT = 0x38;
 pub const VK_9: UINT = 0x39;
-pub const VK_Q: UINT = 0x51;
\ No newline at end of file
+pub const VK_B: UINT = 0x42;
+pub const VK_C: UINT = 0x43;
+pub const VK_D: UINT = 0x44;
+pub const VK_F: UINT = 0x46;
+pub const VK_G: UINT = 0x47;
+pub const VK_I: UINT = 0x49;
+pub const VK_J: UINT = 0x4A;
+pub const VK_K: UINT = 0x4B;
+pub const VK_L: UINT = 0x4C;
+pub const VK_M: UINT = 0x4D;
+pub const VK_P: UINT = 0x50;
+pub const VK_Q: UINT = 0x51;
+pub const VK_R: UINT = 0x52;
+pub const VK_S: UINT = 0x53;
+pub const VK_T: UINT = 0x54;
+pub const VK_U: UINT = 0x55;
+pub const VK_V: UINT = 0x56;
+pub const VK_X: UINT = 0x58;
+pub const VK_Z: UINT = 0x5A;
\ No newline at end of file
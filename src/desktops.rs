@@ -0,0 +1,134 @@
+use backend::{DesktopBackend, DesktopId};
+use utils::Win32Result;
+use window_tracking::Config;
+
+/// Desktop `number` in task view order, counting from 1
+pub fn nth<D: DesktopBackend>(desktops: &D, number: usize) -> Win32Result<Option<DesktopId>> {
+    let all = try!{ desktops.desktops() };
+    Ok(number.checked_sub(1).and_then(|index| all.get(index).cloned()))
+}
+
+/// Scopes slots to the desktop shown right now
+///
+/// Slots stay on the last known desktop while the current one can not be told.
+pub fn sync<D: DesktopBackend>(desktops: &D, config: &mut Config) {
+    if let Ok(desktop) = desktops.current_desktop() {
+        config.set_desktop(Some(desktop));
+    }
+}
+
+/// Shows another desktop and scopes slots to it
+pub fn switch<D: DesktopBackend>(desktops: &mut D, config: &mut Config, desktop: DesktopId) -> Win32Result<()> {
+    try!{ desktops.switch_desktop(desktop) };
+    config.set_desktop(Some(desktop));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::fake::{desktop, hwnd, FakeDesktops};
+    use constants::{VK_1, VK_2};
+    use ini::Ini;
+    use settings::Settings;
+    use window_tracking::{Config, Window};
+    use super::{nth, switch, sync};
+
+    /// Slot 1 is scoped to desktops, slot 2 is global
+    fn config() -> Config {
+        let ini = Ini::parse("[slot 1]\nscope = desktop").unwrap();
+        Config::with_settings(Settings::from_ini(&ini).unwrap())
+    }
+
+    fn front(config: &mut Config, vk: u32) -> Option<usize> {
+        config.get_windows(vk)
+            .and_then(|window_set| window_set.iter().next().map(|w| w.hwnd() as usize))
+    }
+
+    #[test]
+    fn slots_per_desktop() {
+        let mut desktops = FakeDesktops::new(2);
+        let mut config = config();
+
+        sync(&desktops, &mut config);
+        config.track_window(VK_1, Window::new(hwnd(1), "a".to_string()));
+        config.track_window(VK_2, Window::new(hwnd(2), "b".to_string()));
+
+        switch(&mut desktops, &mut config, desktop(2)).unwrap();
+        assert_eq!(desktop(2), desktops.current);
+        assert_eq!(None, front(&mut config, VK_1));
+        assert_eq!(Some(2), front(&mut config, VK_2));
+
+        config.track_window(VK_1, Window::new(hwnd(3), "c".to_string()));
+        assert_eq!(Some(3), front(&mut config, VK_1));
+
+        desktops.current = desktop(1);
+        sync(&desktops, &mut config);
+        assert_eq!(Some(1), front(&mut config, VK_1));
+        assert_eq!(vec![VK_1], config.slots_of(hwnd(1)));
+    }
+
+    #[test]
+    fn keep_last_known_desktop() {
+        let mut desktops = FakeDesktops::new(2);
+        let mut config = config();
+
+        desktops.current = desktop(2);
+        sync(&desktops, &mut config);
+        config.track_window(VK_1, Window::new(hwnd(1), "a".to_string()));
+
+        // E.g. a window shown on all desktops has the focus
+        desktops.unknown = true;
+        sync(&desktops, &mut config);
+        assert_eq!(Some(1), front(&mut config, VK_1));
+
+        desktops.unknown = false;
+        desktops.current = desktop(1);
+        sync(&desktops, &mut config);
+        assert_eq!(None, front(&mut config, VK_1));
+    }
+
+    #[test]
+    fn global_fallback() {
+        let mut config = config();
+
+        // Grabbed before the desktop was known
+        config.track_window(VK_1, Window::new(hwnd(1), "a".to_string()));
+
+        sync(&FakeDesktops::new(2), &mut config);
+        assert_eq!(Some(1), front(&mut config, VK_1));
+
+        config.track_window(VK_1, Window::new(hwnd(2), "b".to_string()));
+        assert_eq!(Some(2), front(&mut config, VK_1));
+
+        config.clear_windows(VK_1);
+        assert_eq!(Some(1), front(&mut config, VK_1));
+
+        // Clearing the slot again clears the global windows it shows
+        config.clear_windows(VK_1);
+        assert_eq!(None, front(&mut config, VK_1));
+    }
+
+    #[test]
+    fn clear_global_only_slot() {
+        let mut desktops = FakeDesktops::new(2);
+        let mut config = config();
+
+        config.track_window(VK_1, Window::new(hwnd(1), "a".to_string()));
+        switch(&mut desktops, &mut config, desktop(2)).unwrap();
+        assert_eq!(Some(1), front(&mut config, VK_1));
+
+        config.clear_windows(VK_1);
+        assert_eq!(None, front(&mut config, VK_1));
+        assert!(config.slots_of(hwnd(1)).is_empty());
+    }
+
+    #[test]
+    fn nth_desktop() {
+        let desktops = FakeDesktops::new(3);
+
+        assert_eq!(Some(desktop(3)), nth(&desktops, 3).unwrap());
+        assert_eq!(None, nth(&desktops, 0).unwrap());
+        assert_eq!(None, nth(&desktops, 4).unwrap());
+    }
+}
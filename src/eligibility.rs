@@ -0,0 +1,217 @@
+use std::ops::Not;
+use std::process;
+
+use winapi::windef::HWND;
+
+use backend::WindowBackend;
+use titles::WindowTitle;
+
+/// Attributes of a top-level window that decide whether it can be switched to
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WindowAttributes {
+    pub title: WindowTitle,
+    pub class: String,
+    pub process_id: u32,
+    pub visible: bool,
+    /// Hidden by DWM while looking visible, e.g. suspended UWP apps or windows on
+    /// another virtual desktop
+    pub cloaked: bool,
+    /// Has an owner window, like dialogs and floating palettes
+    pub owned: bool,
+    /// `WS_EX_TOOLWINDOW`, kept out of the taskbar and Alt+Tab
+    pub tool_window: bool,
+    /// `WS_EX_APPWINDOW`, forced into the taskbar even when owned
+    pub app_window: bool,
+}
+
+/// A composable test on window attributes
+pub struct Filter {
+    test: Box<dyn Fn(&WindowAttributes) -> bool>,
+}
+
+impl Filter {
+    pub fn new<F>(test: F) -> Self
+        where F: Fn(&WindowAttributes) -> bool + 'static {
+
+        Filter { test: Box::new(test) }
+    }
+
+    pub fn matches(&self, window: &WindowAttributes) -> bool {
+        (self.test)(window)
+    }
+
+    pub fn and(self, other: Filter) -> Filter {
+        Filter::new(move |w| self.matches(w) && other.matches(w))
+    }
+
+    pub fn or(self, other: Filter) -> Filter {
+        Filter::new(move |w| self.matches(w) || other.matches(w))
+    }
+}
+
+impl Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        Filter::new(move |w| !self.matches(w))
+    }
+}
+
+pub fn visible() -> Filter {
+    Filter::new(|w| w.visible)
+}
+
+pub fn cloaked() -> Filter {
+    Filter::new(|w| w.cloaked)
+}
+
+/// Windows with a title, or whose title is not known because they do not answer
+pub fn has_title() -> Filter {
+    Filter::new(|w| w.title.text().map_or(true, |title| !title.trim().is_empty()))
+}
+
+/// Windows the taskbar shows a button for: unowned non-tool windows, or any
+/// window that asks for a button with `WS_EX_APPWINDOW`
+pub fn app_window() -> Filter {
+    Filter::new(|w| w.app_window || (!w.owned && !w.tool_window))
+}
+
+pub fn process(process_id: u32) -> Filter {
+    Filter::new(move |w| w.process_id == process_id)
+}
+
+pub fn class(names: &'static [&'static str]) -> Filter {
+    Filter::new(move |w| names.contains(&w.class.as_str()))
+}
+
+/// Desktop and taskbar windows, which are visible and titled but not switchable
+pub fn shell() -> Filter {
+    class(&["Progman", "WorkerW", "Shell_TrayWnd", "Shell_SecondaryTrayWnd"])
+}
+
+/// The windows listed in the switcher, excluding those of `own_process`
+pub fn switchable(own_process: u32) -> Filter {
+    visible()
+        .and(!cloaked())
+        .and(has_title())
+        .and(app_window())
+        .and(!shell())
+        .and(!process(own_process))
+}
+
+/// Top-level windows accepted by `filter`, in z-order
+pub fn eligible_windows<B: WindowBackend>(backend: &B, filter: &Filter) -> Vec<HWND> {
+    backend.windows()
+        .into_iter()
+        .filter(|&hwnd| {
+            backend.window_attributes(hwnd)
+                .map(|attributes| filter.matches(&attributes))
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+/// Windows the user can switch to, in z-order
+pub fn switchable_windows<B: WindowBackend>(backend: &B) -> Vec<HWND> {
+    eligible_windows(backend, &switchable(process::id()))
+}
+
+pub fn is_switchable<B: WindowBackend>(backend: &B, hwnd: HWND) -> bool {
+    backend.window_attributes(hwnd)
+        .map(|attributes| switchable(process::id()).matches(&attributes))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::fake::{self, FakeBackend};
+    use layout::Rect;
+    use titles::WindowTitle;
+    use super::{app_window, eligible_windows, has_title, switchable, visible, WindowAttributes};
+
+    const OWN_PROCESS: u32 = 4242;
+
+    fn window(title: &str, class: &str, process_id: u32) -> WindowAttributes {
+        WindowAttributes {
+            title: WindowTitle::new(title.to_string()),
+            class: class.to_string(),
+            process_id: process_id,
+            visible: true,
+            ..WindowAttributes::default()
+        }
+    }
+
+    /// Top-level windows of a Windows 10 desktop, as reported by `window_attributes`
+    fn recorded() -> Vec<(WindowAttributes, bool)> {
+        vec![
+            (WindowAttributes { visible: false, ..window("Default IME", "IME", 8812) }, false),
+            (WindowAttributes { visible: false, ..window("MSCTFIME UI", "MSCTFIME UI", 8812) }, false),
+            (WindowAttributes { tool_window: true, ..window("", "Shell_TrayWnd", 5120) }, false),
+            (window("Program Manager", "Progman", 5120), false),
+            (WindowAttributes { cloaked: true, ..window("Settings", "ApplicationFrameWindow", 9040) }, false),
+            (WindowAttributes { cloaked: true, ..window("Microsoft Text Input Application", "Windows.UI.Core.CoreWindow", 9432) }, false),
+            (WindowAttributes { owned: true, tool_window: true, ..window("Find", "#32770", 7316) }, false),
+            (WindowAttributes { owned: true, ..window("Save As", "#32770", 7316) }, false),
+            (WindowAttributes { owned: true, app_window: true, ..window("Share screen", "Chrome_WidgetWin_1", 6004) }, true),
+            (WindowAttributes { tool_window: true, ..window("GDI+ Window (Code.exe)", "GDI+ Hook Window Class", 7316) }, false),
+            (window("", "Chrome_WidgetWin_0", 6004), false),
+            (window("   ", "ConsoleWindowClass", 1288), false),
+            (WindowAttributes { visible: false, ..window("", "WinmanMainWindow", OWN_PROCESS) }, false),
+            (window("Search", "WinmanPopupWindow", OWN_PROCESS), false),
+            (window("winman - Visual Studio Code", "Chrome_WidgetWin_1", 7316), true),
+            (window("Inbox - Mozilla Firefox", "MozillaWindowClass", 6612), true),
+            (window("Windows PowerShell", "CASCADIA_HOSTING_WINDOW_CLASS", 1288), true),
+            (window("Calculator", "ApplicationFrameWindow", 9040), true),
+            (WindowAttributes { title: WindowTitle::Unavailable, ..window("", "Notepad", 3376) }, true),
+        ]
+    }
+
+    #[test]
+    fn recorded_desktop() {
+        let filter = switchable(OWN_PROCESS);
+
+        for (attributes, expected) in recorded() {
+            assert_eq!(expected, filter.matches(&attributes), "{:?}", attributes);
+        }
+    }
+
+    #[test]
+    fn composition() {
+        let titled = window("Editor", "Edit", 1);
+        let hidden = WindowAttributes { visible: false, ..titled.clone() };
+        let untitled = window("", "Edit", 1);
+
+        let both = visible().and(has_title());
+        assert!(both.matches(&titled));
+        assert!(!both.matches(&hidden));
+        assert!(!both.matches(&untitled));
+
+        let either = (!visible()).or(!has_title());
+        assert!(!either.matches(&titled));
+        assert!(either.matches(&hidden));
+        assert!(either.matches(&untitled));
+    }
+
+    #[test]
+    fn app_window_style() {
+        let filter = app_window();
+
+        assert!(filter.matches(&window("Main", "Main", 1)));
+        assert!(!filter.matches(&WindowAttributes { owned: true, ..window("Dialog", "#32770", 1) }));
+        assert!(!filter.matches(&WindowAttributes { tool_window: true, ..window("Palette", "Palette", 1) }));
+        assert!(filter.matches(&WindowAttributes { owned: true, app_window: true, ..window("Popout", "Popout", 1) }));
+    }
+
+    #[test]
+    fn backend_windows() {
+        let mut backend = FakeBackend::new(vec![fake::monitor(0, 0, 1920, 1080, true)]);
+        let editor = backend.add_window(1, "Editor", Rect::default());
+        let hidden = backend.add_window(2, "Hidden", Rect::default());
+        let cloaked = backend.add_window(3, "Cloaked", Rect::default());
+        let browser = backend.add_window(4, "Browser", Rect::default());
+        backend.window_mut(hidden).unwrap().visible = false;
+        backend.window_mut(cloaked).unwrap().cloaked = true;
+
+        assert_eq!(vec![editor, browser], eligible_windows(&backend, &switchable(OWN_PROCESS)));
+    }
+}
@@ -0,0 +1,87 @@
+use std::error;
+use std::fmt;
+
+use fuzzy;
+use kernel32;
+use winapi::minwindef::DWORD;
+
+use ini::ParseError;
+use utils::api_wrappers;
+
+/// Everything that can go wrong in WinMan
+#[derive(Debug)]
+pub enum Error {
+    /// A Windows API call failed with this error code or HRESULT
+    Win32(DWORD),
+    /// A config file is malformed
+    Config(ParseError),
+    /// A search query that can not be compiled
+    InvalidQuery(String),
+    /// Talking to another process failed
+    Ipc(String),
+}
+
+impl Error {
+    /// Error for the calling thread's last Win32 error code
+    pub fn last_win32() -> Error {
+        Error::Win32(unsafe { kernel32::GetLastError() })
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Win32(code) => match api_wrappers::format_message(code) {
+                Some(message) => write!(f, "{} (error {:#x})", message, code),
+                None => write!(f, "Windows error {:#x}", code),
+            },
+            Error::Config(ref err) => write!(f, "invalid config, {}", err),
+            Error::InvalidQuery(ref message) => write!(f, "invalid query: {}", message),
+            Error::Ipc(ref message) => write!(f, "IPC failed: {}", message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Config(ref err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Error {
+        Error::Config(err)
+    }
+}
+
+impl From<fuzzy::QueryError> for Error {
+    fn from(err: fuzzy::QueryError) -> Error {
+        Error::InvalidQuery(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as StdError;
+
+    use fuzzy::Finder;
+    use ini::Ini;
+    use super::Error;
+
+    #[test]
+    fn display() {
+        let win32 = Error::Win32(2).to_string();
+        assert!(win32.ends_with("(error 0x2)"), "{}", win32);
+        assert!(win32.len() > "(error 0x2)".len());
+
+        let config = Error::from(Ini::parse("[a]\n  key").unwrap_err());
+        assert_eq!("invalid config, line 2, column 3: expected 'key = value'", config.to_string());
+        assert!(config.source().is_some());
+
+        let query = Error::from(Finder::new("(unclosed").err().unwrap());
+        assert!(query.to_string().starts_with("invalid query: "));
+    }
+}
@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Notices when a file is written, created or deleted
+///
+/// The file's modification time and size are compared each time `changed` is
+/// called, which is cheap enough to do from a timer.
+pub struct FileWatch {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+}
+
+impl FileWatch {
+    /// Starts watching `path`, its current contents count as seen
+    pub fn new(path: PathBuf) -> Self {
+        let stamp = stamp(&path);
+
+        FileWatch {
+            path: path,
+            stamp: stamp,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the file changed since the last call
+    pub fn changed(&mut self) -> bool {
+        let stamp = stamp(&self.path);
+
+        if stamp != self.stamp {
+            self.stamp = stamp;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    fs::metadata(path)
+        .ok()
+        .map(|metadata| (metadata.modified().unwrap_or(UNIX_EPOCH), metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::FileWatch;
+
+    #[test]
+    fn changes() {
+        let path = env::temp_dir().join(format!("winman-watch-{}.ini", process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut watch = FileWatch::new(path.clone());
+        assert!(!watch.changed());
+
+        fs::write(&path, "[slots]").unwrap();
+        assert!(watch.changed());
+        assert!(!watch.changed());
+
+        // The size changes even if the modification time is too coarse to
+        fs::write(&path, "[slots]\n1 = notepad").unwrap();
+        assert!(watch.changed());
+
+        fs::remove_file(&path).unwrap();
+        assert!(watch.changed());
+        assert!(!watch.changed());
+    }
+}
@@ -0,0 +1,575 @@
+use std::fmt;
+use std::str::FromStr;
+
+use winapi::minwindef::UINT;
+
+use commands::Command;
+use constants::*;
+use error::Error;
+use layout::tiling::Tile;
+use utils::Win32Result;
+
+// Keys with a name other than their character
+const KEY_NAMES: [(&'static str, UINT); 16] = [
+    ("Space", 0x20),
+    ("Enter", 0x0D),
+    ("Tab", 0x09),
+    ("Esc", 0x1B),
+    ("Backspace", 0x08),
+    ("Delete", 0x2E),
+    ("Insert", 0x2D),
+    ("Home", 0x24),
+    ("End", 0x23),
+    ("PageUp", 0x21),
+    ("PageDown", 0x22),
+    ("Left", 0x25),
+    ("Up", 0x26),
+    ("Right", 0x27),
+    ("Down", 0x28),
+    ("Pause", 0x13),
+];
+
+const VK_A: UINT = 0x41;
+const VK_F1: UINT = 0x70;
+
+const MODIFIER_NAMES: [(&'static str, UINT); 4] = [
+    ("Ctrl", MOD_CONTROL),
+    ("Alt", MOD_ALT),
+    ("Shift", MOD_SHIFT),
+    ("Win", MOD_WIN),
+];
+
+pub const TILE_NAMES: [(&'static str, Tile); 11] = [
+    ("left_half", Tile::LeftHalf),
+    ("right_half", Tile::RightHalf),
+    ("left_third", Tile::LeftThird),
+    ("center_third", Tile::CenterThird),
+    ("right_third", Tile::RightThird),
+    ("top_left", Tile::TopLeftQuarter),
+    ("top_right", Tile::TopRightQuarter),
+    ("bottom_left", Tile::BottomLeftQuarter),
+    ("bottom_right", Tile::BottomRightQuarter),
+    ("center", Tile::Center),
+    ("maximize", Tile::Maximize),
+];
+
+/// Modifiers and key for `RegisterHotKey`, written like `Ctrl+Alt+3`
+///
+/// Holding a chord down does not repeat it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Chord {
+    pub modifiers: UINT,
+    pub vk: UINT,
+}
+
+impl Chord {
+    pub fn new(modifiers: UINT, vk: UINT) -> Self {
+        Chord {
+            modifiers: modifiers | MOD_NOREPEAT,
+            vk: vk,
+        }
+    }
+}
+
+impl FromStr for Chord {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let (key, modifiers) = parts.split_last().unwrap();
+        let mut chord = Chord::new(0, 0);
+
+        for name in modifiers {
+            match MODIFIER_NAMES.iter().find(|&&(modifier, _)| modifier.eq_ignore_ascii_case(name)) {
+                Some(&(_, modifier)) => chord.modifiers |= modifier,
+                None => return Err(format!("unknown modifier '{}'", name))
+            }
+        }
+
+        chord.vk = try!{ key_code(key).ok_or_else(|| format!("unknown key '{}'", key)) };
+
+        Ok(chord)
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &(name, modifier) in MODIFIER_NAMES.iter() {
+            if self.modifiers & modifier != 0 {
+                try!{ write!(f, "{}+", name) };
+            }
+        }
+
+        match key_name(self.vk) {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "{:#04x}", self.vk),
+        }
+    }
+}
+
+/// Virtual key code for a key name like `3`, `F5` or `PageUp`
+pub fn key_code(name: &str) -> Option<UINT> {
+    let upper = name.to_ascii_uppercase();
+    let bytes = upper.as_bytes();
+
+    if bytes.len() == 1 && (bytes[0].is_ascii_digit() || bytes[0].is_ascii_uppercase()) {
+        return Some(bytes[0] as UINT);
+    }
+
+    if bytes.len() > 1 && bytes[0] == b'F' {
+        if let Ok(n @ 1..=24) = upper[1..].parse::<UINT>() {
+            return Some(VK_F1 + n - 1);
+        }
+    }
+
+    KEY_NAMES.iter()
+        .find(|&&(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|&(_, vk)| vk)
+}
+
+pub fn key_name(vk: UINT) -> Option<String> {
+    match vk {
+        VK_0..=VK_9 | VK_A..=0x5A => Some((vk as u8 as char).to_string()),
+        VK_F1..=0x87 => Some(format!("F{}", vk - VK_F1 + 1)),
+        _ => KEY_NAMES.iter().find(|&&(_, key)| key == vk).map(|&(name, _)| name.to_string()),
+    }
+}
+
+/// What a hotkey does, named like `switch 3` or `tile left_half` in `winman.ini`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    ShowPopup,
+    Grab(UINT),
+    Switch(UINT),
+    SwitchBack(UINT),
+    Clear(UINT),
+    Release,
+    ShiftUp,
+    ShiftDown,
+    CycleLogLevel,
+    Tile(Tile),
+    /// Show the virtual desktop with this number, counting from 1
+    SwitchDesktop(usize),
+    /// Activate the profile after the active one, see `Settings::profiles`
+    NextProfile,
+    /// Wait for a key sequence, see `leader::Leader`
+    Leader,
+}
+
+impl FromStr for Action {
+    type Err = ();
+
+    /// Parses the commands that can be bound, see `commands::COMMANDS`
+    fn from_str(name: &str) -> Result<Self, ()> {
+        match name.parse::<Command>() {
+            Ok(Command::Action(action)) => Ok(action),
+            _ => Err(())
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Action::Quit => write!(f, "quit"),
+            Action::ShowPopup => write!(f, "popup"),
+            Action::Grab(vk) => write!(f, "grab {}", vk - VK_0),
+            Action::Switch(vk) => write!(f, "switch {}", vk - VK_0),
+            Action::SwitchBack(vk) => write!(f, "switch_back {}", vk - VK_0),
+            Action::Clear(vk) => write!(f, "clear {}", vk - VK_0),
+            Action::Release => write!(f, "release"),
+            Action::ShiftUp => write!(f, "shift_up"),
+            Action::ShiftDown => write!(f, "shift_down"),
+            Action::CycleLogLevel => write!(f, "log_level"),
+            Action::SwitchDesktop(number) => write!(f, "desktop {}", number),
+            Action::NextProfile => write!(f, "next_profile"),
+            Action::Leader => write!(f, "leader"),
+            Action::Tile(tile) => {
+                let name = TILE_NAMES.iter().find(|&&(_, t)| t == tile).map(|&(name, _)| name).unwrap();
+                write!(f, "tile {}", name)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub action: Action,
+    pub chord: Chord,
+    /// Registered instead when `chord` is taken
+    pub fallback: Option<Chord>,
+}
+
+impl Binding {
+    pub fn new(action: Action, modifiers: UINT, vk: UINT) -> Self {
+        Binding {
+            action: action,
+            chord: Chord::new(modifiers, vk),
+            fallback: None,
+        }
+    }
+}
+
+/// The built-in hotkeys
+///
+/// * `Ctrl+Alt+Q` quits, `Alt+Space` opens the popup
+/// * `Alt+Shift+<digit>` grabs the foreground window into a slot, `Alt+<digit>`
+///   switches to it, `Ctrl+Alt+<digit>` switches backwards and
+///   `Ctrl+Alt+Shift+<digit>` clears the slot
+/// * `Alt+Shift+Delete` removes the foreground window from its slots,
+///   `Alt+Shift+PageUp/PageDown` moves it towards the front or back of them
+/// * `Ctrl+Alt+V` steps the log level through info, debug and trace
+/// * `Ctrl+Alt+Space` starts a key sequence, see `leader::default_sequences`
+/// * `Ctrl+Alt` with the arrows, D/F/G, U/I/J/K, C and Enter tiles the foreground window
+pub fn default_bindings() -> Vec<Binding> {
+    let mut bindings = vec![
+        Binding::new(Action::Quit, MOD_CONTROL | MOD_ALT, VK_Q),
+        Binding::new(Action::ShowPopup, MOD_ALT, 0x20),
+    ];
+
+    for vk in VK_0..VK_9 + 1 {
+        bindings.push(Binding::new(Action::Grab(vk), MOD_ALT | MOD_SHIFT, vk));
+        bindings.push(Binding::new(Action::Switch(vk), MOD_ALT, vk));
+        bindings.push(Binding::new(Action::SwitchBack(vk), MOD_CONTROL | MOD_ALT, vk));
+        bindings.push(Binding::new(Action::Clear(vk), MOD_CONTROL | MOD_ALT | MOD_SHIFT, vk));
+    }
+
+    bindings.push(Binding::new(Action::Release, MOD_ALT | MOD_SHIFT, 0x2E));
+    bindings.push(Binding::new(Action::ShiftUp, MOD_ALT | MOD_SHIFT, 0x21));
+    bindings.push(Binding::new(Action::ShiftDown, MOD_ALT | MOD_SHIFT, 0x22));
+    bindings.push(Binding::new(Action::CycleLogLevel, MOD_CONTROL | MOD_ALT, VK_V));
+    bindings.push(Binding::new(Action::NextProfile, MOD_CONTROL | MOD_ALT, VK_P));
+    bindings.push(Binding::new(Action::Leader, MOD_CONTROL | MOD_ALT, 0x20));
+
+    let tile_keys = [
+        (0x25, Tile::LeftHalf),
+        (0x27, Tile::RightHalf),
+        (VK_D, Tile::LeftThird),
+        (VK_F, Tile::CenterThird),
+        (VK_G, Tile::RightThird),
+        (VK_U, Tile::TopLeftQuarter),
+        (VK_I, Tile::TopRightQuarter),
+        (VK_J, Tile::BottomLeftQuarter),
+        (VK_K, Tile::BottomRightQuarter),
+        (VK_C, Tile::Center),
+        (0x0D, Tile::Maximize),
+    ];
+
+    for &(vk, tile) in tile_keys.iter() {
+        bindings.push(Binding::new(Action::Tile(tile), MOD_CONTROL | MOD_ALT, vk));
+    }
+
+    bindings
+}
+
+/// A chord that could not be registered
+#[derive(Debug)]
+pub struct Failure {
+    pub action: Action,
+    pub chord: Chord,
+    pub error: Error,
+    /// Chord that was registered in its place
+    pub fallback: Option<Chord>,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!{ write!(f, "{} ({}): {}", self.chord, self.action, self.error) };
+
+        match self.fallback {
+            Some(fallback) => write!(f, ", using {} instead", fallback),
+            None => Ok(())
+        }
+    }
+}
+
+/// Outcome of `register_all`, maps hotkey IDs back to their action
+#[derive(Debug, Default)]
+pub struct HotkeyReport {
+    active: Vec<(i32, Action, Chord)>,
+    failures: Vec<Failure>,
+}
+
+impl HotkeyReport {
+    pub fn action(&self, id: i32) -> Option<Action> {
+        self.active.iter().find(|&&(active, _, _)| active == id).map(|&(_, action, _)| action)
+    }
+
+    /// Registered hotkeys with their ID
+    pub fn active(&self) -> &[(i32, Action, Chord)] {
+        &self.active
+    }
+
+    pub fn failures(&self) -> &[Failure] {
+        &self.failures
+    }
+
+    /// Switches from the hotkeys registered for `old` to the ones of `new`
+    ///
+    /// Hotkeys whose binding did not change stay registered under their ID, the
+    /// others are unregistered and registered again like in `register_all`. Bindings
+    /// that failed before are retried, and kept ones running on their fallback keep
+    /// the failure of their first chord.
+    pub fn update<F, G>(self, old: &[Binding], new: &[Binding], mut register: F, mut unregister: G) -> HotkeyReport
+        where F: FnMut(i32, Chord) -> Win32Result<()>,
+              G: FnMut(i32)
+    {
+        let mut report = HotkeyReport::default();
+
+        for (id, action, chord) in self.active {
+            let index = (id - 1) as usize;
+
+            if index < new.len() && old.get(index) == Some(&new[index]) {
+                report.active.push((id, action, chord));
+            } else {
+                unregister(id);
+            }
+        }
+
+        for failure in self.failures {
+            let kept = report.active.iter()
+                .any(|&(_, action, chord)| action == failure.action && Some(chord) == failure.fallback);
+
+            if kept {
+                report.failures.push(failure);
+            }
+        }
+
+        for (index, binding) in new.iter().enumerate() {
+            let id = index as i32 + 1;
+
+            if report.action(id).is_none() {
+                report.register(id, binding, &mut register);
+            }
+        }
+
+        report.active.sort_by_key(|&(id, _, _)| id);
+        report
+    }
+
+    fn register<F>(&mut self, id: i32, binding: &Binding, register: &mut F)
+        where F: FnMut(i32, Chord) -> Win32Result<()>
+    {
+        let err = match register(id, binding.chord) {
+            Ok(_) => {
+                self.active.push((id, binding.action, binding.chord));
+                return;
+            },
+            Err(err) => err
+        };
+
+        let mut fallback_failure = None;
+        let fallback = binding.fallback.and_then(|fallback| match register(id, fallback) {
+            Ok(_) => Some(fallback),
+            Err(err) => {
+                fallback_failure = Some(Failure {
+                    action: binding.action,
+                    chord: fallback,
+                    error: err,
+                    fallback: None,
+                });
+                None
+            }
+        });
+
+        if let Some(fallback) = fallback {
+            self.active.push((id, binding.action, fallback));
+        }
+
+        self.failures.push(Failure {
+            action: binding.action,
+            chord: binding.chord,
+            error: err,
+            fallback: fallback,
+        });
+        self.failures.extend(fallback_failure);
+    }
+}
+
+/// Registers each binding under its own ID with `register`, falling back to the
+/// binding's fallback chord when the first one fails
+pub fn register_all<F>(bindings: &[Binding], mut register: F) -> HotkeyReport
+    where F: FnMut(i32, Chord) -> Win32Result<()>
+{
+    let mut report = HotkeyReport::default();
+
+    for (index, binding) in bindings.iter().enumerate() {
+        report.register(index as i32 + 1, binding, &mut register);
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use constants::*;
+    use error::Error;
+    use layout::tiling::Tile;
+    use super::{default_bindings, register_all, Action, Binding, Chord};
+
+    // ERROR_HOTKEY_ALREADY_REGISTERED
+    const TAKEN: u32 = 1409;
+
+    #[test]
+    fn parse_chord() {
+        assert_eq!(Chord::new(MOD_CONTROL | MOD_ALT, VK_3), "Ctrl+Alt+3".parse().unwrap());
+        assert_eq!(Chord::new(MOD_WIN | MOD_SHIFT, 0x21), "shift + win + pageup".parse().unwrap());
+        assert_eq!(Chord::new(MOD_ALT, 0x7B), "Alt+F12".parse().unwrap());
+        assert_eq!(Chord::new(0, 0x13), "Pause".parse().unwrap());
+
+        assert!("Hyper+3".parse::<Chord>().is_err());
+        assert!("Alt+F25".parse::<Chord>().is_err());
+        assert!("Alt+".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn display_chord() {
+        assert_eq!("Ctrl+Alt+Shift+3", Chord::new(MOD_SHIFT | MOD_ALT | MOD_CONTROL, VK_3).to_string());
+        assert_eq!("Win+F1", Chord::new(MOD_WIN, 0x70).to_string());
+        assert_eq!("Alt+Space", Chord::new(MOD_ALT, 0x20).to_string());
+        assert_eq!("Alt+0xba", Chord::new(MOD_ALT, 0xBA).to_string());
+    }
+
+    #[test]
+    fn action_names() {
+        for binding in default_bindings() {
+            let name = binding.action.to_string();
+            assert_eq!(Ok(binding.action), name.parse(), "{}", name);
+        }
+
+        assert_eq!(Ok(Action::Switch(VK_3)), "switch  3".parse());
+        assert_eq!(Ok(Action::Tile(Tile::TopLeftQuarter)), "tile top_left".parse());
+        assert_eq!(Err(()), "switch 10".parse::<Action>());
+        assert_eq!(Err(()), "tile".parse::<Action>());
+        assert_eq!(Err(()), "quit now".parse::<Action>());
+
+        for &action in [Action::SwitchDesktop(2), Action::SwitchDesktop(12)].iter() {
+            assert_eq!(Ok(action), action.to_string().parse());
+        }
+        assert_eq!(Err(()), "desktop 0".parse::<Action>());
+    }
+
+    #[test]
+    fn unique_ids() {
+        let bindings = default_bindings();
+        let mut ids = Vec::new();
+        let report = register_all(&bindings, |id, _| {
+            ids.push(id);
+            Ok(())
+        });
+
+        ids.sort();
+        ids.dedup();
+        assert_eq!(bindings.len(), ids.len());
+        assert!(report.failures().is_empty());
+        assert_eq!(Some(Action::Switch(VK_3)), report.action(report.active().iter()
+            .find(|&&(_, _, chord)| chord == Chord::new(MOD_ALT, VK_3))
+            .unwrap().0));
+    }
+
+    #[test]
+    fn failures_and_fallbacks() {
+        let mut bindings = vec![
+            Binding::new(Action::Switch(VK_1), MOD_ALT, VK_1),
+            Binding::new(Action::Switch(VK_3), MOD_ALT, VK_3),
+            Binding::new(Action::Switch(VK_4), MOD_ALT, VK_4),
+            Binding::new(Action::ShowPopup, MOD_ALT, 0x20),
+        ];
+        bindings[1].fallback = Some(Chord::new(MOD_WIN | MOD_ALT, VK_3));
+        bindings[2].fallback = Some(Chord::new(MOD_ALT, VK_1));
+
+        let mut taken = vec![Chord::new(MOD_ALT, VK_3), Chord::new(MOD_ALT, VK_4), Chord::new(MOD_ALT, 0x20)];
+        let report = register_all(&bindings, |_, chord| {
+            if taken.contains(&chord) {
+                Err(Error::Win32(TAKEN))
+            } else {
+                taken.push(chord);
+                Ok(())
+            }
+        });
+
+        let active: Vec<_> = report.active().iter().map(|&(_, action, chord)| (action, chord.to_string())).collect();
+        assert_eq!(vec![
+            (Action::Switch(VK_1), "Alt+1".to_string()),
+            (Action::Switch(VK_3), "Alt+Win+3".to_string()),
+        ], active);
+
+        let failures: Vec<_> = report.failures().iter().map(|f| (f.action, f.chord.to_string(), f.fallback)).collect();
+        assert_eq!(vec![
+            (Action::Switch(VK_3), "Alt+3".to_string(), Some(Chord::new(MOD_WIN | MOD_ALT, VK_3))),
+            (Action::Switch(VK_4), "Alt+4".to_string(), None),
+            (Action::Switch(VK_4), "Alt+1".to_string(), None),
+            (Action::ShowPopup, "Alt+Space".to_string(), None),
+        ], failures);
+
+        let message = report.failures()[0].to_string();
+        assert!(message.starts_with("Alt+3 (switch 3): "), "{}", message);
+        assert!(message.contains("0x581"), "{}", message);
+        assert!(message.ends_with(", using Alt+Win+3 instead"), "{}", message);
+    }
+
+    #[test]
+    fn update() {
+        let old = vec![
+            Binding::new(Action::Switch(VK_1), MOD_ALT, VK_1),
+            Binding::new(Action::Switch(VK_2), MOD_ALT, VK_2),
+            Binding::new(Action::Switch(VK_3), MOD_ALT, VK_3),
+            Binding::new(Action::ShowPopup, MOD_ALT, 0x20),
+        ];
+        let report = register_all(&old, |_, chord| match chord.vk {
+            0x20 => Err(Error::Win32(TAKEN)),
+            _ => Ok(())
+        });
+
+        let mut new = old[..3].to_vec();
+        new[1] = Binding::new(Action::Switch(VK_2), MOD_WIN, VK_2);
+
+        let mut registered = Vec::new();
+        let mut unregistered = Vec::new();
+        let report = report.update(&old, &new, |id, chord| {
+            registered.push((id, chord));
+            Ok(())
+        }, |id| unregistered.push(id));
+
+        assert_eq!(vec![(2, Chord::new(MOD_WIN, VK_2))], registered);
+        assert_eq!(vec![2], unregistered);
+        assert!(report.failures().is_empty());
+        assert_eq!(vec![
+            (1, Action::Switch(VK_1), Chord::new(MOD_ALT, VK_1)),
+            (2, Action::Switch(VK_2), Chord::new(MOD_WIN, VK_2)),
+            (3, Action::Switch(VK_3), Chord::new(MOD_ALT, VK_3)),
+        ], report.active());
+
+        // Failed bindings are retried even if they did not change
+        let report = report.update(&new, &old, |_, _| Err(Error::Win32(TAKEN)), |_| {});
+        assert_eq!(2, report.active().len());
+        assert_eq!(vec![Action::Switch(VK_2), Action::ShowPopup],
+            report.failures().iter().map(|f| f.action).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn update_keeps_fallback_failures() {
+        let bindings = vec![
+            Binding::new(Action::Switch(VK_1), MOD_ALT, VK_1),
+            Binding { fallback: Some(Chord::new(MOD_WIN, 0x20)), ..Binding::new(Action::ShowPopup, MOD_ALT, 0x20) },
+        ];
+        let report = register_all(&bindings, |_, chord| match chord {
+            chord if chord == Chord::new(MOD_ALT, 0x20) => Err(Error::Win32(TAKEN)),
+            _ => Ok(())
+        });
+        assert_eq!(1, report.failures().len());
+
+        let mut registered = Vec::new();
+        let report = report.update(&bindings, &bindings, |id, chord| {
+            registered.push((id, chord));
+            Ok(())
+        }, |_| {});
+
+        assert!(registered.is_empty());
+        assert_eq!(1, report.failures().len());
+        assert_eq!(Chord::new(MOD_ALT, 0x20), report.failures()[0].chord);
+        assert_eq!(Some(Chord::new(MOD_WIN, 0x20)), report.failures()[0].fallback);
+        assert_eq!((2, Action::ShowPopup, Chord::new(MOD_WIN, 0x20)), report.active()[1]);
+    }
+}
@@ -0,0 +1,287 @@
+use std::error;
+use std::fmt;
+
+/// Minimal INI document used for the files in the config directory
+///
+/// Sections keep their order and may repeat, which lets a file hold lists of
+/// records (one `[name]` block per record). Keys before the first header belong
+/// to a section with an empty name. Lines starting with `#` or `;` are comments.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Ini {
+    sections: Vec<Section>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub name: String,
+    pub line: usize,
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    key: String,
+    value: String,
+    line: usize,
+    /// 1-based column where the value starts
+    column: usize,
+}
+
+/// A value together with its position in the file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Value<'a> {
+    pub text: &'a str,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// 1-based line of the offending text
+    pub line: usize,
+    /// 1-based column of the offending text
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl error::Error for ParseError {}
+
+impl Section {
+    pub fn new(name: &str) -> Self {
+        Section {
+            name: name.to_string(),
+            line: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Value of the last entry for `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.key == key)
+            .map(|e| e.value.as_str())
+    }
+
+    /// Line of the last entry for `key`, for error reporting
+    pub fn line_of(&self, key: &str) -> usize {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.key == key)
+            .map_or(self.line, |e| e.line)
+    }
+
+    /// Every value of `key` in order, for keys that may repeat
+    pub fn get_all(&self, key: &str) -> Vec<Value<'_>> {
+        self.entries
+            .iter()
+            .filter(|e| e.key == key)
+            .map(|e| Value { text: &e.value, line: e.line, column: e.column })
+            .collect()
+    }
+
+    pub fn entries(&self) -> Vec<(&str, &str)> {
+        self.entries
+            .iter()
+            .map(|e| (e.key.as_str(), e.value.as_str()))
+            .collect()
+    }
+
+    pub fn push(&mut self, key: &str, value: &str) {
+        self.entries.push(Entry {
+            key: key.to_string(),
+            value: value.to_string(),
+            line: 0,
+            column: 0,
+        });
+    }
+}
+
+impl Ini {
+    pub fn new() -> Self {
+        Ini { sections: Vec::new() }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut sections = vec![Section::new("")];
+
+        for (index, raw) in text.lines().enumerate() {
+            let line = index + 1;
+            let indent = raw.len() - raw.trim_start().len();
+            let trimmed = raw.trim();
+
+            if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+                continue;
+            }
+
+            if trimmed.starts_with('[') {
+                if !trimmed.ends_with(']') {
+                    return Err(ParseError {
+                        line,
+                        column: indent + trimmed.len() + 1,
+                        message: "expected ']' at the end of the section header".to_string(),
+                    });
+                }
+
+                let name = trimmed[1..trimmed.len() - 1].trim();
+                let mut section = Section::new(name);
+                section.line = line;
+                sections.push(section);
+                continue;
+            }
+
+            let separator = match trimmed.find('=') {
+                Some(separator) => separator,
+                None => return Err(ParseError {
+                    line,
+                    column: indent + 1,
+                    message: "expected 'key = value'".to_string(),
+                }),
+            };
+
+            let key = trimmed[..separator].trim();
+            if key.is_empty() {
+                return Err(ParseError {
+                    line,
+                    column: indent + 1,
+                    message: "missing key before '='".to_string(),
+                });
+            }
+
+            let raw_value = &trimmed[separator + 1..];
+            let value = raw_value.trim();
+
+            let section = sections.last_mut().unwrap();
+            section.entries.push(Entry {
+                key: key.to_string(),
+                value: value.to_string(),
+                line,
+                column: indent + separator + 1 + (raw_value.len() - raw_value.trim_start().len()) + 1,
+            });
+        }
+
+        // Drop the implicit leading section if nothing was written to it
+        if sections[0].entries.is_empty() {
+            sections.remove(0);
+        }
+
+        Ok(Ini { sections })
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// First section called `name`
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    pub fn push(&mut self, section: Section) {
+        self.sections.push(section);
+    }
+}
+
+impl fmt::Display for Ini {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, section) in self.sections.iter().enumerate() {
+            if i > 0 {
+                try!{ writeln!(f) };
+            }
+
+            if !section.name.is_empty() || i > 0 {
+                try!{ writeln!(f, "[{}]", section.name) };
+            }
+
+            for entry in &section.entries {
+                try!{ writeln!(f, "{} = {}", entry.key, entry.value) };
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ini, Section};
+
+    #[test]
+    fn parse() {
+        let ini = Ini::parse("
+            # Comment
+            global = 1
+
+            [popup]
+            placement = cursor
+            ; Another comment
+            theme=dark = yes
+        ").unwrap();
+
+        assert_eq!(2, ini.sections().len());
+        assert_eq!(Some("1"), ini.section("").unwrap().get("global"));
+        assert_eq!(Some("cursor"), ini.section("popup").unwrap().get("placement"));
+        assert_eq!(Some("dark = yes"), ini.section("popup").unwrap().get("theme"));
+        assert_eq!(6, ini.section("popup").unwrap().line_of("placement"));
+    }
+
+    #[test]
+    fn get_all() {
+        let ini = Ini::parse("[slot 1]\nrule = exe = a.exe\n  rule=title ~ b\n").unwrap();
+        let values = ini.section("slot 1").unwrap().get_all("rule");
+
+        assert_eq!(2, values.len());
+        assert_eq!(("exe = a.exe", 2, 8), (values[0].text, values[0].line, values[0].column));
+        assert_eq!(("title ~ b", 3, 8), (values[1].text, values[1].line, values[1].column));
+    }
+
+    #[test]
+    fn repeated_sections() {
+        let ini = Ini::parse("[window]\ntitle = a\n[window]\ntitle = b\n").unwrap();
+        let titles: Vec<_> = ini.sections().iter().map(|s| s.get("title").unwrap()).collect();
+
+        assert_eq!(vec!["a", "b"], titles);
+    }
+
+    #[test]
+    fn last_value_wins() {
+        let ini = Ini::parse("[a]\nkey = 1\nkey = 2").unwrap();
+
+        assert_eq!(Some("2"), ini.section("a").unwrap().get("key"));
+    }
+
+    #[test]
+    fn errors() {
+        let err = Ini::parse("[a]\n  [broken\n").unwrap_err();
+        assert_eq!((2, 10), (err.line, err.column));
+
+        let err = Ini::parse("[a]\nkey = 1\n    no separator").unwrap_err();
+        assert_eq!((3, 5), (err.line, err.column));
+
+        let err = Ini::parse(" = value").unwrap_err();
+        assert_eq!((1, 2), (err.line, err.column));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut ini = Ini::new();
+        let mut section = Section::new("layout");
+        section.push("title", "Visual Studio Code");
+        section.push("bounds", "0,0,960,1040");
+        ini.push(section.clone());
+        ini.push(section);
+
+        let parsed = Ini::parse(&ini.to_string()).unwrap();
+
+        assert_eq!(2, parsed.sections().len());
+        assert_eq!(Some("0,0,960,1040"), parsed.sections()[1].get("bounds"));
+    }
+}
@@ -0,0 +1,357 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A JSON value, enough of it for the browser bridge, see `native_host`
+///
+/// Objects keep their keys in order. Numbers are `f64` like in JavaScript.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Value of `key` if this is an object that has it
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref entries) => entries.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref value)| value),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Json::String(ref text) => Some(text),
+            _ => None
+        }
+    }
+
+    /// Whole numbers that are not negative, like tab IDs
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Json::Number(n) if n >= 0.0 && n.fract() == 0.0 && n < 9007199254740992.0 => Some(n as u64),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match *self {
+            Json::Array(ref items) => Some(items),
+            _ => None
+        }
+    }
+}
+
+impl FromStr for Json {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let mut parser = Parser { text: text, pos: 0 };
+        let value = try!{ parser.value(0) };
+
+        parser.skip_whitespace();
+        if parser.pos < text.len() {
+            return Err(parser.error("trailing characters"));
+        }
+
+        Ok(value)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            // Whole numbers are written without a fraction, JSON has no NaN
+            Json::Number(n) if !n.is_finite() => write!(f, "null"),
+            Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", n as i64),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(ref text) => write_string(f, text),
+            Json::Array(ref items) => {
+                try!{ write!(f, "[") };
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        try!{ write!(f, ",") };
+                    }
+                    try!{ write!(f, "{}", item) };
+                }
+                write!(f, "]")
+            },
+            Json::Object(ref entries) => {
+                try!{ write!(f, "{{") };
+                for (i, &(ref key, ref value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        try!{ write!(f, ",") };
+                    }
+                    try!{ write_string(f, key) };
+                    try!{ write!(f, ":{}", value) };
+                }
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, text: &str) -> fmt::Result {
+    try!{ write!(f, "\"") };
+
+    for c in text.chars() {
+        match c {
+            '"' => try!{ write!(f, "\\\"") },
+            '\\' => try!{ write!(f, "\\\\") },
+            '\n' => try!{ write!(f, "\\n") },
+            '\r' => try!{ write!(f, "\\r") },
+            '\t' => try!{ write!(f, "\\t") },
+            c if (c as u32) < 0x20 => try!{ write!(f, "\\u{:04x}", c as u32) },
+            c => try!{ write!(f, "{}", c) },
+        }
+    }
+
+    write!(f, "\"")
+}
+
+/// Arrays and objects nested deeper than this are rejected
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    text: &'a str,
+    /// Byte offset of the next character
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at offset {}", message, self.pos)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if let Some(c) = c {
+            self.pos += c.len_utf8();
+        }
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ') | Some('\t') | Some('\n') | Some('\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        if self.text[self.pos..].starts_with(word) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("unexpected character"))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+
+        self.skip_whitespace();
+        match self.peek() {
+            Some('n') => self.literal("null", Json::Null),
+            Some('t') => self.literal("true", Json::Bool(true)),
+            Some('f') => self.literal("false", Json::Bool(false)),
+            Some('"') => self.string().map(Json::String),
+            Some('[') => self.array(depth),
+            Some('{') => self.object(depth),
+            Some('-') | Some('0'..='9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while let Some('0'..='9') | Some('-') | Some('+') | Some('.') | Some('e') | Some('E') = self.peek() {
+            self.pos += 1;
+        }
+
+        self.text[start..self.pos]
+            .parse()
+            .map(Json::Number)
+            .map_err(|_| format!("invalid number at offset {}", start))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        try!{ self.expect('"') };
+        let mut text = String::new();
+
+        loop {
+            match self.next() {
+                Some('"') => return Ok(text),
+                Some('\\') => {
+                    let c = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => try!{ self.escaped_char() },
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    text.push(c);
+                },
+                Some(c) if (c as u32) < 0x20 => return Err(self.error("control character in string")),
+                Some(c) => text.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    /// Character of a `\u` escape, which may be the first half of a surrogate pair
+    fn escaped_char(&mut self) -> Result<char, String> {
+        let high = try!{ self.hex4() };
+
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if !self.text[self.pos..].starts_with("\\u") {
+                return Err(self.error("unpaired surrogate"));
+            }
+            self.pos += 2;
+
+            let low = try!{ self.hex4() };
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("unpaired surrogate"));
+            }
+
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+
+        ::std::char::from_u32(code).ok_or_else(|| self.error("invalid character escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self.text.get(self.pos..self.pos + 4).unwrap_or("");
+
+        if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(self.error("invalid unicode escape"));
+        }
+
+        self.pos += 4;
+        Ok(u32::from_str_radix(digits, 16).unwrap())
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Json, String> {
+        try!{ self.expect('[') };
+        let mut items = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+
+        loop {
+            items.push(try!{ self.value(depth + 1) });
+
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => {},
+                Some(']') => return Ok(Json::Array(items)),
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Json, String> {
+        try!{ self.expect('{') };
+        let mut entries = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Json::Object(entries));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = try!{ self.string() };
+            try!{ self.expect(':') };
+            entries.push((key, try!{ self.value(depth + 1) }));
+
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => {},
+                Some('}') => return Ok(Json::Object(entries)),
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Json;
+
+    #[test]
+    fn parse() {
+        let json: Json = r#" {"type": "tabs", "tabs": [{"id": 12, "title": "Inbox \"1\"", "pinned": true}, null], "ratio": -1.5e2} "#
+            .parse()
+            .unwrap();
+
+        assert_eq!(Some("tabs"), json.get("type").and_then(Json::as_str));
+        assert_eq!(Some(-150.0), match json.get("ratio") { Some(&Json::Number(n)) => Some(n), _ => None });
+
+        let tabs = json.get("tabs").and_then(Json::as_array).unwrap();
+        assert_eq!(2, tabs.len());
+        assert_eq!(Some(12), tabs[0].get("id").and_then(Json::as_u64));
+        assert_eq!(Some("Inbox \"1\""), tabs[0].get("title").and_then(Json::as_str));
+        assert_eq!(Some(&Json::Bool(true)), tabs[0].get("pinned"));
+        assert_eq!(Json::Null, tabs[1]);
+
+        assert_eq!(Ok(Json::String("é 😀 /\n".to_string())), r#""\u00e9 \ud83d\ude00 \/\n""#.parse());
+        assert_eq!(Ok(Json::Array(vec![])), "[ ]".parse());
+    }
+
+    #[test]
+    fn errors() {
+        for text in ["", "{", "[1,]", "{\"a\" 1}", "\"open", "tru", "1 2", "\"\\x\"", "\"\\ud83d\"", "-", "\"a\nb\""].iter() {
+            assert!(text.parse::<Json>().is_err(), "{}", text);
+        }
+
+        let deep = "[".repeat(100) + &"]".repeat(100);
+        assert!(deep.parse::<Json>().is_err());
+    }
+
+    #[test]
+    fn display() {
+        let json = Json::Object(vec![
+            ("type".to_string(), Json::String("activate".to_string())),
+            ("tabId".to_string(), Json::Number(12.0)),
+            ("ratio".to_string(), Json::Number(0.5)),
+            ("title".to_string(), Json::String("a\"b\\c\u{1}".to_string())),
+            ("tags".to_string(), Json::Array(vec![Json::Bool(false), Json::Null])),
+        ]);
+
+        let text = json.to_string();
+        assert_eq!(r#"{"type":"activate","tabId":12,"ratio":0.5,"title":"a\"b\\c\u0001","tags":[false,null]}"#, text);
+        assert_eq!(Ok(json), text.parse());
+    }
+}
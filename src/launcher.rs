@@ -0,0 +1,222 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use winapi::minwindef::UINT;
+use winapi::windef::HWND;
+
+use backend::WindowBackend;
+use eligibility;
+use utils::Win32Result;
+
+/// Program started when focusing a slot that has no windows
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchCommand {
+    pub exe: String,
+    /// Raw command line arguments, passed on unquoted
+    pub args: String,
+    pub working_dir: Option<String>,
+    /// How long to wait for the first window
+    pub timeout: Duration,
+}
+
+impl LaunchCommand {
+    pub fn new(exe: &str) -> Self {
+        LaunchCommand {
+            exe: exe.to_string(),
+            args: String::new(),
+            working_dir: None,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// File name of the executable, e.g. `Code.exe`
+    pub fn exe_name(&self) -> &str {
+        self.exe
+            .rsplit(|c| c == '\\' || c == '/')
+            .next()
+            .unwrap_or(&self.exe)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LaunchEvent {
+    /// The first window of a launched program appeared and belongs in the slot
+    Grabbed(UINT, HWND),
+    /// No window appeared before the command's timeout
+    TimedOut(UINT),
+}
+
+struct PendingLaunch {
+    slot: UINT,
+    pid: u32,
+    exe_name: String,
+    deadline: Instant,
+    /// Windows that existed before the launch and must not be grabbed
+    known: HashSet<HWND>,
+}
+
+/// Starts programs for empty slots and waits for their first window
+///
+/// A window is picked up when it did not exist at launch time and either belongs
+/// to the spawned process or to the same executable, since many programs hand
+/// the request to an already running instance or a helper process.
+pub struct Launcher {
+    pending: Vec<PendingLaunch>,
+}
+
+impl Launcher {
+    pub fn new() -> Self {
+        Launcher { pending: Vec::new() }
+    }
+
+    pub fn is_pending(&self, slot: UINT) -> bool {
+        self.pending.iter().any(|p| p.slot == slot)
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Starts `command` for `slot`, unless a launch for the slot is still waiting
+    pub fn launch<B: WindowBackend>(&mut self, backend: &mut B, slot: UINT, command: &LaunchCommand, now: Instant) -> Win32Result<()> {
+        if self.is_pending(slot) {
+            return Ok(());
+        }
+
+        let known = backend.windows().into_iter().collect();
+        let pid = try!{ backend.spawn(command) };
+
+        self.pending.push(PendingLaunch {
+            slot: slot,
+            pid: pid,
+            exe_name: command.exe_name().to_string(),
+            deadline: now + command.timeout,
+            known: known,
+        });
+
+        Ok(())
+    }
+
+    /// Looks for windows of pending launches, called periodically while `has_pending`
+    pub fn poll<B: WindowBackend>(&mut self, backend: &B, now: Instant) -> Vec<LaunchEvent> {
+        let windows = eligibility::switchable_windows(backend);
+        let mut events = Vec::new();
+
+        self.pending.retain(|launch| {
+            let grabbed = windows
+                .iter()
+                .filter(|hwnd| !launch.known.contains(hwnd))
+                .find(|&&hwnd| {
+                    let same_process = backend.window_process_id(hwnd).ok() == Some(launch.pid);
+                    let same_exe = backend.window_identity(hwnd)
+                        .map(|identity| identity.exe.eq_ignore_ascii_case(&launch.exe_name))
+                        .unwrap_or(false);
+
+                    same_process || same_exe
+                });
+
+            match grabbed {
+                Some(&hwnd) => {
+                    events.push(LaunchEvent::Grabbed(launch.slot, hwnd));
+                    false
+                },
+                None if now >= launch.deadline => {
+                    events.push(LaunchEvent::TimedOut(launch.slot));
+                    false
+                },
+                None => true
+            }
+        });
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use backend::fake::{self, FakeBackend};
+    use layout::Rect;
+    use super::{LaunchCommand, LaunchEvent, Launcher};
+
+    const SLOT: u32 = 0x31;
+
+    fn command() -> LaunchCommand {
+        LaunchCommand::new(r"C:\Program Files\Microsoft VS Code\Code.exe")
+    }
+
+    #[test]
+    fn grabs_first_new_window() {
+        let mut backend = FakeBackend::new(vec![fake::monitor(0, 0, 1920, 1080, true)]);
+        let mut launcher = Launcher::new();
+        let now = Instant::now();
+
+        launcher.launch(&mut backend, SLOT, &command(), now).unwrap();
+        assert_eq!(vec![command()], backend.spawned);
+        assert!(launcher.is_pending(SLOT));
+
+        // Nothing yet
+        assert_eq!(Vec::<LaunchEvent>::new(), launcher.poll(&backend, now + Duration::from_secs(1)));
+
+        let pid = backend.last_pid;
+        let hwnd = backend.add_window(7, "Welcome - Visual Studio Code", Rect::default());
+        backend.window_mut(hwnd).unwrap().pid = pid;
+
+        assert_eq!(vec![LaunchEvent::Grabbed(SLOT, hwnd)], launcher.poll(&backend, now + Duration::from_secs(2)));
+        assert!(!launcher.has_pending());
+    }
+
+    #[test]
+    fn ignores_existing_windows() {
+        let mut backend = FakeBackend::new(vec![fake::monitor(0, 0, 1920, 1080, true)]);
+        let mut launcher = Launcher::new();
+        let now = Instant::now();
+
+        let existing = backend.add_identified_window(1, fake::identity("Code.exe", "Chrome_WidgetWin_1", "old"), Rect::default());
+        launcher.launch(&mut backend, SLOT, &command(), now).unwrap();
+
+        // An unrelated window opens, then the editor window from a helper process
+        backend.add_window(2, "Notepad", Rect::default());
+        let hwnd = backend.add_identified_window(3, fake::identity("code.exe", "Chrome_WidgetWin_1", "new"), Rect::default());
+
+        assert_eq!(vec![LaunchEvent::Grabbed(SLOT, hwnd)], launcher.poll(&backend, now));
+        assert!(backend.window(existing).is_some());
+    }
+
+    #[test]
+    fn times_out() {
+        let mut backend = FakeBackend::new(vec![fake::monitor(0, 0, 1920, 1080, true)]);
+        let mut launcher = Launcher::new();
+        let now = Instant::now();
+
+        launcher.launch(&mut backend, SLOT, &command(), now).unwrap();
+
+        assert!(launcher.poll(&backend, now + Duration::from_secs(9)).is_empty());
+        assert_eq!(vec![LaunchEvent::TimedOut(SLOT)], launcher.poll(&backend, now + Duration::from_secs(10)));
+        assert!(!launcher.is_pending(SLOT));
+    }
+
+    #[test]
+    fn launches_once_per_slot() {
+        let mut backend = FakeBackend::new(vec![fake::monitor(0, 0, 1920, 1080, true)]);
+        let mut launcher = Launcher::new();
+        let now = Instant::now();
+
+        launcher.launch(&mut backend, SLOT, &command(), now).unwrap();
+        launcher.launch(&mut backend, SLOT, &command(), now).unwrap();
+        launcher.launch(&mut backend, SLOT + 1, &command(), now).unwrap();
+
+        assert_eq!(2, backend.spawned.len());
+    }
+
+    #[test]
+    fn spawn_failure() {
+        let mut backend = FakeBackend::new(vec![fake::monitor(0, 0, 1920, 1080, true)]);
+        let mut launcher = Launcher::new();
+        backend.fail_spawn = true;
+
+        assert!(launcher.launch(&mut backend, SLOT, &command(), Instant::now()).is_err());
+        assert!(!launcher.has_pending());
+    }
+}
@@ -0,0 +1,390 @@
+use std::cmp;
+use std::collections::BTreeMap;
+
+use winapi::windef::HWND;
+
+use backend::{ShowState, WindowBackend, WindowIdentity, WindowPlacement};
+use eligibility;
+use ini::{Ini, ParseError, Section};
+use layout::Rect;
+use layout::monitor::{self, Monitor};
+use titles::WindowTitle;
+
+/// A window as it was placed when its layout was saved
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedWindow {
+    pub identity: WindowIdentity,
+    /// Bounds of the monitor the window was on
+    pub monitor: Rect,
+    pub placement: WindowPlacement,
+}
+
+pub type SavedLayout = Vec<SavedWindow>;
+
+/// Named window arrangements, stored in `layouts.ini` as one section per window
+///
+/// A layout without windows is stored as one section without entries.
+#[derive(Debug, Default)]
+pub struct Layouts {
+    layouts: BTreeMap<String, SavedLayout>,
+}
+
+impl Layouts {
+    pub fn new() -> Self {
+        Layouts { layouts: BTreeMap::new() }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SavedLayout> {
+        self.layouts.get(name)
+    }
+
+    pub fn insert(&mut self, name: &str, layout: SavedLayout) {
+        self.layouts.insert(name.to_string(), layout);
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.layouts.keys().map(|k| k.as_str()).collect()
+    }
+
+    pub fn from_ini(ini: &Ini) -> Result<Self, ParseError> {
+        let mut layouts = Layouts::new();
+
+        for section in ini.sections() {
+            let layout = layouts.layouts
+                .entry(section.name.clone())
+                .or_insert(Vec::new());
+
+            if !section.entries().is_empty() {
+                layout.push(try!{ parse_window(section) });
+            }
+        }
+
+        Ok(layouts)
+    }
+
+    pub fn to_ini(&self) -> Ini {
+        let mut ini = Ini::new();
+
+        for (name, layout) in &self.layouts {
+            if layout.is_empty() {
+                ini.push(Section::new(name));
+            }
+
+            for window in layout {
+                let mut section = Section::new(name);
+                section.push("exe", &window.identity.exe);
+                section.push("class", &window.identity.class);
+                section.push("title", window.identity.title.text().unwrap_or(""));
+                section.push("monitor", &format_rect(&window.monitor));
+                section.push("bounds", &format_rect(&window.placement.bounds));
+                section.push("state", match window.placement.state {
+                    ShowState::Normal => "normal",
+                    ShowState::Minimized => "minimized",
+                    ShowState::Maximized => "maximized",
+                });
+
+                ini.push(section);
+            }
+        }
+
+        ini
+    }
+}
+
+/// Records the identity and placement of every switchable window
+pub fn capture<B: WindowBackend>(backend: &B) -> SavedLayout {
+    let monitors = backend.monitors();
+
+    eligibility::switchable_windows(backend)
+        .into_iter()
+        .filter_map(|hwnd| {
+            let identity = backend.window_identity(hwnd).ok();
+            let placement = backend.window_placement(hwnd).ok();
+
+            match (identity, placement) {
+                (Some(identity), Some(placement)) => {
+                    let monitor = monitor::monitor_from_bounds(&monitors, placement.bounds)
+                        .map(|m| m.bounds)
+                        .unwrap_or(Rect::default());
+
+                    Some(SavedWindow {
+                        identity: identity,
+                        monitor: monitor,
+                        placement: placement,
+                    })
+                },
+                _ => None
+            }
+        })
+        .collect()
+}
+
+/// Moves the live windows matching `layout` back into place
+///
+/// Returns the number of windows that were restored.
+pub fn restore<B: WindowBackend>(backend: &mut B, layout: &SavedLayout) -> usize {
+    let monitors = backend.monitors();
+    let live: Vec<_> = eligibility::switchable_windows(backend)
+        .into_iter()
+        .filter_map(|hwnd| backend.window_identity(hwnd).ok().map(|identity| (hwnd, identity)))
+        .collect();
+
+    let mut restored = 0;
+    for (index, hwnd) in match_windows(layout, &live) {
+        let saved = &layout[index];
+        let placement = WindowPlacement {
+            bounds: target_bounds(saved, &monitors),
+            state: saved.placement.state,
+        };
+
+        if backend.set_window_placement(hwnd, &placement).is_ok() {
+            restored += 1;
+        }
+    }
+
+    restored
+}
+
+/// Pairs saved windows with live windows, each live window is used at most once
+///
+/// A live window is a candidate when its executable and class match. Candidates
+/// with the same title are preferred, then saved windows are served in order,
+/// so two editor windows with changed titles still map to two distinct windows.
+pub fn match_windows(saved: &[SavedWindow], live: &[(HWND, WindowIdentity)]) -> Vec<(usize, HWND)> {
+    let mut candidates = Vec::new();
+
+    for (saved_index, window) in saved.iter().enumerate() {
+        for (live_index, &(_, ref identity)) in live.iter().enumerate() {
+            let same_app = window.identity.exe.eq_ignore_ascii_case(&identity.exe)
+                && window.identity.class == identity.class;
+
+            if same_app {
+                let same_title = window.identity.title == identity.title;
+                candidates.push((!same_title, saved_index, live_index));
+            }
+        }
+    }
+
+    candidates.sort();
+
+    let mut used_saved = vec![false; saved.len()];
+    let mut used_live = vec![false; live.len()];
+    let mut matches = Vec::new();
+
+    for (_, saved_index, live_index) in candidates {
+        if !used_saved[saved_index] && !used_live[live_index] {
+            used_saved[saved_index] = true;
+            used_live[live_index] = true;
+            matches.push((saved_index, live[live_index].0));
+        }
+    }
+
+    matches.sort();
+    matches
+}
+
+/// Saved bounds if the monitor is still connected, otherwise the same position
+/// relative to the primary monitor, shrunk to fit its work area
+fn target_bounds(saved: &SavedWindow, monitors: &[Monitor]) -> Rect {
+    if monitors.iter().any(|m| m.bounds == saved.monitor) {
+        return saved.placement.bounds;
+    }
+
+    match monitor::primary_monitor(monitors) {
+        Some(primary) => {
+            let bounds = saved.placement.bounds.offset(
+                primary.bounds.left - saved.monitor.left,
+                primary.bounds.top - saved.monitor.top);
+
+            fit_into(bounds, primary.work_area)
+        },
+        None => saved.placement.bounds
+    }
+}
+
+fn fit_into(bounds: Rect, area: Rect) -> Rect {
+    let w = cmp::min(bounds.width(), area.width());
+    let h = cmp::min(bounds.height(), area.height());
+    let x = cmp::max(cmp::min(bounds.left, area.right - w), area.left);
+    let y = cmp::max(cmp::min(bounds.top, area.bottom - h), area.top);
+
+    Rect::from_origin_size(x, y, w, h)
+}
+
+fn parse_window(section: &Section) -> Result<SavedWindow, ParseError> {
+    let error = |key: &str, message: &str| ParseError {
+        line: section.line_of(key),
+        column: 1,
+        message: format!("layout '{}': {}", section.name, message),
+    };
+
+    let rect = |key: &str| -> Result<Rect, ParseError> {
+        let value = try!{ section.get(key).ok_or_else(|| error(key, &format!("missing '{}'", key))) };
+        parse_rect(value).ok_or_else(|| error(key, &format!("'{}' must be 'left,top,right,bottom'", key)))
+    };
+
+    let state = match section.get("state").unwrap_or("normal") {
+        "normal" => ShowState::Normal,
+        "minimized" => ShowState::Minimized,
+        "maximized" => ShowState::Maximized,
+        other => return Err(error("state", &format!("unknown state '{}'", other))),
+    };
+
+    Ok(SavedWindow {
+        identity: WindowIdentity {
+            exe: section.get("exe").unwrap_or("").to_string(),
+            class: section.get("class").unwrap_or("").to_string(),
+            title: WindowTitle::new(section.get("title").unwrap_or("").to_string()),
+        },
+        monitor: try!{ rect("monitor") },
+        placement: WindowPlacement {
+            bounds: try!{ rect("bounds") },
+            state: state,
+        },
+    })
+}
+
+fn parse_rect(s: &str) -> Option<Rect> {
+    let parts: Vec<_> = s.split(',').map(|p| p.trim().parse::<i32>()).collect();
+
+    match parts.as_slice() {
+        [Ok(l), Ok(t), Ok(r), Ok(b)] => Some(Rect::from_edges(*l, *t, *r, *b)),
+        _ => None
+    }
+}
+
+fn format_rect(rect: &Rect) -> String {
+    format!("{},{},{},{}", rect.left, rect.top, rect.right, rect.bottom)
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::{ShowState, WindowBackend, WindowPlacement};
+    use backend::fake::{self, FakeBackend};
+    use ini::Ini;
+    use layout::Rect;
+    use super::{capture, match_windows, restore, Layouts, SavedWindow};
+
+    fn saved(exe: &str, class: &str, title: &str, bounds: Rect) -> SavedWindow {
+        SavedWindow {
+            identity: fake::identity(exe, class, title),
+            monitor: Rect::from_edges(0, 0, 1920, 1080),
+            placement: WindowPlacement {
+                bounds: bounds,
+                state: ShowState::Normal,
+            },
+        }
+    }
+
+    fn docked() -> FakeBackend {
+        FakeBackend::new(vec![
+            fake::monitor(0, 0, 1920, 1080, true),
+            fake::monitor(1920, 0, 4480, 1440, false),
+        ])
+    }
+
+    #[test]
+    fn prefers_same_title() {
+        let saved = vec![
+            saved("Code.exe", "Chrome_WidgetWin_1", "winman - Visual Studio Code", Rect::default()),
+            saved("Code.exe", "Chrome_WidgetWin_1", "notes - Visual Studio Code", Rect::default()),
+        ];
+        let live = vec![
+            (fake::hwnd(1), fake::identity("Code.exe", "Chrome_WidgetWin_1", "notes - Visual Studio Code")),
+            (fake::hwnd(2), fake::identity("Code.exe", "Chrome_WidgetWin_1", "winman - Visual Studio Code")),
+        ];
+
+        assert_eq!(vec![(0, fake::hwnd(2)), (1, fake::hwnd(1))], match_windows(&saved, &live));
+    }
+
+    #[test]
+    fn falls_back_to_same_app() {
+        let saved = vec![
+            saved("WindowsTerminal.exe", "CASCADIA_HOSTING_WINDOW_CLASS", "PowerShell", Rect::default()),
+            saved("firefox.exe", "MozillaWindowClass", "Inbox", Rect::default()),
+        ];
+        let live = vec![
+            (fake::hwnd(1), fake::identity("firefox.exe", "MozillaWindowClass", "Pull requests")),
+            (fake::hwnd(2), fake::identity("windowsterminal.exe", "CASCADIA_HOSTING_WINDOW_CLASS", "cmd")),
+            (fake::hwnd(3), fake::identity("explorer.exe", "CabinetWClass", "Inbox")),
+        ];
+
+        assert_eq!(vec![(0, fake::hwnd(2)), (1, fake::hwnd(1))], match_windows(&saved, &live));
+    }
+
+    #[test]
+    fn uses_each_window_once() {
+        let saved = vec![
+            saved("Code.exe", "Chrome_WidgetWin_1", "a", Rect::default()),
+            saved("Code.exe", "Chrome_WidgetWin_1", "b", Rect::default()),
+        ];
+        let live = vec![
+            (fake::hwnd(1), fake::identity("Code.exe", "Chrome_WidgetWin_1", "c")),
+        ];
+
+        assert_eq!(vec![(0, fake::hwnd(1))], match_windows(&saved, &live));
+    }
+
+    #[test]
+    fn capture_and_restore() {
+        let mut backend = docked();
+        let editor = backend.add_identified_window(1, fake::identity("Code.exe", "Chrome_WidgetWin_1", "winman"), Rect::from_edges(0, 0, 960, 1040));
+        let terminal = backend.add_identified_window(2, fake::identity("WindowsTerminal.exe", "CASCADIA_HOSTING_WINDOW_CLASS", "bash"), Rect::from_edges(960, 0, 1920, 1040));
+        let browser = backend.add_identified_window(3, fake::identity("firefox.exe", "MozillaWindowClass", "Docs"), Rect::from_edges(1920, 0, 4480, 1400));
+        backend.window_mut(browser).unwrap().state = ShowState::Maximized;
+
+        let layout = capture(&backend);
+        assert_eq!(3, layout.len());
+        assert_eq!(Rect::from_edges(1920, 0, 4480, 1440), layout[2].monitor);
+
+        // Shuffle everything and reopen the terminal
+        backend.set_window_bounds(editor, Rect::from_edges(100, 100, 500, 500)).unwrap();
+        backend.set_window_bounds(browser, Rect::from_edges(0, 0, 800, 600)).unwrap();
+        backend.close_window(terminal);
+        let terminal = backend.add_identified_window(4, fake::identity("WindowsTerminal.exe", "CASCADIA_HOSTING_WINDOW_CLASS", "vim"), Rect::default());
+
+        assert_eq!(3, restore(&mut backend, &layout));
+        assert_eq!(Rect::from_edges(0, 0, 960, 1040), backend.window(editor).unwrap().bounds);
+        assert_eq!(Rect::from_edges(960, 0, 1920, 1040), backend.window(terminal).unwrap().bounds);
+        assert_eq!(Rect::from_edges(1920, 0, 4480, 1400), backend.window(browser).unwrap().bounds);
+        assert_eq!(ShowState::Maximized, backend.window(browser).unwrap().state);
+    }
+
+    #[test]
+    fn restore_without_monitor() {
+        let mut backend = docked();
+        backend.add_identified_window(1, fake::identity("firefox.exe", "MozillaWindowClass", "Docs"), Rect::from_edges(2000, 100, 4000, 1300));
+        let layout = capture(&backend);
+
+        // Undocked: only the laptop panel is left
+        backend.monitors.truncate(1);
+        restore(&mut backend, &layout);
+
+        assert_eq!(Rect::from_edges(0, 0, 1920, 1040), backend.window(fake::hwnd(1)).unwrap().bounds);
+    }
+
+    #[test]
+    fn ini_round_trip() {
+        let mut layouts = Layouts::new();
+        layouts.insert("docked", vec![
+            saved("Code.exe", "Chrome_WidgetWin_1", "winman", Rect::from_edges(0, 0, 960, 1040)),
+            saved("firefox.exe", "MozillaWindowClass", "Docs", Rect::from_edges(-1000, 0, 0, 800)),
+        ]);
+        layouts.insert("undocked", vec![]);
+
+        let ini = Ini::parse(&layouts.to_ini().to_string()).unwrap();
+        let parsed = Layouts::from_ini(&ini).unwrap();
+
+        assert_eq!(vec!["docked", "undocked"], parsed.names());
+        assert_eq!(layouts.get("docked"), parsed.get("docked"));
+        assert_eq!(Some(&vec![]), parsed.get("undocked"));
+    }
+
+    #[test]
+    fn ini_errors() {
+        let ini = Ini::parse("[docked]\nexe = a.exe\nmonitor = 0,0,10,10\nbounds = 0,0,ten,10\n").unwrap();
+        let err = Layouts::from_ini(&ini).unwrap_err();
+
+        assert_eq!(4, err.line);
+    }
+}
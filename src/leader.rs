@@ -0,0 +1,335 @@
+use std::time::{Duration, Instant};
+
+use winapi::minwindef::UINT;
+
+use constants::*;
+use hotkeys::{self, Action};
+use layout::tiling::Tile;
+
+const VK_ESCAPE: UINT = 0x1B;
+
+/// Key sequences typed after the leader chord and the actions they trigger
+///
+/// No sequence may be the start of another one, so a sequence triggers as soon
+/// as its last key is pressed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyTrie {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Node {
+    action: Option<Action>,
+    children: Vec<(UINT, usize)>,
+}
+
+impl KeyTrie {
+    pub fn new() -> Self {
+        KeyTrie { nodes: vec![Node::default()] }
+    }
+
+    /// Adds a sequence, or changes the action of an existing one
+    pub fn insert(&mut self, keys: &[UINT], action: Action) -> Result<(), String> {
+        if keys.is_empty() {
+            return Err("empty key sequence".to_string());
+        }
+
+        let mut node = 0;
+
+        for (i, &vk) in keys.iter().enumerate() {
+            if self.nodes[node].action.is_some() {
+                return Err(format!("'{}' is already a sequence", format_sequence(&keys[..i])));
+            }
+
+            node = match self.child(node, vk) {
+                Some(child) => child,
+                None => {
+                    self.nodes.push(Node::default());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.push((vk, child));
+                    child
+                }
+            };
+        }
+
+        if !self.nodes[node].children.is_empty() {
+            return Err(format!("'{}' is the start of longer sequences", format_sequence(keys)));
+        }
+
+        self.nodes[node].action = Some(action);
+        Ok(())
+    }
+
+    pub fn get(&self, keys: &[UINT]) -> Option<Action> {
+        keys.iter()
+            .try_fold(0, |node, &vk| self.child(node, vk))
+            .and_then(|node| self.nodes[node].action)
+    }
+
+    /// Every key used in a sequence
+    pub fn keys(&self) -> Vec<UINT> {
+        let mut keys: Vec<UINT> = self.nodes.iter()
+            .flat_map(|node| node.children.iter().map(|&(vk, _)| vk))
+            .collect();
+
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    fn child(&self, node: usize, vk: UINT) -> Option<usize> {
+        self.nodes[node].children.iter().find(|&&(key, _)| key == vk).map(|&(_, child)| child)
+    }
+}
+
+/// Keys separated by spaces, like `t l` or `g 3`
+pub fn parse_sequence(text: &str) -> Result<Vec<UINT>, String> {
+    text.split_whitespace()
+        .map(|name| hotkeys::key_code(name).ok_or_else(|| format!("unknown key '{}'", name)))
+        .collect()
+}
+
+fn format_sequence(keys: &[UINT]) -> String {
+    keys.iter()
+        .map(|&vk| hotkeys::key_name(vk).unwrap_or_else(|| format!("{:#04x}", vk)).to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The built-in sequences
+///
+/// * `g <digit>` grabs the foreground window into a slot, `f <digit>` focuses
+///   the slot, `b <digit>` focuses it backwards and `c <digit>` clears it
+/// * `d <digit>` shows that virtual desktop
+/// * `x` removes the foreground window from its slots, `k` and `j` move it
+///   towards the front or back of them
+/// * `t` followed by `l`, `r`, `d`/`f`/`g`, `u`/`i`/`j`/`k`, `c` or `m` tiles
+///   the foreground window to a half, third, quarter, the center or maximized
+/// * `space` opens the popup, `v` steps the log level, `p` activates the next profile
+pub fn default_sequences() -> KeyTrie {
+    let mut trie = KeyTrie::new();
+    let mut add = |keys: &[UINT], action| trie.insert(keys, action).unwrap();
+
+    for vk in VK_0..VK_9 + 1 {
+        add(&[VK_G, vk], Action::Grab(vk));
+        add(&[VK_F, vk], Action::Switch(vk));
+        add(&[VK_B, vk], Action::SwitchBack(vk));
+        add(&[VK_C, vk], Action::Clear(vk));
+    }
+
+    for number in 1..10 {
+        let vk = VK_0 + number as UINT;
+        add(&[VK_D, vk], Action::SwitchDesktop(number));
+    }
+
+    add(&[VK_X], Action::Release);
+    add(&[VK_K], Action::ShiftUp);
+    add(&[VK_J], Action::ShiftDown);
+    add(&[0x20], Action::ShowPopup);
+    add(&[VK_V], Action::CycleLogLevel);
+    add(&[VK_P], Action::NextProfile);
+
+    let tiles = [
+        (VK_L, Tile::LeftHalf),
+        (VK_R, Tile::RightHalf),
+        (VK_D, Tile::LeftThird),
+        (VK_F, Tile::CenterThird),
+        (VK_G, Tile::RightThird),
+        (VK_U, Tile::TopLeftQuarter),
+        (VK_I, Tile::TopRightQuarter),
+        (VK_J, Tile::BottomLeftQuarter),
+        (VK_K, Tile::BottomRightQuarter),
+        (VK_C, Tile::Center),
+        (VK_M, Tile::Maximize),
+    ];
+
+    for &(vk, tile) in tiles.iter() {
+        add(&[VK_T, vk], Action::Tile(tile));
+    }
+
+    trie
+}
+
+/// Key sequences and how long to wait for each key, from the `[leader]` section of `winman.ini`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaderOptions {
+    pub sequences: KeyTrie,
+    pub timeout: Duration,
+}
+
+impl Default for LeaderOptions {
+    fn default() -> Self {
+        LeaderOptions {
+            sequences: default_sequences(),
+            timeout: Duration::from_millis(1500),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// The keys so far start a sequence
+    Pending,
+    /// A sequence is complete
+    Done(Action),
+    /// The key does not continue any sequence, was `Esc` or came too late
+    Cancelled,
+}
+
+/// Turns keys pressed after the leader chord into actions
+///
+/// Each key must follow the previous one within the timeout. Plain keys are
+/// only captured while a sequence is active, see `keys`.
+pub struct Leader {
+    options: LeaderOptions,
+    /// Trie node reached so far and when the next key is due
+    state: Option<(usize, Instant)>,
+}
+
+impl Leader {
+    pub fn new(options: LeaderOptions) -> Self {
+        Leader {
+            options: options,
+            state: None,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.options.timeout
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state.is_some()
+    }
+
+    /// Keys to capture while a sequence is active
+    pub fn keys(&self) -> Vec<UINT> {
+        let mut keys = self.options.sequences.keys();
+        keys.push(VK_ESCAPE);
+        keys
+    }
+
+    /// Starts a new sequence, dropping any unfinished one
+    pub fn start(&mut self, now: Instant) {
+        self.state = Some((0, now + self.options.timeout));
+    }
+
+    pub fn cancel(&mut self) {
+        self.state = None;
+    }
+
+    pub fn key(&mut self, vk: UINT, now: Instant) -> Step {
+        let node = match self.state.take() {
+            Some((node, deadline)) if now <= deadline && vk != VK_ESCAPE => node,
+            _ => return Step::Cancelled
+        };
+
+        let trie = &self.options.sequences;
+
+        match trie.child(node, vk) {
+            Some(next) => match trie.nodes[next].action {
+                Some(action) => Step::Done(action),
+                None => {
+                    self.state = Some((next, now + self.options.timeout));
+                    Step::Pending
+                }
+            },
+            None => Step::Cancelled
+        }
+    }
+
+    /// Cancels the sequence if its next key is overdue, returns whether it did
+    pub fn expire(&mut self, now: Instant) -> bool {
+        match self.state {
+            Some((_, deadline)) if now > deadline => {
+                self.state = None;
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use constants::*;
+    use hotkeys::Action;
+    use layout::tiling::Tile;
+    use super::{default_sequences, parse_sequence, KeyTrie, Leader, LeaderOptions, Step, VK_ESCAPE};
+
+    fn seq(text: &str) -> Vec<u32> {
+        parse_sequence(text).unwrap()
+    }
+
+    #[test]
+    fn defaults() {
+        let trie = default_sequences();
+
+        assert_eq!(Some(Action::Grab(VK_3)), trie.get(&seq("g 3")));
+        assert_eq!(Some(Action::Switch(VK_0)), trie.get(&seq("f 0")));
+        assert_eq!(Some(Action::Tile(Tile::LeftHalf)), trie.get(&seq("t l")));
+        assert_eq!(Some(Action::Tile(Tile::BottomLeftQuarter)), trie.get(&seq("t j")));
+        assert_eq!(Some(Action::ShiftDown), trie.get(&seq("j")));
+        assert_eq!(Some(Action::SwitchDesktop(2)), trie.get(&seq("d 2")));
+        assert_eq!(None, trie.get(&seq("t")));
+        assert_eq!(None, trie.get(&seq("t z")));
+        assert!(trie.keys().contains(&0x20));
+    }
+
+    #[test]
+    fn conflicts() {
+        let mut trie = KeyTrie::new();
+        trie.insert(&seq("f b"), Action::Switch(VK_2)).unwrap();
+        trie.insert(&seq("f b"), Action::Switch(VK_3)).unwrap();
+        assert_eq!(Some(Action::Switch(VK_3)), trie.get(&seq("f b")));
+
+        assert_eq!(Err("'f' is the start of longer sequences".to_string()), trie.insert(&seq("f"), Action::Release));
+        assert_eq!(Err("'f b' is already a sequence".to_string()), trie.insert(&seq("f b x"), Action::Release));
+        assert!(trie.insert(&[], Action::Release).is_err());
+
+        assert_eq!(Err("unknown key 'ctrl'".to_string()), parse_sequence("ctrl x"));
+    }
+
+    #[test]
+    fn sequences() {
+        let now = Instant::now();
+        let mut leader = Leader::new(LeaderOptions::default());
+
+        assert_eq!(Step::Cancelled, leader.key(VK_3, now));
+
+        leader.start(now);
+        assert_eq!(Step::Pending, leader.key(VK_G, now));
+        assert_eq!(Step::Done(Action::Grab(VK_3)), leader.key(VK_3, now));
+        assert!(!leader.is_active());
+
+        leader.start(now);
+        assert_eq!(Step::Pending, leader.key(VK_T, now));
+        assert_eq!(Step::Cancelled, leader.key(VK_Z, now));
+        assert!(!leader.is_active());
+
+        leader.start(now);
+        assert_eq!(Step::Cancelled, leader.key(VK_ESCAPE, now));
+    }
+
+    #[test]
+    fn timeout() {
+        let now = Instant::now();
+        let second = Duration::from_secs(1);
+        let mut leader = Leader::new(LeaderOptions { sequences: default_sequences(), timeout: second });
+
+        // Each key restarts the timeout
+        leader.start(now);
+        assert_eq!(Step::Pending, leader.key(VK_T, now + second));
+        assert!(!leader.expire(now + second * 2));
+        assert_eq!(Step::Done(Action::Tile(Tile::Maximize)), leader.key(VK_M, now + second * 2));
+
+        leader.start(now);
+        assert!(leader.expire(now + second * 2));
+        assert!(!leader.is_active());
+
+        leader.start(now);
+        assert_eq!(Step::Cancelled, leader.key(VK_T, now + second * 2));
+    }
+}
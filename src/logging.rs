@@ -0,0 +1,428 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{self, LevelFilter, Log, Metadata, Record};
+use log::kv::{self, Key, Value, VisitSource};
+
+/// Minimum level per module, written like `info, watcher=debug, launcher=trace`
+///
+/// Modules are paths below the crate, the most specific match wins and the
+/// bare level applies to everything else.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFilter {
+    pub level: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    pub fn new(level: LevelFilter) -> Self {
+        LogFilter {
+            level: level,
+            modules: Vec::new(),
+        }
+    }
+
+    pub fn module(mut self, module: &str, level: LevelFilter) -> Self {
+        self.modules.push((qualify(module), level));
+        self
+    }
+
+    /// Level for records logged from `target`
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|&&(ref module, _)| {
+                target == module || (target.starts_with(module.as_str()) && target[module.len()..].starts_with("::"))
+            })
+            .max_by_key(|&&(ref module, _)| module.len())
+            .map_or(self.level, |&(_, level)| level)
+    }
+
+    /// Most verbose level any module logs at
+    pub fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.level, |a, b| if b > a { b } else { a })
+    }
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        LogFilter::new(LevelFilter::Info)
+    }
+}
+
+impl FromStr for LogFilter {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, String> {
+        let mut filter = LogFilter::default();
+
+        for part in spec.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let mut pair = part.splitn(2, '=');
+            let name = pair.next().unwrap().trim();
+
+            match pair.next() {
+                Some(level) => {
+                    let level = try!{ parse_level(level.trim()) };
+                    filter = filter.module(name, level);
+                },
+                None => filter.level = try!{ parse_level(name) },
+            }
+        }
+
+        Ok(filter)
+    }
+}
+
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!{ write!(f, "{}", self.level.to_string().to_lowercase()) };
+
+        for &(ref module, level) in &self.modules {
+            try!{ write!(f, ", {}={}", module, level.to_string().to_lowercase()) };
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level.parse().map_err(|_| format!("unknown log level '{}'", level))
+}
+
+/// Module paths are written relative to the crate
+fn qualify(module: &str) -> String {
+    let crate_name = module_path!().split("::").next().unwrap();
+
+    if module == crate_name || module.starts_with(&format!("{}::", crate_name)) {
+        module.to_string()
+    } else {
+        format!("{}::{}", crate_name, module)
+    }
+}
+
+/// Where and how much to log, from the `[log]` section of `winman.ini`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogOptions {
+    pub filter: LogFilter,
+    /// Size in bytes after which the log file is rotated
+    pub max_size: u64,
+    /// Number of rotated files to keep next to the current one
+    pub keep: usize,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        LogOptions {
+            filter: LogFilter::default(),
+            max_size: 1024 * 1024,
+            keep: 3,
+        }
+    }
+}
+
+/// Append-only file that moves itself to `<name>.1` when it grows too large
+///
+/// Older files shift up to `<name>.<keep>`, anything beyond is deleted.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    keep: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, max_size: u64, keep: usize) -> io::Result<Self> {
+        let file = try!{ OpenOptions::new().create(true).append(true).open(path) };
+        let size = try!{ file.metadata() }.len();
+
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            max_size: max_size,
+            keep: keep,
+            file: file,
+            size: size,
+        })
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+
+        if self.size > 0 && self.size + len > self.max_size {
+            try!{ self.rotate() };
+        }
+
+        try!{ writeln!(self.file, "{}", line) };
+        self.size += len;
+
+        Ok(())
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep == 0 {
+            try!{ self.file.set_len(0) };
+        } else {
+            let _ = fs::remove_file(self.rotated(self.keep));
+
+            for index in (1..self.keep).rev() {
+                let _ = fs::rename(self.rotated(index), self.rotated(index + 1));
+            }
+
+            try!{ fs::rename(&self.path, self.rotated(1)) };
+        }
+
+        self.file = try!{ OpenOptions::new().create(true).append(true).open(&self.path) };
+        self.size = 0;
+
+        Ok(())
+    }
+}
+
+/// Name of the current log file in the directory passed to `init`
+pub const FILE_NAME: &'static str = "winman.log";
+
+struct Logger {
+    filter: RwLock<LogFilter>,
+    file: Mutex<Option<RotatingFile>>,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.read().unwrap().level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format_record(SystemTime::now(), record);
+        eprintln!("{}", line);
+
+        if let Some(ref mut file) = *self.file.lock().unwrap() {
+            let _ = file.write_line(&line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(ref mut file) = *self.file.lock().unwrap() {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+/// Starts logging to stderr and `FILE_NAME` in `dir`
+///
+/// Logging to stderr continues if the file can not be opened. Calling this
+/// again replaces the filter and the file.
+pub fn init(options: &LogOptions, dir: &Path) -> io::Result<()> {
+    let file = fs::create_dir_all(dir)
+        .and_then(|_| RotatingFile::open(&dir.join(FILE_NAME), options.max_size, options.keep));
+
+    let logger = LOGGER.get_or_init(|| Logger {
+        filter: RwLock::new(options.filter.clone()),
+        file: Mutex::new(None),
+    });
+
+    // Only the first call can install the logger, later ones find it in place
+    let _ = log::set_logger(logger);
+    set_filter(options.filter.clone());
+
+    match file {
+        Ok(file) => {
+            *logger.file.lock().unwrap() = Some(file);
+            Ok(())
+        },
+        Err(err) => Err(err)
+    }
+}
+
+pub fn filter() -> LogFilter {
+    LOGGER.get()
+        .map(|logger| logger.filter.read().unwrap().clone())
+        .unwrap_or_default()
+}
+
+pub fn set_filter(filter: LogFilter) {
+    log::set_max_level(filter.max_level());
+
+    if let Some(logger) = LOGGER.get() {
+        *logger.filter.write().unwrap() = filter;
+    }
+}
+
+/// Changes the level of modules without their own level
+pub fn set_level(level: LevelFilter) {
+    let mut filter = filter();
+    filter.level = level;
+    set_filter(filter);
+}
+
+/// Steps the default level through info, debug and trace, for the verbosity hotkey
+pub fn cycle_level() -> LevelFilter {
+    let level = match filter().level {
+        LevelFilter::Info => LevelFilter::Debug,
+        LevelFilter::Debug => LevelFilter::Trace,
+        _ => LevelFilter::Info,
+    };
+
+    set_level(level);
+    level
+}
+
+/// `2026-01-31T12:00:00.000Z INFO  winman::watcher: message key=value`
+fn format_record(time: SystemTime, record: &Record) -> String {
+    let mut line = format!("{} {:<5} {}: {}", format_time(time), record.level(), record.target(), record.args());
+
+    let mut fields = Fields(&mut line);
+    let _ = record.key_values().visit(&mut fields);
+
+    line
+}
+
+struct Fields<'a>(&'a mut String);
+
+impl<'a, 'kvs> VisitSource<'kvs> for Fields<'a> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = value.to_string();
+
+        if value.is_empty() || value.contains(char::is_whitespace) || value.contains('"') {
+            self.0.push_str(&format!(" {}={:?}", key, value));
+        } else {
+            self.0.push_str(&format!(" {}={}", key, value));
+        }
+
+        Ok(())
+    }
+}
+
+/// UTC timestamp in RFC 3339 format with milliseconds
+fn format_time(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let time_of_day = seconds % 86400;
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year, month, day,
+        time_of_day / 3600, time_of_day / 60 % 60, time_of_day % 60,
+        since_epoch.subsec_millis())
+}
+
+/// Date of a day count since 1970-01-01, from http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use log::{Level, LevelFilter, Record};
+    use super::{format_record, format_time, qualify, LogFilter, RotatingFile};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("winman-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parse_filter() {
+        let filter: LogFilter = "warn, watcher=debug, launcher=trace".parse().unwrap();
+
+        assert_eq!(LevelFilter::Warn, filter.level_for(&qualify("tiling")));
+        assert_eq!(LevelFilter::Debug, filter.level_for(&qualify("watcher")));
+        assert_eq!(LevelFilter::Trace, filter.level_for(&qualify("launcher")));
+        assert_eq!(LevelFilter::Trace, filter.max_level());
+        assert_eq!(filter, filter.to_string().parse().unwrap());
+
+        // Already qualified names are kept
+        assert_eq!(filter, format!("warn, {}=debug, launcher=trace", qualify("watcher")).parse().unwrap());
+
+        assert_eq!(LogFilter::default(), "".parse().unwrap());
+        assert!("loud".parse::<LogFilter>().is_err());
+        assert!("watcher=loud".parse::<LogFilter>().is_err());
+    }
+
+    #[test]
+    fn most_specific_module_wins() {
+        let filter = LogFilter::new(LevelFilter::Info)
+            .module("windows", LevelFilter::Error)
+            .module("windows::popup", LevelFilter::Debug);
+
+        assert_eq!(LevelFilter::Error, filter.level_for(&qualify("windows::main")));
+        assert_eq!(LevelFilter::Debug, filter.level_for(&qualify("windows::popup::edit")));
+        assert_eq!(LevelFilter::Info, filter.level_for(&qualify("windows_extra")));
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!("1970-01-01T00:00:00.000Z", format_time(UNIX_EPOCH));
+        assert_eq!("2024-02-29T23:59:59.250Z", format_time(UNIX_EPOCH + Duration::from_millis(1_709_251_199_250)));
+
+        let kvs: [(&str, &str); 2] = [("slot", "3"), ("title", "PROJ-1 - Jira")];
+        let line = format_record(UNIX_EPOCH, &Record::builder()
+            .args(format_args!("Tracking window"))
+            .level(Level::Info)
+            .target("winman::watcher")
+            .key_values(&kvs)
+            .build());
+
+        assert_eq!("1970-01-01T00:00:00.000Z INFO  winman::watcher: Tracking window slot=3 title=\"PROJ-1 - Jira\"", line);
+    }
+
+    #[test]
+    fn rotation() {
+        let dir = temp_dir("rotation");
+        let path = dir.join("test.log");
+        let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+
+        for line in &["first", "second", "third", "fourth"] {
+            file.write_line(line).unwrap();
+        }
+
+        assert_eq!("fourth\n", fs::read_to_string(&path).unwrap());
+        assert_eq!("third\n", fs::read_to_string(dir.join("test.log.1")).unwrap());
+        assert_eq!("second\n", fs::read_to_string(dir.join("test.log.2")).unwrap());
+        assert!(!dir.join("test.log.3").exists());
+
+        // Appends to an existing file
+        let mut file = RotatingFile::open(&path, 100, 2).unwrap();
+        file.write_line("fifth").unwrap();
+        assert_eq!("fourth\nfifth\n", fs::read_to_string(&path).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
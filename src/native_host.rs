@@ -0,0 +1,343 @@
+use std::io::{self, Read, Write};
+
+use winapi::windef::HWND;
+
+use error::Error;
+use json::Json;
+use sources::{self, ResultAction, ResultSource, SearchResult, SourceOptions};
+
+use fuzzy;
+
+/// Largest message a browser may send, Chrome's own limit
+const MAX_MESSAGE_LEN: usize = 64 * 1024 * 1024;
+
+/// A browser tab as reported by the WinMan extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tab {
+    pub id: u64,
+    pub window_id: u64,
+    pub title: String,
+    pub url: String,
+}
+
+/// All tabs of one browser, sent whenever a tab opens, closes or changes
+///
+/// ```json
+/// {"type": "tabs", "browser": "chrome", "tabs": [{"id": 12, "windowId": 3, "title": "Inbox", "url": "https://..."}]}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TabList {
+    pub browser: String,
+    pub tabs: Vec<Tab>,
+}
+
+impl TabList {
+    pub fn from_json(json: &Json) -> Result<Self, String> {
+        if json.get("type").and_then(Json::as_str) != Some("tabs") {
+            return Err("not a tab list".to_string());
+        }
+
+        let items = try!{ json.get("tabs").and_then(Json::as_array).ok_or_else(|| "'tabs' is not an array".to_string()) };
+        let mut tabs = Vec::new();
+
+        for (index, item) in items.iter().enumerate() {
+            let field = |name: &str| item.get(name).ok_or_else(|| format!("tab {} has no '{}'", index, name));
+
+            tabs.push(Tab {
+                id: try!{ try!{ field("id") }.as_u64().ok_or_else(|| format!("tab {} has an invalid id", index)) },
+                window_id: try!{ try!{ field("windowId") }.as_u64().ok_or_else(|| format!("tab {} has an invalid windowId", index)) },
+                title: item.get("title").and_then(Json::as_str).unwrap_or("").to_string(),
+                url: item.get("url").and_then(Json::as_str).unwrap_or("").to_string(),
+            });
+        }
+
+        Ok(TabList {
+            browser: json.get("browser").and_then(Json::as_str).unwrap_or("browser").to_string(),
+            tabs: tabs,
+        })
+    }
+
+    pub fn to_json(&self) -> Json {
+        let tabs = self.tabs.iter()
+            .map(|tab| Json::Object(vec![
+                ("id".to_string(), Json::Number(tab.id as f64)),
+                ("windowId".to_string(), Json::Number(tab.window_id as f64)),
+                ("title".to_string(), Json::String(tab.title.clone())),
+                ("url".to_string(), Json::String(tab.url.clone())),
+            ]))
+            .collect();
+
+        Json::Object(vec![
+            ("type".to_string(), Json::String("tabs".to_string())),
+            ("browser".to_string(), Json::String(self.browser.clone())),
+            ("tabs".to_string(), Json::Array(tabs)),
+        ])
+    }
+}
+
+/// True for the command line a browser starts the native messaging host with
+///
+/// Chrome passes the origin of the extension, Firefox the path of the host
+/// manifest followed by the ID of the extension, and `--native-host` works for
+/// wrappers of either. `args` leaves out the program.
+pub fn is_host_command_line<S: AsRef<str>>(args: &[S]) -> bool {
+    let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+
+    let firefox = args.len() == 2
+        && args[0].to_lowercase().ends_with(".json")
+        && !args[1].is_empty()
+        && !args[1].starts_with('-');
+
+    firefox || args.iter().any(|&arg| arg == "--native-host" || arg.starts_with("chrome-extension://"))
+}
+
+/// Message asking the extension to show a tab and focus its window
+pub fn activate_message(tab: &Tab) -> Json {
+    Json::Object(vec![
+        ("type".to_string(), Json::String("activate".to_string())),
+        ("tabId".to_string(), Json::Number(tab.id as f64)),
+        ("windowId".to_string(), Json::Number(tab.window_id as f64)),
+    ])
+}
+
+/// Reads one native messaging message, `None` once the browser closed the stream
+///
+/// Each message is its length as a 32-bit number in native byte order, followed
+/// by that many bytes of UTF-8 JSON.
+pub fn read_message<R: Read>(reader: &mut R) -> Result<Option<Json>, Error> {
+    let mut header = [0u8; 4];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {},
+        Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(io_error(err)),
+    }
+
+    let len = u32::from_ne_bytes(header) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(Error::Ipc(format!("message of {} bytes is too long", len)));
+    }
+
+    let mut body = vec![0u8; len];
+    try!{ reader.read_exact(&mut body).map_err(io_error) };
+
+    let text = try!{ String::from_utf8(body).map_err(|_| Error::Ipc("message is not UTF-8".to_string())) };
+    text.parse().map(Some).map_err(|err| Error::Ipc(format!("invalid message: {}", err)))
+}
+
+pub fn write_message<W: Write>(writer: &mut W, message: &Json) -> Result<(), Error> {
+    let body = message.to_string();
+
+    try!{ writer.write_all(&(body.len() as u32).to_ne_bytes()).map_err(io_error) };
+    try!{ writer.write_all(body.as_bytes()).map_err(io_error) };
+    writer.flush().map_err(io_error)
+}
+
+fn io_error(err: io::Error) -> Error {
+    Error::Ipc(err.to_string())
+}
+
+/// Passes every tab list the extension sends to `on_tabs` until it disconnects
+///
+/// Other messages are skipped so newer extensions can talk to older hosts.
+pub fn serve<R: Read, F: FnMut(TabList)>(reader: &mut R, mut on_tabs: F) -> Result<(), Error> {
+    while let Some(message) = try!{ read_message(reader) } {
+        match TabList::from_json(&message) {
+            Ok(list) => on_tabs(list),
+            Err(err) => debug!("Skipping message from the extension: {}", err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Browser tabs, searched by title and URL
+///
+/// Each browser is served by its own host process, identified by the window
+/// it receives `activate_message` through.
+pub struct TabSource {
+    hosts: Vec<(HWND, TabList)>,
+}
+
+impl TabSource {
+    pub fn new() -> Self {
+        TabSource { hosts: Vec::new() }
+    }
+
+    /// Replaces the tabs of a host, an empty list forgets it
+    pub fn update(&mut self, host: HWND, list: TabList) {
+        self.hosts.retain(|&(h, _)| h != host);
+
+        if !list.tabs.is_empty() {
+            self.hosts.push((host, list));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.hosts.iter().map(|&(_, ref list)| list.tabs.len()).sum()
+    }
+}
+
+impl ResultSource for TabSource {
+    fn name(&self) -> &'static str {
+        "tabs"
+    }
+
+    fn default_options(&self) -> SourceOptions {
+        // Between windows and programs
+        SourceOptions::new(5, "")
+    }
+
+    fn search(&self, query: &str) -> Vec<SearchResult> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut results = Vec::new();
+        for &(host, ref list) in &self.hosts {
+            for tab in &list.tabs {
+                let score = ::std::cmp::max(
+                    sources::score(fuzzy::fuzzy_match(query, &tab.title)),
+                    sources::score(fuzzy::fuzzy_match(query, &tab.url)).min(1));
+
+                if score > 0 {
+                    results.push(SearchResult {
+                        label: tab.title.clone(),
+                        subtitle: tab.url.clone(),
+                        icon: format!("tab:{}", list.browser),
+                        score: score,
+                        action: ResultAction::ActivateTab(host, tab.clone()),
+                    });
+                }
+            }
+        }
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use backend::fake::hwnd;
+    use json::Json;
+    use sources::{ResultAction, ResultSource};
+    use super::{activate_message, is_host_command_line, read_message, serve, write_message, Tab, TabList, TabSource};
+
+    fn tab(id: u64, title: &str, url: &str) -> Tab {
+        Tab {
+            id: id,
+            window_id: 1,
+            title: title.to_string(),
+            url: url.to_string(),
+        }
+    }
+
+    /// The browser side of the pipes, writing what the extension would send
+    struct FakeExtension {
+        to_host: Vec<u8>,
+    }
+
+    impl FakeExtension {
+        fn new() -> Self {
+            FakeExtension { to_host: Vec::new() }
+        }
+
+        fn send(&mut self, message: &Json) {
+            write_message(&mut self.to_host, message).unwrap();
+        }
+
+        fn send_raw(&mut self, body: &[u8]) {
+            self.to_host.extend_from_slice(&(body.len() as u32).to_ne_bytes());
+            self.to_host.extend_from_slice(body);
+        }
+
+        /// Messages the host wrote for the extension
+        fn received(from_host: Vec<u8>) -> Vec<Json> {
+            let mut reader = Cursor::new(from_host);
+            let mut messages = Vec::new();
+            while let Some(message) = read_message(&mut reader).unwrap() {
+                messages.push(message);
+            }
+            messages
+        }
+    }
+
+    #[test]
+    fn protocol() {
+        let first = TabList { browser: "chrome".to_string(), tabs: vec![tab(12, "Inbox", "https://mail.example.com/")] };
+        let second = TabList { browser: "chrome".to_string(), tabs: vec![] };
+
+        let mut extension = FakeExtension::new();
+        extension.send(&first.to_json());
+        extension.send(&"{\"type\": \"ping\"}".parse().unwrap());
+        extension.send(&second.to_json());
+
+        let mut lists = Vec::new();
+        serve(&mut Cursor::new(extension.to_host), |list| lists.push(list)).unwrap();
+        assert_eq!(vec![first.clone(), second], lists);
+
+        let mut from_host = Vec::new();
+        write_message(&mut from_host, &activate_message(&first.tabs[0])).unwrap();
+
+        let received = FakeExtension::received(from_host);
+        assert_eq!(1, received.len());
+        assert_eq!(Some("activate"), received[0].get("type").and_then(Json::as_str));
+        assert_eq!(Some(12), received[0].get("tabId").and_then(Json::as_u64));
+        assert_eq!(Some(1), received[0].get("windowId").and_then(Json::as_u64));
+    }
+
+    #[test]
+    fn broken_streams() {
+        let mut extension = FakeExtension::new();
+        extension.send_raw(b"{\"type\":");
+        assert!(serve(&mut Cursor::new(extension.to_host), |_| {}).is_err());
+
+        // Cut off in the middle of a message
+        let mut extension = FakeExtension::new();
+        extension.send(&TabList::default().to_json());
+        let len = extension.to_host.len();
+        assert!(serve(&mut Cursor::new(&extension.to_host[..len - 1]), |_| {}).is_err());
+
+        let mut too_long = Cursor::new(vec![0xFF; 8]);
+        assert!(read_message(&mut too_long).is_err());
+
+        let missing_id = "{\"type\": \"tabs\", \"tabs\": [{\"windowId\": 1}]}".parse().unwrap();
+        assert_eq!(Err("tab 0 has no 'id'".to_string()), TabList::from_json(&missing_id));
+    }
+
+    #[test]
+    fn command_lines() {
+        assert!(is_host_command_line(&["--native-host"]));
+        assert!(is_host_command_line(&["chrome-extension://abcdefghijklmnop/", "--parent-window=1234"]));
+        assert!(is_host_command_line(&["C:\\WinMan\\winman.json", "winman@example.com"]));
+
+        assert!(!is_host_command_line::<&str>(&[]));
+        assert!(!is_host_command_line(&["C:\\layouts\\layout.json"]));
+        assert!(!is_host_command_line(&["--run", "restore layout.json"]));
+        assert!(!is_host_command_line(&["layout.json", "--profile"]));
+        assert!(!is_host_command_line(&["--profile", "ops"]));
+    }
+
+    #[test]
+    fn source() {
+        let mut source = TabSource::new();
+        source.update(hwnd(1), TabList { browser: "chrome".to_string(), tabs: vec![tab(1, "Inbox", "https://mail.example.com/")] });
+        source.update(hwnd(2), TabList { browser: "firefox".to_string(), tabs: vec![tab(7, "Pull requests", "https://git.example.com/")] });
+        assert_eq!(2, source.len());
+
+        let results = source.search("inbox");
+        assert_eq!(1, results.len());
+        assert_eq!("tab:chrome", results[0].icon);
+        assert_eq!(ResultAction::ActivateTab(hwnd(1), tab(1, "Inbox", "https://mail.example.com/")), results[0].action);
+
+        // URLs match too, below any title
+        assert_eq!(1, source.search("git.example").len());
+        assert_eq!(1, source.search("git.example")[0].score);
+
+        source.update(hwnd(1), TabList::default());
+        assert!(source.search("inbox").is_empty());
+        assert_eq!(1, source.len());
+    }
+}
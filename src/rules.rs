@@ -0,0 +1,384 @@
+use std::fmt;
+use std::str::FromStr;
+
+use winapi::minwindef::UINT;
+
+use backend::WindowIdentity;
+
+/// Puts matching windows into a slot when they appear
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub slot: UINT,
+    pub condition: Condition,
+}
+
+/// Slot of the first rule matching `identity`
+pub fn slot_for(rules: &[Rule], identity: &WindowIdentity) -> Option<UINT> {
+    rules.iter()
+        .find(|rule| rule.condition.matches(identity))
+        .map(|rule| rule.slot)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Field {
+    Exe,
+    Class,
+    Title,
+}
+
+/// Comparisons ignore ASCII case
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Operator {
+    /// `=`
+    Equals,
+    /// `!=`
+    NotEquals,
+    /// `~`, the value occurs somewhere in the field
+    Contains,
+    /// `!~`
+    NotContains,
+}
+
+/// Boolean expression over a window's identity
+///
+/// ```text
+/// expr       = and_expr { "or" and_expr }
+/// and_expr   = unary { "and" unary }
+/// unary      = "not" unary | "(" expr ")" | comparison
+/// comparison = ("exe" | "class" | "title") ("=" | "!=" | "~" | "!~") value
+/// value      = word | 'single quoted' | "double quoted"
+/// ```
+///
+/// For example `class = Chrome_WidgetWin_1 and title ~ 'Jira'`. Keywords and
+/// field names are case insensitive.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Compare(Field, Operator, String),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    /// 1-based column of the offending text
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Condition, SyntaxError> {
+        let tokens = try!{ tokenize(text) };
+        let mut parser = Parser { tokens: &tokens, position: 0, end: text.len() + 1 };
+
+        let condition = try!{ parser.expr() };
+        match parser.peek() {
+            None => Ok(condition),
+            Some(_) => Err(parser.error("expected 'and', 'or' or the end of the rule")),
+        }
+    }
+
+    /// Whether the window fits the condition, no comparison of a title that is
+    /// not known matches
+    pub fn matches(&self, identity: &WindowIdentity) -> bool {
+        match *self {
+            Condition::Compare(field, operator, ref value) => {
+                let actual = match field {
+                    Field::Exe => &identity.exe,
+                    Field::Class => &identity.class,
+                    Field::Title => match identity.title.text() {
+                        Some(title) => title,
+                        None => return false,
+                    },
+                };
+
+                match operator {
+                    Operator::Equals => actual.eq_ignore_ascii_case(value),
+                    Operator::NotEquals => !actual.eq_ignore_ascii_case(value),
+                    Operator::Contains => contains(actual, value),
+                    Operator::NotContains => !contains(actual, value),
+                }
+            },
+            Condition::And(ref a, ref b) => a.matches(identity) && b.matches(identity),
+            Condition::Or(ref a, ref b) => a.matches(identity) || b.matches(identity),
+            Condition::Not(ref a) => !a.matches(identity),
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = SyntaxError;
+
+    fn from_str(text: &str) -> Result<Condition, SyntaxError> {
+        Condition::parse(text)
+    }
+}
+
+fn contains(haystack: &str, needle: &str) -> bool {
+    haystack.to_ascii_lowercase().contains(&needle.to_ascii_lowercase())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Operator(Operator),
+    Open,
+    Close,
+}
+
+/// Tokens with the 1-based column they start at
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, SyntaxError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        let column = index + 1;
+
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '=' => Token::Operator(Operator::Equals),
+            '~' => Token::Operator(Operator::Contains),
+            '!' => match chars.next() {
+                Some((_, '=')) => Token::Operator(Operator::NotEquals),
+                Some((_, '~')) => Token::Operator(Operator::NotContains),
+                _ => return Err(SyntaxError { column, message: "expected '!=' or '!~'".to_string() }),
+            },
+            '\'' | '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => break,
+                        Some((_, other)) => value.push(other),
+                        None => return Err(SyntaxError { column, message: "unterminated string".to_string() }),
+                    }
+                }
+                Token::Quoted(value)
+            },
+            c => {
+                let mut word = c.to_string();
+                while let Some(&(_, next)) = chars.peek() {
+                    if next.is_whitespace() || "()=!~'\"".contains(next) {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                Token::Word(word)
+            }
+        };
+
+        tokens.push((column, token));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [(usize, Token)],
+    position: usize,
+    /// Column just past the end of the text
+    end: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position).map(|&(_, ref token)| token)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(&Token::Word(ref word)) => word.eq_ignore_ascii_case(keyword),
+            _ => false
+        }
+    }
+
+    /// Error at the current token
+    fn error(&self, message: &str) -> SyntaxError {
+        SyntaxError {
+            column: self.tokens.get(self.position).map_or(self.end, |&(column, _)| column),
+            message: message.to_string(),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Condition, SyntaxError> {
+        let mut condition = try!{ self.and_expr() };
+
+        while self.is_keyword("or") {
+            self.position += 1;
+            condition = Condition::Or(Box::new(condition), Box::new(try!{ self.and_expr() }));
+        }
+
+        Ok(condition)
+    }
+
+    fn and_expr(&mut self) -> Result<Condition, SyntaxError> {
+        let mut condition = try!{ self.unary() };
+
+        while self.is_keyword("and") {
+            self.position += 1;
+            condition = Condition::And(Box::new(condition), Box::new(try!{ self.unary() }));
+        }
+
+        Ok(condition)
+    }
+
+    fn unary(&mut self) -> Result<Condition, SyntaxError> {
+        if self.is_keyword("not") {
+            self.position += 1;
+            return Ok(Condition::Not(Box::new(try!{ self.unary() })));
+        }
+
+        if self.peek() == Some(&Token::Open) {
+            self.position += 1;
+            let condition = try!{ self.expr() };
+
+            if self.peek() != Some(&Token::Close) {
+                return Err(self.error("expected ')'"));
+            }
+
+            self.position += 1;
+            return Ok(condition);
+        }
+
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Condition, SyntaxError> {
+        let field = match self.peek() {
+            Some(&Token::Word(ref word)) => match word.to_ascii_lowercase().as_str() {
+                "exe" => Field::Exe,
+                "class" => Field::Class,
+                "title" => Field::Title,
+                _ => return Err(self.error(&format!("unknown field '{}', expected exe, class or title", word))),
+            },
+            _ => return Err(self.error("expected exe, class or title")),
+        };
+        self.position += 1;
+
+        let operator = match self.peek() {
+            Some(&Token::Operator(operator)) => operator,
+            _ => return Err(self.error("expected '=', '!=', '~' or '!~'")),
+        };
+        self.position += 1;
+
+        match self.peek() {
+            Some(&Token::Word(ref value)) | Some(&Token::Quoted(ref value)) => {
+                self.position += 1;
+                Ok(Condition::Compare(field, operator, value.clone()))
+            },
+            _ => Err(self.error("expected a value")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::WindowIdentity;
+    use titles::WindowTitle;
+    use super::{slot_for, Condition, Field, Operator, Rule};
+
+    fn identity(exe: &str, class: &str, title: &str) -> WindowIdentity {
+        WindowIdentity {
+            exe: exe.to_string(),
+            class: class.to_string(),
+            title: WindowTitle::new(title.to_string()),
+        }
+    }
+
+    fn matches(rule: &str, identity: &WindowIdentity) -> bool {
+        Condition::parse(rule).unwrap().matches(identity)
+    }
+
+    fn error(rule: &str) -> (usize, String) {
+        let err = Condition::parse(rule).unwrap_err();
+        (err.column, err.message)
+    }
+
+    #[test]
+    fn parse_comparison() {
+        assert_eq!(
+            Condition::Compare(Field::Exe, Operator::Equals, "WindowsTerminal.exe".to_string()),
+            Condition::parse("exe = WindowsTerminal.exe").unwrap());
+
+        assert_eq!(
+            Condition::Compare(Field::Title, Operator::NotContains, "Jira Board".to_string()),
+            Condition::parse("TITLE!~\"Jira Board\"").unwrap());
+    }
+
+    #[test]
+    fn precedence() {
+        let a = || Box::new(Condition::Compare(Field::Exe, Operator::Equals, "a".to_string()));
+        let b = || Box::new(Condition::Compare(Field::Class, Operator::Equals, "b".to_string()));
+        let c = || Box::new(Condition::Compare(Field::Title, Operator::Equals, "c".to_string()));
+
+        assert_eq!(
+            Condition::Or(a(), Box::new(Condition::And(b(), c()))),
+            Condition::parse("exe = a or class = b and title = c").unwrap());
+
+        assert_eq!(
+            Condition::And(Box::new(Condition::Or(a(), b())), c()),
+            Condition::parse("(exe = a or class = b) and title = c").unwrap());
+
+        assert_eq!(
+            Condition::And(Box::new(Condition::Not(a())), b()),
+            Condition::parse("not exe = a and class = b").unwrap());
+    }
+
+    #[test]
+    fn evaluate() {
+        let jira = identity("chrome.exe", "Chrome_WidgetWin_1", "PROJ-12 - Jira - Google Chrome");
+        let mail = identity("chrome.exe", "Chrome_WidgetWin_1", "Inbox - Gmail - Google Chrome");
+        let terminal = identity("WindowsTerminal.exe", "CASCADIA_HOSTING_WINDOW_CLASS", "PowerShell");
+
+        let rule = "class = Chrome_WidgetWin_1 and title ~ 'Jira'";
+        assert!(matches(rule, &jira));
+        assert!(!matches(rule, &mail));
+        assert!(!matches(rule, &terminal));
+
+        assert!(matches("exe = windowsterminal.EXE", &terminal));
+        assert!(matches("exe != chrome.exe", &terminal));
+        assert!(matches("title !~ jira", &mail));
+        assert!(matches("not (title ~ jira or title ~ gmail)", &terminal));
+        assert!(!matches("not (title ~ jira or title ~ gmail)", &mail));
+
+        let hung = WindowIdentity { title: WindowTitle::Unavailable, ..terminal };
+        assert!(!matches("title ~ PowerShell", &hung));
+        assert!(!matches("title !~ jira", &hung));
+        assert!(matches("exe = windowsterminal.exe", &hung));
+    }
+
+    #[test]
+    fn first_matching_rule() {
+        let rules = vec![
+            Rule { slot: 3, condition: Condition::parse("title ~ Jira").unwrap() },
+            Rule { slot: 2, condition: Condition::parse("exe = chrome.exe").unwrap() },
+        ];
+
+        assert_eq!(Some(3), slot_for(&rules, &identity("chrome.exe", "", "Jira")));
+        assert_eq!(Some(2), slot_for(&rules, &identity("chrome.exe", "", "Gmail")));
+        assert_eq!(None, slot_for(&rules, &identity("code.exe", "", "Gmail")));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(1, error("").0);
+        assert_eq!(1, error("name = a").0);
+        assert_eq!(7, error("title a").0);
+        assert_eq!(8, error("title =").0);
+        assert_eq!(9, error("title = 'Jira").0);
+        assert_eq!(7, error("title ! a").0);
+        assert_eq!(9, error("exe = a b").0);
+        assert_eq!(17, error("(exe = a or exe b").0);
+        assert_eq!(9, error("(exe = a").0);
+        assert_eq!(1, error(") exe = a").0);
+    }
+}
@@ -0,0 +1,254 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+use winapi::windef::HWND;
+
+use apps::AppIndex;
+use backend::{WindowBackend, WindowEvent};
+use commands::CommandSource;
+use native_host::{TabList, TabSource};
+use settings::Settings;
+use sources::{self, ResultSource, SearchResult, WindowSource};
+
+/// Work for the search thread, see `SearchWorker`
+pub enum Request {
+    /// Rebuild the window index if it is stale, or always if true, and scan the
+    /// app directories again if they are stale
+    Refresh(bool),
+    /// Keeps the window index current
+    Event(WindowEvent),
+    /// Tabs reported by a native messaging host, see `TabSource::update`
+    Tabs(HWND, TabList),
+    /// Scan these directories for programs
+    AppDirs(Vec<PathBuf>),
+    /// Weights and prefixes of the sources changed
+    Settings(Settings),
+}
+
+/// Results of a search, best first
+pub struct SearchResults {
+    /// Returned by `SearchWorker::search`
+    pub id: usize,
+    pub query: String,
+    pub results: Vec<SearchResult>,
+}
+
+// winapi declares `HWND` as a raw pointer, which is not `Send`, but a handle is
+// only an identifier that is valid on every thread. Results hold the handles of
+// windows and native messaging hosts in their `ResultAction`, which are only
+// compared and passed to the API, everything else in them is owned data.
+unsafe impl Send for SearchResults {}
+
+enum Job {
+    Request(Request),
+    Search(usize, String),
+}
+
+// Requests hold window handles in window events and as the native messaging
+// host of tabs, see `SearchResults`. The worker never dereferences them.
+unsafe impl Send for Job {}
+
+/// Searches the popup sources on a thread of its own
+///
+/// The thread owns the window index, tabs and programs, so enumerating windows,
+/// reading titles and ranking never block the message loop. Each search gets an
+/// ID and starting one cancels the ones before, whose results are dropped.
+/// `notify` is called whenever results are ready to be taken with `try_recv`.
+pub struct SearchWorker {
+    tx: mpsc::Sender<Job>,
+    rx: mpsc::Receiver<SearchResults>,
+    /// ID of the newest search, the worker drops any other
+    latest: Arc<AtomicUsize>,
+}
+
+impl SearchWorker {
+    /// Starts the thread, which builds its window index with the backend from `make_backend`
+    pub fn spawn<B, F, N>(make_backend: F, notify: N) -> Self
+        where B: WindowBackend, F: FnOnce() -> B + Send + 'static, N: Fn() + Send + 'static {
+
+        let (tx, jobs) = mpsc::channel();
+        let (results, rx) = mpsc::channel();
+        let latest = Arc::new(AtomicUsize::new(0));
+        let worker_latest = latest.clone();
+
+        thread::spawn(move || run(make_backend(), jobs, results, worker_latest, notify));
+
+        SearchWorker {
+            tx: tx,
+            rx: rx,
+            latest: latest,
+        }
+    }
+
+    pub fn send(&self, request: Request) {
+        let _ = self.tx.send(Job::Request(request));
+    }
+
+    /// Starts searching for `query`, returns the ID its results will have
+    pub fn search(&self, query: &str) -> usize {
+        let id = self.latest.fetch_add(1, Ordering::SeqCst) + 1;
+        let _ = self.tx.send(Job::Search(id, query.to_string()));
+        id
+    }
+
+    /// Stops the search in progress, if any
+    pub fn cancel(&self) {
+        self.latest.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Results of the newest search once they are ready
+    pub fn try_recv(&self) -> Option<SearchResults> {
+        while let Ok(results) = self.rx.try_recv() {
+            if results.id == self.latest.load(Ordering::SeqCst) {
+                return Some(results);
+            }
+        }
+
+        None
+    }
+}
+
+fn run<B, N>(backend: B, jobs: mpsc::Receiver<Job>, results: mpsc::Sender<SearchResults>,
+             latest: Arc<AtomicUsize>, notify: N)
+    where B: WindowBackend, N: Fn() {
+
+    let mut windows = WindowSource::new();
+    let mut tabs = TabSource::new();
+    let mut apps = AppIndex::new(Vec::new());
+    let mut settings = Settings::default();
+
+    windows.refresh(&backend, Instant::now());
+
+    // Ends once the `SearchWorker` is dropped
+    while let Ok(job) = jobs.recv() {
+        match job {
+            Job::Request(Request::Refresh(force)) => {
+                if force {
+                    windows.invalidate();
+                }
+
+                if windows.refresh_if_stale(&backend, Instant::now()) {
+                    debug!("Grabbed {} window titles", windows.len());
+                }
+
+                if apps.refresh_if_stale(Instant::now()) {
+                    debug!("Found {} programs", apps.apps().len());
+                }
+            },
+
+            Job::Request(Request::Event(event)) => windows.handle(&backend, event),
+
+            Job::Request(Request::Tabs(host, list)) => tabs.update(host, list),
+
+            Job::Request(Request::AppDirs(dirs)) => {
+                apps.set_dirs(dirs);
+                debug!("Found {} programs", apps.apps().len());
+            },
+
+            Job::Request(Request::Settings(new_settings)) => settings = new_settings,
+
+            Job::Search(id, query) => {
+                let cancelled = || latest.load(Ordering::SeqCst) != id;
+                if cancelled() {
+                    continue;
+                }
+
+                let sources: Vec<(&dyn ResultSource, _)> = vec![
+                    (&windows, settings.source_options(&windows)),
+                    (&tabs, settings.source_options(&tabs)),
+                    (&apps, settings.source_options(&apps)),
+                    (&CommandSource, settings.source_options(&CommandSource)),
+                ];
+
+                if let Some(found) = sources::search_until(&sources, &query, &cancelled) {
+                    let _ = results.send(SearchResults { id: id, query: query, results: found });
+                    notify();
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use backend::WindowEvent;
+    use backend::fake::{self, FakeBackend};
+    use layout::Rect;
+    use native_host::{Tab, TabList};
+    use sources::ResultAction;
+    use super::{Request, SearchResults, SearchWorker};
+
+    fn spawn() -> (SearchWorker, mpsc::Receiver<()>) {
+        let (notify, notified) = mpsc::channel();
+
+        let worker = SearchWorker::spawn(|| {
+            let mut backend = FakeBackend::new(vec![]);
+            let bounds = Rect::from_edges(0, 0, 800, 600);
+            backend.add_window(1, "Inbox - Mail", bounds);
+            backend.add_window(2, "Release notes", bounds);
+            backend
+        }, move || { let _ = notify.send(()); });
+
+        (worker, notified)
+    }
+
+    /// Waits for the results of the newest search
+    fn wait(worker: &SearchWorker, notified: &mpsc::Receiver<()>) -> SearchResults {
+        loop {
+            notified.recv_timeout(Duration::from_secs(5)).expect("no results");
+
+            if let Some(results) = worker.try_recv() {
+                return results;
+            }
+        }
+    }
+
+    fn labels(results: &SearchResults) -> Vec<&str> {
+        results.results.iter().map(|result| result.label.as_str()).collect()
+    }
+
+    #[test]
+    fn searches() {
+        let (worker, notified) = spawn();
+
+        let id = worker.search("inbox");
+        let results = wait(&worker, &notified);
+        assert_eq!(id, results.id);
+        assert_eq!("inbox", results.query);
+        assert_eq!(vec!["Inbox - Mail"], labels(&results));
+        assert_eq!(ResultAction::Focus(fake::hwnd(1)), results.results[0].action);
+
+        let tab = Tab { id: 4, window_id: 1, title: "Release dashboard".to_string(), url: String::new() };
+        worker.send(Request::Tabs(fake::hwnd(9), TabList { browser: "chrome".to_string(), tabs: vec![tab] }));
+        worker.send(Request::Event(WindowEvent::Destroyed(fake::hwnd(2))));
+
+        worker.search("release");
+        assert_eq!(vec!["Release dashboard"], labels(&wait(&worker, &notified)));
+    }
+
+    #[test]
+    fn newer_searches_cancel_older_ones() {
+        let (worker, notified) = spawn();
+
+        let first = worker.search("inbox");
+        let second = worker.search("notes");
+        assert!(second > first);
+
+        let results = wait(&worker, &notified);
+        assert_eq!(second, results.id);
+        assert_eq!(vec!["Release notes"], labels(&results));
+
+        // Results of a cancelled search are never handed out
+        worker.search("inbox");
+        worker.cancel();
+        let _ = notified.recv_timeout(Duration::from_millis(200));
+        assert!(worker.try_recv().is_none());
+    }
+}
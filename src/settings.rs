@@ -0,0 +1,763 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use winapi::minwindef::UINT;
+
+use apps::AppOptions;
+use backend::WindowIdentity;
+use constants::VK_0;
+use hotkeys::{self, Action, Binding, Chord};
+use ini::{Ini, ParseError, Section};
+use launcher::LaunchCommand;
+use leader::{self, LeaderOptions};
+use logging::LogOptions;
+use layout::monitor::Placement;
+use layout::tiling::TilingOptions;
+use rules::{self, Condition, Rule};
+use sources::{ResultSource, SourceOptions};
+use theme::Theme;
+use window_tracking::{CyclePolicy, SlotScope};
+
+/// User settings from `winman.ini`
+///
+/// ```ini
+/// [tiling]
+/// center_width = 1280
+/// center_height = 800
+///
+/// [theme]
+/// background = #222222
+/// text = #a3ffa3
+/// edit_background = #323232
+///
+/// [popup]
+/// placement = cursor
+///
+/// [log]
+/// filter = info, watcher=debug
+/// max_size = 1024
+/// keep = 3
+///
+/// [hotkeys]
+/// switch 3 = Alt+3, Ctrl+Win+3
+/// popup = Ctrl+Alt+Space
+///
+/// [desktops]
+/// scope = desktop
+///
+/// [apps]
+/// start_menu = true
+/// dir = C:\Tools
+/// dir = D:\Portable
+///
+/// [source apps]
+/// weight = 2
+/// prefix = !
+///
+/// [leader]
+/// chord = Win+W
+/// timeout_ms = 1500
+/// f b = switch 2
+///
+/// [slot 1]
+/// launch = C:\Program Files\Microsoft VS Code\Code.exe
+/// args = --new-window
+/// working_dir = C:\src
+/// timeout = 10
+/// cycle = most_recent
+/// scope = global
+/// rule = exe = Code.exe
+/// rule = class = Chrome_WidgetWin_1 and title ~ 'Visual Studio'
+///
+/// [slot 1 @ meetings]
+/// launch = C:\Program Files\Teams\ms-teams.exe
+/// rule = exe = ms-teams.exe
+/// ```
+///
+/// Each `rule` puts matching windows into the slot when they appear, see
+/// `rules::Condition` for the syntax. `cycle` is one of `most_recent`, `cycle` or
+/// `if_focused`, see `CyclePolicy`. `scope` is `global` or `desktop`, see
+/// `SlotScope`, and `[desktops]` sets it for all slots without one.
+///
+/// A slot section named `@ <profile>` belongs to that profile and replaces the
+/// slot's plain section, including its rules, while the profile is active.
+/// Slots without a section in the profile keep their plain one, and the
+/// `default` profile only has plain sections.
+///
+/// The log `filter` sets a level per module, see
+/// `logging::LogFilter`, and `max_size` is in kilobytes. Each entry in `[hotkeys]`
+/// names an action, see `hotkeys::Action`, and the chord to register for it in
+/// place of its default, optionally followed by a chord to register when the
+/// first one is taken by another program. `[leader]` replaces the chord that
+/// starts a key sequence, and every other entry adds a sequence, see
+/// `leader::default_sequences`. The popup offers the programs in the Start Menu
+/// unless `start_menu` is false, and in each `dir`. A `[source <name>]` section
+/// changes how the popup ranks one of its sources, see `sources::SourceOptions`.
+/// The popup opens on the monitor of the foreground window, or with `placement`
+/// on the one under the `cursor` or the `primary` one.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub tiling: TilingOptions,
+    pub theme: Theme,
+    pub log: LogOptions,
+    pub leader: LeaderOptions,
+    pub apps: AppOptions,
+    /// Monitor the popup opens on
+    pub popup_placement: Placement,
+    leader_chord: Option<Chord>,
+    /// Chord and fallback of the actions in `[hotkeys]`
+    hotkey_chords: Vec<(Action, Chord, Option<Chord>)>,
+    /// Weight and prefix of popup sources by name, where set
+    sources: HashMap<String, (Option<u32>, Option<String>)>,
+    default_scope: SlotScope,
+    slots: SlotSettings,
+    profiles: Vec<(String, SlotSettings)>,
+    /// Index into `profiles`, `None` for the default profile
+    profile: Option<usize>,
+}
+
+/// Name of the profile made of the slot sections without a profile
+pub const DEFAULT_PROFILE: &'static str = "default";
+
+/// Slot sections outside of profiles, or of one profile
+#[derive(Debug, Clone, Default)]
+struct SlotSettings {
+    /// Slots with a section
+    slots: Vec<UINT>,
+    launch: HashMap<UINT, LaunchCommand>,
+    rules: Vec<Rule>,
+    cycle: HashMap<UINT, CyclePolicy>,
+    scopes: HashMap<UINT, SlotScope>,
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Settings::default()
+    }
+
+    /// Names of all profiles, starting with `DEFAULT_PROFILE`
+    pub fn profiles(&self) -> Vec<&str> {
+        let mut names = vec![DEFAULT_PROFILE];
+        names.extend(self.profiles.iter().map(|&(ref name, _)| name.as_str()));
+        names
+    }
+
+    /// Name of the active profile
+    pub fn profile(&self) -> &str {
+        match self.profile {
+            Some(index) => &self.profiles[index].0,
+            None => DEFAULT_PROFILE
+        }
+    }
+
+    /// Activates a profile, returns false if there is none named `name`
+    pub fn set_profile(&mut self, name: &str) -> bool {
+        if name == DEFAULT_PROFILE {
+            self.profile = None;
+            return true;
+        }
+
+        match self.profiles.iter().position(|&(ref profile, _)| profile == name) {
+            Some(index) => {
+                self.profile = Some(index);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Program to start when focusing the empty slot on hotkey `vk`
+    pub fn launch_command(&self, vk: UINT) -> Option<&LaunchCommand> {
+        self.slot_settings(vk).launch.get(&vk)
+    }
+
+    pub fn cycle_policy(&self, vk: UINT) -> CyclePolicy {
+        self.slot_settings(vk).cycle.get(&vk).cloned().unwrap_or_default()
+    }
+
+    pub fn slot_scope(&self, vk: UINT) -> SlotScope {
+        self.slot_settings(vk).scopes.get(&vk).cloned().unwrap_or(self.default_scope)
+    }
+
+    /// Rules outside of profiles
+    pub fn rules(&self) -> &[Rule] {
+        &self.slots.rules
+    }
+
+    /// Slot the first matching rule assigns a new window to
+    ///
+    /// Rules of the active profile come first, followed by the rules outside of
+    /// profiles for slots the profile does not replace.
+    pub fn rule_slot(&self, identity: &WindowIdentity) -> Option<UINT> {
+        let profile = self.profile.map(|index| &self.profiles[index].1);
+
+        profile
+            .and_then(|profile| rules::slot_for(&profile.rules, identity))
+            .or_else(|| self.slots.rules.iter()
+                .filter(|rule| profile.map_or(true, |profile| !profile.slots.contains(&rule.slot)))
+                .find(|rule| rule.condition.matches(identity))
+                .map(|rule| rule.slot))
+    }
+
+    /// Settings of slot `vk` in the active profile
+    fn slot_settings(&self, vk: UINT) -> &SlotSettings {
+        match self.profile.map(|index| &self.profiles[index].1) {
+            Some(profile) if profile.slots.contains(&vk) => profile,
+            _ => &self.slots
+        }
+    }
+
+    /// Default hotkeys with the chords and fallbacks configured for them
+    pub fn hotkeys(&self) -> Vec<Binding> {
+        let mut bindings = hotkeys::default_bindings();
+
+        for binding in &mut bindings {
+            if let Some(&(_, chord, fallback)) = self.hotkey_chords.iter().find(|&&(action, _, _)| action == binding.action) {
+                binding.chord = chord;
+                binding.fallback = fallback;
+            }
+
+            if let (Action::Leader, Some(chord)) = (binding.action, self.leader_chord) {
+                binding.chord = chord;
+            }
+        }
+
+        bindings
+    }
+
+    /// Options of a popup source, its defaults changed by `[source <name>]`
+    pub fn source_options(&self, source: &dyn ResultSource) -> SourceOptions {
+        let mut options = source.default_options();
+
+        if let Some(&(weight, ref prefix)) = self.sources.get(source.name()) {
+            options.weight = weight.unwrap_or(options.weight);
+            options.prefix = prefix.clone().unwrap_or(options.prefix);
+        }
+
+        options
+    }
+
+    pub fn from_ini(ini: &Ini) -> Result<Self, ParseError> {
+        let mut settings = Settings::new();
+
+        for section in ini.sections() {
+            match section.name.as_str() {
+                "theme" => {
+                    try!{ check_keys(section, &["background", "text", "edit_background"]) };
+
+                    let theme = &mut settings.theme;
+                    theme.background = try!{ parse_value(section, "background") }.unwrap_or(theme.background);
+                    theme.text = try!{ parse_value(section, "text") }.unwrap_or(theme.text);
+                    theme.edit_background = try!{ parse_value(section, "edit_background") }.unwrap_or(theme.edit_background);
+                },
+
+                "popup" => {
+                    try!{ check_keys(section, &["placement"]) };
+
+                    settings.popup_placement = try!{ parse_value(section, "placement") }.unwrap_or_default();
+                },
+
+                "log" => {
+                    try!{ check_keys(section, &["filter", "max_size", "keep"]) };
+
+                    if let Some(filter) = try!{ parse_value(section, "filter") } {
+                        settings.log.filter = filter;
+                    }
+                    if let Some(kilobytes) = try!{ parse_value::<u64>(section, "max_size") } {
+                        settings.log.max_size = try!{ kilobytes.checked_mul(1024)
+                            .ok_or_else(|| error(section, section.line_of("max_size"), &format!("invalid value for 'max_size': {}", kilobytes))) };
+                    }
+                    if let Some(keep) = try!{ parse_value(section, "keep") } {
+                        settings.log.keep = keep;
+                    }
+                },
+
+                "hotkeys" => {
+                    for (key, value) in section.entries() {
+                        let line = section.line_of(key);
+                        let action = try!{ key.parse::<Action>()
+                            .map_err(|_| error(section, line, &format!("unknown action '{}'", key))) };
+                        let mut chords = Vec::new();
+
+                        for chord in value.split(',') {
+                            chords.push(try!{ chord.parse::<Chord>()
+                                .map_err(|err| error(section, line, &format!("invalid chord for '{}': {}", key, err))) });
+                        }
+
+                        if chords.len() > 2 {
+                            return Err(error(section, line, &format!("'{}' takes a chord and a fallback, not {} chords", key, chords.len())));
+                        }
+
+                        settings.hotkey_chords.push((action, chords[0], chords.get(1).cloned()));
+                    }
+                },
+
+                "leader" => {
+                    settings.leader_chord = try!{ parse_value(section, "chord") };
+
+                    if let Some(ms) = try!{ parse_value(section, "timeout_ms") } {
+                        settings.leader.timeout = Duration::from_millis(ms);
+                    }
+
+                    for (key, value) in section.entries() {
+                        if key == "chord" || key == "timeout_ms" {
+                            continue;
+                        }
+
+                        let line = section.line_of(key);
+                        let keys = try!{ leader::parse_sequence(key)
+                            .map_err(|err| error(section, line, &format!("invalid sequence '{}': {}", key, err))) };
+                        let action = try!{ value.parse::<Action>()
+                            .map_err(|_| error(section, line, &format!("unknown action '{}'", value))) };
+
+                        try!{ settings.leader.sequences.insert(&keys, action)
+                            .map_err(|err| error(section, line, &format!("invalid sequence '{}': {}", key, err))) };
+                    }
+                },
+
+                "apps" => {
+                    try!{ check_keys(section, &["start_menu", "dir"]) };
+
+                    settings.apps.start_menu = try!{ parse_value(section, "start_menu") }.unwrap_or(true);
+                    settings.apps.dirs = section.get_all("dir").iter().map(|value| PathBuf::from(value.text)).collect();
+                },
+
+                name if name.starts_with("source ") => {
+                    try!{ check_keys(section, &["weight", "prefix"]) };
+
+                    let weight = try!{ parse_value(section, "weight") };
+                    let prefix = section.get("prefix").map(String::from);
+
+                    settings.sources.insert(name["source ".len()..].trim().to_string(), (weight, prefix));
+                },
+
+                "desktops" => {
+                    try!{ check_keys(section, &["scope"]) };
+
+                    settings.default_scope = try!{ parse_value(section, "scope") }.unwrap_or_default();
+                },
+
+                "tiling" => {
+                    try!{ check_keys(section, &["center_width", "center_height"]) };
+
+                    let (width, height) = settings.tiling.center_size;
+                    settings.tiling.center_size = (
+                        try!{ parse_value(section, "center_width") }.unwrap_or(width),
+                        try!{ parse_value(section, "center_height") }.unwrap_or(height),
+                    );
+                },
+
+                name if name.starts_with("slot ") => {
+                    let (slot, profile) = match name.find('@') {
+                        Some(at) => (&name["slot ".len()..at], Some(name[at + 1..].trim())),
+                        None => (&name["slot ".len()..], None)
+                    };
+                    let vk = try!{ parse_slot(section, slot) };
+                    try!{ check_keys(section, &["launch", "args", "working_dir", "timeout", "cycle", "scope", "rule"]) };
+
+                    let slots = match profile {
+                        None => &mut settings.slots,
+                        Some(profile) if profile.is_empty() || profile == DEFAULT_PROFILE => {
+                            return Err(error(section, section.line, &format!("invalid profile name '{}'", profile)));
+                        },
+                        Some(profile) => {
+                            let index = match settings.profiles.iter().position(|&(ref name, _)| name == profile) {
+                                Some(index) => index,
+                                None => {
+                                    settings.profiles.push((profile.to_string(), SlotSettings::default()));
+                                    settings.profiles.len() - 1
+                                }
+                            };
+
+                            &mut settings.profiles[index].1
+                        }
+                    };
+
+                    slots.slots.push(vk);
+
+                    if let Some(command) = try!{ parse_launch_command(section) } {
+                        slots.launch.insert(vk, command);
+                    }
+
+                    if let Some(policy) = try!{ parse_value(section, "cycle") } {
+                        slots.cycle.insert(vk, policy);
+                    }
+
+                    if let Some(scope) = try!{ parse_value(section, "scope") } {
+                        slots.scopes.insert(vk, scope);
+                    }
+
+                    for value in section.get_all("rule") {
+                        let condition = try!{ Condition::parse(value.text).map_err(|err| ParseError {
+                            line: value.line,
+                            column: value.column + err.column - 1,
+                            message: format!("[{}] invalid rule: {}", section.name, err.message),
+                        }) };
+
+                        slots.rules.push(Rule { slot: vk, condition: condition });
+                    }
+                },
+
+                name => return Err(error(section, section.line, &format!("unknown section '{}'", name)))
+            }
+        }
+
+        Ok(settings)
+    }
+}
+
+fn error(section: &Section, line: usize, message: &str) -> ParseError {
+    ParseError {
+        line: line,
+        column: 1,
+        message: format!("[{}] {}", section.name, message),
+    }
+}
+
+/// Fails on the first key of `section` that is not in `known`, which is usually a typo
+fn check_keys(section: &Section, known: &[&str]) -> Result<(), ParseError> {
+    match section.entries().into_iter().find(|&(key, _)| !known.contains(&key)) {
+        Some((key, _)) => Err(error(section, section.line_of(key), &format!("unknown key '{}'", key))),
+        None => Ok(())
+    }
+}
+
+fn parse_value<T: FromStr>(section: &Section, key: &str) -> Result<Option<T>, ParseError> {
+    match section.get(key) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| error(section, section.line_of(key), &format!("invalid value for '{}': {}", key, value))),
+        None => Ok(None)
+    }
+}
+
+/// Slots are named after the digit of their hotkey
+pub fn slot_key(name: &str) -> Option<UINT> {
+    match name.trim().parse::<u8>() {
+        Ok(digit) if digit <= 9 => Some(VK_0 + digit as UINT),
+        _ => None
+    }
+}
+
+pub fn parse_slot(section: &Section, name: &str) -> Result<UINT, ParseError> {
+    slot_key(name)
+        .ok_or_else(|| error(section, section.line, &format!("slot must be a digit from 0 to 9, not '{}'", name.trim())))
+}
+
+fn parse_launch_command(section: &Section) -> Result<Option<LaunchCommand>, ParseError> {
+    let exe = match section.get("launch") {
+        Some(exe) if !exe.is_empty() => exe,
+        _ => return Ok(None)
+    };
+
+    let mut command = LaunchCommand::new(exe);
+    command.args = section.get("args").unwrap_or("").to_string();
+    command.working_dir = section.get("working_dir").map(|dir| dir.to_string());
+
+    if let Some(seconds) = try!{ parse_value::<u64>(section, "timeout") } {
+        command.timeout = Duration::from_secs(seconds);
+    }
+
+    Ok(Some(command))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use apps::AppIndex;
+    use backend::fake;
+    use commands::CommandSource;
+    use constants::{MOD_ALT, MOD_CONTROL, MOD_WIN, VK_1, VK_2, VK_3, VK_P};
+    use hotkeys::{self, Action, Chord};
+    use ini::Ini;
+    use layout::monitor::Placement;
+    use layout::tiling::Tile;
+    use leader;
+    use log::LevelFilter;
+    use sources::SourceOptions;
+    use theme::{Color, Theme};
+    use window_tracking::{CyclePolicy, SlotScope};
+    use super::Settings;
+
+    fn parse(text: &str) -> Settings {
+        Settings::from_ini(&Ini::parse(text).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn defaults() {
+        let settings = parse("");
+
+        assert_eq!((1280, 800), settings.tiling.center_size);
+        assert_eq!(None, settings.launch_command(VK_3));
+    }
+
+    #[test]
+    fn tiling() {
+        let settings = parse("[tiling]\ncenter_width = 1600\n");
+
+        assert_eq!((1600, 800), settings.tiling.center_size);
+    }
+
+    #[test]
+    fn apps() {
+        let settings = parse("[apps]\nstart_menu = false\ndir = C:\\Tools\ndir = D:\\Portable");
+
+        assert!(!settings.apps.start_menu);
+        assert_eq!(vec![PathBuf::from("C:\\Tools"), PathBuf::from("D:\\Portable")], settings.apps.dirs);
+        assert!(parse("").apps.start_menu);
+
+        let err = Settings::from_ini(&Ini::parse("[apps]\nstart_menu = maybe").unwrap()).unwrap_err();
+        assert_eq!(2, err.line);
+    }
+
+    #[test]
+    fn sources() {
+        let settings = parse("[source commands]\nweight = 1\n[source apps]\nprefix = !");
+
+        assert_eq!(SourceOptions::new(1, ">"), settings.source_options(&CommandSource));
+        assert_eq!(SourceOptions::new(0, ">"), parse("").source_options(&CommandSource));
+        assert_eq!("!", settings.source_options(&AppIndex::new(vec![])).prefix);
+
+        let err = Settings::from_ini(&Ini::parse("[source apps]\n\nweight = -1").unwrap()).unwrap_err();
+        assert_eq!(3, err.line);
+    }
+
+    #[test]
+    fn popup() {
+        assert_eq!(Placement::ForegroundWindow, parse("").popup_placement);
+        assert_eq!(Placement::Cursor, parse("[popup]\nplacement = cursor").popup_placement);
+
+        let err = Settings::from_ini(&Ini::parse("[popup]\nplacement = mouse").unwrap()).unwrap_err();
+        assert_eq!(2, err.line);
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let err = Settings::from_ini(&Ini::parse("[apps]\ndir = C:\\Tools\nlauch = true").unwrap()).unwrap_err();
+        assert_eq!((3, 1), (err.line, err.column));
+        assert!(err.message.contains("unknown key 'lauch'"), "{}", err.message);
+
+        let err = Settings::from_ini(&Ini::parse("[slot 2]\nrule = exe = code.exe\n\nlauch = code.exe").unwrap()).unwrap_err();
+        assert_eq!(4, err.line);
+        assert!(err.message.starts_with("[slot 2] unknown key"), "{}", err.message);
+
+        let err = Settings::from_ini(&Ini::parse("[tiling]\ncenter_widht = 1600").unwrap()).unwrap_err();
+        assert_eq!(2, err.line);
+    }
+
+    #[test]
+    fn theme() {
+        let settings = parse("[theme]\ntext = #ffffff");
+
+        assert_eq!(Color(0x00FFFFFF), settings.theme.text);
+        assert_eq!(Theme::default().background, settings.theme.background);
+
+        let err = Settings::from_ini(&Ini::parse("[theme]\nbackground = red").unwrap()).unwrap_err();
+        assert_eq!(2, err.line);
+    }
+
+    #[test]
+    fn log() {
+        let settings = parse("[log]\nfilter = warn, watcher = trace\nmax_size = 64");
+
+        assert_eq!(LevelFilter::Warn, settings.log.filter.level);
+        assert_eq!(LevelFilter::Trace, settings.log.filter.max_level());
+        assert_eq!(64 * 1024, settings.log.max_size);
+        assert_eq!(3, settings.log.keep);
+
+        let err = Settings::from_ini(&Ini::parse("[log]\nfilter = loud").unwrap()).unwrap_err();
+        assert_eq!(2, err.line);
+
+        // Too large once in bytes
+        let err = Settings::from_ini(&Ini::parse("[log]\nkeep = 2\nmax_size = 18446744073709551615").unwrap()).unwrap_err();
+        assert_eq!(3, err.line);
+        assert_eq!("[log] invalid value for 'max_size': 18446744073709551615", err.message);
+    }
+
+    #[test]
+    fn hotkeys() {
+        let settings = parse("[hotkeys]\nswitch 3 = Alt+3, Ctrl+Win+3\npopup = Ctrl+Alt+Space");
+        let bindings = settings.hotkeys();
+        let binding = |action| *bindings.iter().find(|b| b.action == action).unwrap();
+
+        assert_eq!(Chord::new(MOD_ALT, VK_3), binding(Action::Switch(VK_3)).chord);
+        assert_eq!(Some(Chord::new(MOD_CONTROL | MOD_WIN, VK_3)), binding(Action::Switch(VK_3)).fallback);
+        assert_eq!(Chord::new(MOD_CONTROL | MOD_ALT, 0x20), binding(Action::ShowPopup).chord);
+        assert_eq!(None, binding(Action::ShowPopup).fallback);
+        assert_eq!(Chord::new(MOD_ALT, VK_1), binding(Action::Switch(VK_1)).chord);
+
+        let err = Settings::from_ini(&Ini::parse("[hotkeys]\nswitch 11 = Win+1").unwrap()).unwrap_err();
+        assert_eq!(2, err.line);
+
+        let err = Settings::from_ini(&Ini::parse("[hotkeys]\n\nquit = Hyper+Q").unwrap()).unwrap_err();
+        assert_eq!(3, err.line);
+        assert!(err.message.contains("unknown modifier 'Hyper'"), "{}", err.message);
+
+        let err = Settings::from_ini(&Ini::parse("[hotkeys]\nquit = Win+Q, Alt+Q, Ctrl+Q").unwrap()).unwrap_err();
+        assert_eq!(2, err.line);
+    }
+
+    #[test]
+    fn reload_hotkeys() {
+        let old = parse("[hotkeys]\npopup = Win+Space").hotkeys();
+        let new = parse("[hotkeys]\npopup = Ctrl+Alt+P").hotkeys();
+        let id = old.iter().position(|b| b.action == Action::ShowPopup).unwrap() as i32 + 1;
+
+        let report = hotkeys::register_all(&old, |_, _| Ok(()));
+        assert_eq!(Some(&(id, Action::ShowPopup, Chord::new(MOD_WIN, 0x20))), report.active().iter().find(|a| a.0 == id));
+
+        let mut registered = Vec::new();
+        let mut unregistered = Vec::new();
+        let report = report.update(&old, &new, |id, chord| {
+            registered.push((id, chord));
+            Ok(())
+        }, |id| unregistered.push(id));
+
+        assert_eq!(vec![id], unregistered);
+        assert_eq!(vec![(id, Chord::new(MOD_CONTROL | MOD_ALT, VK_P))], registered);
+        assert_eq!(Some(Action::ShowPopup), report.action(id));
+        assert_eq!(old.len(), report.active().len());
+    }
+
+    #[test]
+    fn leader() {
+        let settings = parse("[leader]\nchord = Win+W\ntimeout_ms = 800\nf b = switch 2\nt l = tile maximize");
+        let leader = settings.hotkeys().into_iter().find(|b| b.action == Action::Leader).unwrap();
+        let sequence = |text| settings.leader.sequences.get(&leader::parse_sequence(text).unwrap());
+
+        assert_eq!(Chord::new(MOD_WIN, 0x57), leader.chord);
+        assert_eq!(Duration::from_millis(800), settings.leader.timeout);
+        assert_eq!(Some(Action::Switch(VK_2)), sequence("f b"));
+        assert_eq!(Some(Action::Tile(Tile::Maximize)), sequence("t l"));
+        assert_eq!(Some(Action::Switch(VK_3)), sequence("f 3"));
+
+        let err = Settings::from_ini(&Ini::parse("[leader]\nf = release").unwrap()).unwrap_err();
+        assert_eq!(2, err.line);
+        assert!(err.message.contains("'f' is the start of longer sequences"), "{}", err.message);
+
+        let err = Settings::from_ini(&Ini::parse("[leader]\nq = sleep").unwrap()).unwrap_err();
+        assert_eq!(2, err.line);
+    }
+
+    #[test]
+    fn launch_command() {
+        let settings = parse(r"
+            [slot 3]
+            launch = C:\Windows\System32\notepad.exe
+            args = C:\notes.txt
+            timeout = 5
+        ");
+
+        let command = settings.launch_command(VK_3).unwrap();
+        assert_eq!(r"C:\Windows\System32\notepad.exe", command.exe);
+        assert_eq!("notepad.exe", command.exe_name());
+        assert_eq!(r"C:\notes.txt", command.args);
+        assert_eq!(None, command.working_dir);
+        assert_eq!(Duration::from_secs(5), command.timeout);
+    }
+
+    #[test]
+    fn cycle_policy() {
+        let settings = parse("[slot 1]\ncycle = if_focused\n[slot 3]\ncycle = cycle");
+
+        assert_eq!(CyclePolicy::CycleIfFocused, settings.cycle_policy(VK_1));
+        assert_eq!(CyclePolicy::Cycle, settings.cycle_policy(VK_3));
+
+        let err = Settings::from_ini(&Ini::parse("[slot 1]\ncycle = sideways").unwrap()).unwrap_err();
+        assert_eq!(2, err.line);
+    }
+
+    #[test]
+    fn slot_scope() {
+        assert_eq!(SlotScope::Global, parse("").slot_scope(VK_1));
+
+        let settings = parse("[desktops]\nscope = desktop\n[slot 3]\nscope = global");
+        assert_eq!(SlotScope::Desktop, settings.slot_scope(VK_1));
+        assert_eq!(SlotScope::Global, settings.slot_scope(VK_3));
+
+        let err = Settings::from_ini(&Ini::parse("[slot 1]\n\nscope = monitor").unwrap()).unwrap_err();
+        assert_eq!(3, err.line);
+    }
+
+    #[test]
+    fn rules() {
+        let settings = parse(r"
+            [slot 1]
+            rule = exe = WindowsTerminal.exe
+
+            [slot 3]
+            rule = class = Chrome_WidgetWin_1 and title ~ 'Jira'
+            rule = exe = jira.exe
+        ");
+
+        assert_eq!(3, settings.rules().len());
+        assert_eq!(CyclePolicy::MostRecentFirst, settings.cycle_policy(VK_3));
+        assert_eq!(Some(VK_1), settings.rule_slot(&fake::identity("WindowsTerminal.exe", "", "Terminal")));
+        assert_eq!(Some(VK_3), settings.rule_slot(&fake::identity("chrome.exe", "Chrome_WidgetWin_1", "PROJ-1 - Jira")));
+        assert_eq!(Some(VK_3), settings.rule_slot(&fake::identity("jira.exe", "", "")));
+        assert_eq!(None, settings.rule_slot(&fake::identity("chrome.exe", "Chrome_WidgetWin_1", "Gmail")));
+    }
+
+    #[test]
+    fn profiles() {
+        let mut settings = parse(r"
+            [slot 1]
+            launch = code.exe
+            rule = exe = code.exe
+
+            [slot 2]
+            rule = exe = chrome.exe
+
+            [slot 1 @ meetings]
+            launch = teams.exe
+            rule = exe = teams.exe
+
+            [slot 3 @ ops]
+            cycle = cycle
+        ");
+
+        assert_eq!(vec!["default", "meetings", "ops"], settings.profiles());
+        assert_eq!("default", settings.profile());
+        assert_eq!("code.exe", settings.launch_command(VK_1).unwrap().exe);
+        assert_eq!(None, settings.rule_slot(&fake::identity("teams.exe", "", "")));
+
+        assert!(settings.set_profile("meetings"));
+        assert_eq!("meetings", settings.profile());
+        assert_eq!("teams.exe", settings.launch_command(VK_1).unwrap().exe);
+        assert_eq!(Some(VK_1), settings.rule_slot(&fake::identity("teams.exe", "", "")));
+        assert_eq!(None, settings.rule_slot(&fake::identity("code.exe", "", "")));
+        assert_eq!(Some(VK_2), settings.rule_slot(&fake::identity("chrome.exe", "", "")));
+
+        assert!(settings.set_profile("ops"));
+        assert_eq!("code.exe", settings.launch_command(VK_1).unwrap().exe);
+        assert_eq!(CyclePolicy::Cycle, settings.cycle_policy(VK_3));
+
+        assert!(!settings.set_profile("coding"));
+        assert_eq!("ops", settings.profile());
+        assert!(settings.set_profile("default"));
+        assert_eq!(CyclePolicy::MostRecentFirst, settings.cycle_policy(VK_3));
+
+        let err = Settings::from_ini(&Ini::parse("[slot 1 @ default]").unwrap()).unwrap_err();
+        assert_eq!(1, err.line);
+    }
+
+    #[test]
+    fn errors() {
+        let err = Settings::from_ini(&Ini::parse("[slot 10]\nlaunch = a.exe").unwrap()).unwrap_err();
+        assert_eq!(1, err.line);
+
+        let err = Settings::from_ini(&Ini::parse("[tiling]\n\ncenter_width = wide").unwrap()).unwrap_err();
+        assert_eq!(3, err.line);
+
+        let err = Settings::from_ini(&Ini::parse("[tilling]").unwrap()).unwrap_err();
+        assert_eq!(1, err.line);
+
+        let err = Settings::from_ini(&Ini::parse("[slot 1]\nrule = title ~ 'Jira").unwrap()).unwrap_err();
+        assert_eq!((2, 16), (err.line, err.column));
+    }
+}
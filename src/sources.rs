@@ -0,0 +1,481 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use fuzzy::{self, FuzzyResult};
+use winapi::windef::HWND;
+
+use backend::{WindowBackend, WindowEvent};
+use commands::Command;
+use eligibility;
+use native_host::Tab;
+use titles::WindowTitle;
+
+/// What accepting a search result does
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResultAction {
+    Focus(HWND),
+    /// Open a file or shortcut like Explorer would
+    Open(PathBuf),
+    Run(Command),
+    /// Show a browser tab through the native messaging host owning this window
+    ActivateTab(HWND, Tab),
+    /// The result can not be used as typed, accepting logs this explanation
+    Usage(String),
+}
+
+/// One line of the popup
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub label: String,
+    /// Shown after the label, e.g. the executable of a window
+    pub subtitle: String,
+    /// Names the icon of the result, like `window:chrome.exe`
+    pub icon: String,
+    /// How well the result matches, higher is better
+    pub score: u32,
+    pub action: ResultAction,
+}
+
+/// How a source takes part in searches, from `[source <name>]` in `winman.ini`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceOptions {
+    /// Scores of the source are multiplied by this, 0 leaves it out unless its prefix is typed
+    pub weight: u32,
+    /// Queries starting with this search the source alone, empty for none
+    pub prefix: String,
+}
+
+impl SourceOptions {
+    pub fn new(weight: u32, prefix: &str) -> Self {
+        SourceOptions {
+            weight: weight,
+            prefix: prefix.to_string(),
+        }
+    }
+}
+
+/// Something the popup can search, like windows, programs or commands
+pub trait ResultSource {
+    /// Names the source in `winman.ini`
+    fn name(&self) -> &'static str;
+
+    /// Weight and prefix unless `winman.ini` changes them
+    fn default_options(&self) -> SourceOptions;
+
+    /// Results matching `query`, scored with `score`
+    fn search(&self, query: &str) -> Vec<SearchResult>;
+}
+
+/// Score of a fuzzy match, from 6 for an exact one down to 0 for none
+pub fn score(result: FuzzyResult) -> u32 {
+    match result {
+        FuzzyResult::ExactMatch => 6,
+        FuzzyResult::StartsWith => 5,
+        FuzzyResult::SmartCamel => 4,
+        FuzzyResult::UpperCamel => 3,
+        FuzzyResult::Contains => 2,
+        FuzzyResult::Vague => 1,
+        FuzzyResult::None => 0,
+    }
+}
+
+/// Searches every source, or only the one whose prefix starts `query`, best results first
+///
+/// Results with the same score keep the order of `sources` and of each source.
+pub fn search(sources: &[(&dyn ResultSource, SourceOptions)], query: &str) -> Vec<SearchResult> {
+    search_until(sources, query, &|| false).unwrap_or_default()
+}
+
+/// Like `search`, but gives up with `None` once `cancelled` returns true, which
+/// is asked before each source
+pub fn search_until(sources: &[(&dyn ResultSource, SourceOptions)], query: &str, cancelled: &dyn Fn() -> bool)
+    -> Option<Vec<SearchResult>> {
+
+    let prefixed = sources.iter()
+        .find(|&&(_, ref options)| !options.prefix.is_empty() && query.starts_with(options.prefix.as_str()));
+
+    let searched: Vec<(&dyn ResultSource, &str, u32)> = match prefixed {
+        Some(&(source, ref options)) => vec![(source, &query[options.prefix.len()..], 1)],
+        None => sources.iter()
+            .filter(|&&(_, ref options)| options.weight > 0)
+            .map(|&(source, ref options)| (source, query, options.weight))
+            .collect()
+    };
+
+    let mut results = Vec::new();
+    for (source, query, weight) in searched {
+        if cancelled() {
+            return None;
+        }
+
+        for mut result in source.search(query) {
+            result.score *= weight;
+            results.push(result);
+        }
+    }
+
+    results.sort_by(|a, b| b.score.cmp(&a.score));
+    Some(results)
+}
+
+/// An index older than this is rebuilt when asked, in case an event was missed
+const MAX_INDEX_AGE_SECS: u64 = 60;
+
+/// Switchable windows, searched by title
+///
+/// The index is built by `refresh` and kept current by passing window events
+/// to `handle`, so the popup does not read every title again when it opens.
+/// Windows created or focused since come first, the rest stay in z-order.
+/// Queries are fuzzy matched, and also tried as a regular expression like
+/// `chr.*inbox`. Windows whose title is not available, because they did not
+/// answer, are listed by executable until a title can be read.
+pub struct WindowSource {
+    /// Handle, title and executable of each window
+    windows: Vec<(HWND, WindowTitle, String)>,
+    /// When `refresh` last read all windows, `None` before the first time
+    refreshed: Option<Instant>,
+}
+
+impl WindowSource {
+    pub fn new() -> Self {
+        WindowSource {
+            windows: Vec::new(),
+            refreshed: None,
+        }
+    }
+
+    /// Rebuilds the index from the titles and executables of all switchable windows
+    pub fn refresh<B: WindowBackend>(&mut self, backend: &B, now: Instant) {
+        self.windows = eligibility::switchable_windows(backend)
+            .into_iter()
+            .filter_map(|hwnd| backend.window_identity(hwnd).ok().map(|identity| (hwnd, identity.title, identity.exe)))
+            .collect();
+
+        self.refreshed = Some(now);
+    }
+
+    /// Rebuilds the index if it was never built, was invalidated or is older than
+    /// `MAX_INDEX_AGE_SECS`, returns whether it did
+    ///
+    /// Otherwise only the windows whose title was not available are read again.
+    pub fn refresh_if_stale<B: WindowBackend>(&mut self, backend: &B, now: Instant) -> bool {
+        let stale = match self.refreshed {
+            Some(refreshed) => now.duration_since(refreshed) >= Duration::from_secs(MAX_INDEX_AGE_SECS),
+            None => true
+        };
+
+        if stale {
+            self.refresh(backend, now);
+        } else {
+            let unavailable: Vec<HWND> = self.windows.iter()
+                .filter(|&&(_, ref title, _)| *title == WindowTitle::Unavailable)
+                .map(|&(hwnd, _, _)| hwnd)
+                .collect();
+
+            for hwnd in unavailable {
+                self.update(backend, hwnd, false);
+            }
+        }
+
+        stale
+    }
+
+    /// Makes the next `refresh_if_stale` rebuild the index, for when there are no events
+    pub fn invalidate(&mut self) {
+        self.refreshed = None;
+    }
+
+    /// Updates the window an event is about, only reading its title and executable
+    pub fn handle<B: WindowBackend>(&mut self, backend: &B, event: WindowEvent) {
+        match event {
+            WindowEvent::Created(hwnd) => self.update(backend, hwnd, true),
+            WindowEvent::TitleChanged(hwnd) | WindowEvent::Hidden(hwnd) => self.update(backend, hwnd, false),
+
+            WindowEvent::Foreground(hwnd) => {
+                match self.position(hwnd) {
+                    Some(index) => {
+                        let window = self.windows.remove(index);
+                        self.windows.insert(0, window);
+                    },
+                    None => self.update(backend, hwnd, true)
+                }
+            },
+
+            WindowEvent::Destroyed(hwnd) => {
+                if let Some(index) = self.position(hwnd) {
+                    self.windows.remove(index);
+                }
+            },
+        }
+    }
+
+    /// Reads a window again, adding it if it became switchable and dropping it if
+    /// it no longer is
+    fn update<B: WindowBackend>(&mut self, backend: &B, hwnd: HWND, to_front: bool) {
+        let identity = if eligibility::is_switchable(backend, hwnd) {
+            backend.window_identity(hwnd).ok()
+        } else {
+            None
+        };
+
+        match (self.position(hwnd), identity) {
+            (Some(index), Some(identity)) => {
+                if to_front {
+                    self.windows.remove(index);
+                    self.windows.insert(0, (hwnd, identity.title, identity.exe));
+                } else {
+                    self.windows[index] = (hwnd, identity.title, identity.exe);
+                }
+            },
+            (None, Some(identity)) => self.windows.insert(0, (hwnd, identity.title, identity.exe)),
+            (Some(index), None) => {
+                self.windows.remove(index);
+            },
+            (None, None) => {}
+        }
+    }
+
+    fn position(&self, hwnd: HWND) -> Option<usize> {
+        self.windows.iter().position(|&(h, _, _)| h == hwnd)
+    }
+
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+}
+
+impl ResultSource for WindowSource {
+    fn name(&self) -> &'static str {
+        "windows"
+    }
+
+    fn default_options(&self) -> SourceOptions {
+        // Above any program, see `apps::AppIndex`
+        SourceOptions::new(10, "")
+    }
+
+    fn search(&self, query: &str) -> Vec<SearchResult> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let finder = fuzzy::Finder::new(query).ok();
+
+        self.windows.iter()
+            .filter_map(|&(hwnd, ref title, ref exe)| {
+                let (title, subtitle) = match title.text() {
+                    Some(title) => (title, exe.clone()),
+                    None => (exe.as_str(), "not responding".to_string()),
+                };
+
+                let score = match score(fuzzy::fuzzy_match(query, title)) {
+                    0 if finder.as_ref().map_or(false, |finder| finder.is_match(title)) => 1,
+                    score => score
+                };
+
+                if score == 0 {
+                    return None;
+                }
+
+                Some(SearchResult {
+                    label: title.to_string(),
+                    subtitle: subtitle,
+                    icon: format!("window:{}", exe),
+                    score: score,
+                    action: ResultAction::Focus(hwnd),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use backend::{WindowBackend, WindowEvent};
+    use backend::fake::{self, FakeBackend};
+    use layout::Rect;
+    use titles::WindowTitle;
+    use super::{search, search_until, ResultAction, ResultSource, SearchResult, SourceOptions, WindowSource, MAX_INDEX_AGE_SECS};
+
+    /// Lists fixed labels with fixed scores
+    struct FixedSource(&'static str, Vec<(&'static str, u32)>);
+
+    impl ResultSource for FixedSource {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn default_options(&self) -> SourceOptions {
+            SourceOptions::new(1, "")
+        }
+
+        fn search(&self, query: &str) -> Vec<SearchResult> {
+            self.1.iter()
+                .filter(|&&(label, _)| label.contains(query))
+                .map(|&(label, score)| SearchResult {
+                    label: label.to_string(),
+                    subtitle: String::new(),
+                    icon: String::new(),
+                    score: score,
+                    action: ResultAction::Usage(label.to_string()),
+                })
+                .collect()
+        }
+    }
+
+    fn labels(results: Vec<SearchResult>) -> Vec<String> {
+        results.into_iter().map(|result| result.label).collect()
+    }
+
+    #[test]
+    fn weights_and_prefixes() {
+        let files = FixedSource("files", vec![("notes.txt", 3), ("todo.txt", 1)]);
+        let tabs = FixedSource("tabs", vec![("notes - Docs", 2), ("todo - Jira", 2)]);
+        let hidden = FixedSource("hidden", vec![("notes.md", 6)]);
+
+        let sources: Vec<(&dyn ResultSource, SourceOptions)> = vec![
+            (&files, SourceOptions::new(1, "f ")),
+            (&tabs, SourceOptions::new(2, "")),
+            (&hidden, SourceOptions::new(0, "#")),
+        ];
+
+        assert_eq!(vec!["notes - Docs", "todo - Jira", "notes.txt", "todo.txt"], labels(search(&sources, "t")));
+        assert_eq!(vec!["notes.txt"], labels(search(&sources, "f notes")));
+        assert_eq!(vec!["notes.md"], labels(search(&sources, "#notes")));
+        assert!(search(&sources, "xyzzy").is_empty());
+        assert_eq!(None, search_until(&sources, "t", &|| true));
+    }
+
+    #[test]
+    fn windows() {
+        let mut backend = FakeBackend::new(vec![]);
+        let bounds = Rect::from_edges(0, 0, 800, 600);
+        let inbox = backend.add_identified_window(1, fake::identity("chrome.exe", "Chrome", "Inbox - Mail"), bounds);
+        let notes = backend.add_window(2, "Release notes", bounds);
+        backend.add_window(3, "", bounds);
+
+        let mut source = WindowSource::new();
+        source.refresh(&backend, Instant::now());
+        assert_eq!(2, source.len());
+
+        let results = source.search("inbox");
+        assert_eq!(1, results.len());
+        assert_eq!(ResultAction::Focus(inbox), results[0].action);
+        assert_eq!("chrome.exe", results[0].subtitle);
+        assert_eq!("window:chrome.exe", results[0].icon);
+
+        // Regular expressions still find windows fuzzy matching does not
+        let results = source.search("rel.*tes");
+        assert_eq!(vec![ResultAction::Focus(notes)], results.into_iter().map(|result| result.action).collect::<Vec<_>>());
+        assert!(source.search(" ").is_empty());
+
+        backend.close_window(inbox);
+        source.refresh(&backend, Instant::now());
+        assert!(source.search("inbox").is_empty());
+    }
+
+    #[test]
+    fn unavailable_titles() {
+        let mut backend = FakeBackend::new(vec![]);
+        let bounds = Rect::from_edges(0, 0, 800, 600);
+        let hung = backend.add_identified_window(1, fake::identity("notepad.exe", "Notepad", ""), bounds);
+        backend.window_mut(hung).unwrap().identity.title = WindowTitle::Unavailable;
+
+        let start = Instant::now();
+        let mut source = WindowSource::new();
+        assert!(source.refresh_if_stale(&backend, start));
+
+        // Listed by executable while the window does not answer
+        let results = source.search("notepad");
+        assert_eq!(vec!["notepad.exe"], labels(results.clone()));
+        assert_eq!("not responding", results[0].subtitle);
+
+        // Read again on the next refresh, even though the index is not stale
+        backend.window_mut(hung).unwrap().identity.title = WindowTitle::new("todo.txt - Notepad".to_string());
+        assert!(!source.refresh_if_stale(&backend, start + Duration::from_secs(1)));
+        assert_eq!(vec!["todo.txt - Notepad"], labels(source.search("todo")));
+    }
+
+    /// Windows in the index by handle, events reorder them
+    fn indexed(source: &WindowSource) -> Vec<(usize, Option<String>)> {
+        let mut windows: Vec<_> = source.windows.iter()
+            .map(|&(hwnd, ref title, _)| (hwnd as usize, title.text().map(String::from)))
+            .collect();
+        windows.sort();
+        windows
+    }
+
+    #[test]
+    fn index_follows_events() {
+        let mut backend = FakeBackend::new(vec![]);
+        let rx = backend.watch_windows().unwrap();
+        let bounds = Rect::from_edges(0, 0, 800, 600);
+        let start = Instant::now();
+
+        for id in 1..4 {
+            backend.add_window(id, &format!("Window {}", id), bounds);
+        }
+
+        let mut source = WindowSource::new();
+        assert!(source.refresh_if_stale(&backend, start));
+        while let Ok(_) = rx.try_recv() {}
+
+        // Steps through windows being opened, renamed, hidden, focused and closed,
+        // comparing the index to a fresh one after each
+        let mut seed: u32 = 7;
+        for step in 0..300 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let id = 1 + (seed >> 16) as usize % 8;
+            let hwnd = fake::hwnd(id);
+
+            match (seed >> 8) % 8 {
+                0 | 1 if backend.window(hwnd).is_none() => { backend.add_window(id, &format!("New {}", step), bounds); },
+                0 => backend.close_window(hwnd),
+                1 | 2 => backend.set_title(hwnd, &format!("Title {}", step)),
+                3 => backend.set_title(hwnd, ""),
+                4 => {
+                    let _ = backend.focus_window(hwnd);
+                },
+                5 => {
+                    let visible = backend.window(hwnd).map_or(false, |window| !window.visible);
+                    backend.set_visible(hwnd, visible);
+                },
+                _ => {
+                    if let Ok(window) = backend.window_mut(hwnd) {
+                        window.tool_window = !window.tool_window;
+                    }
+                    backend.emit(WindowEvent::Created(hwnd));
+                }
+            }
+
+            while let Ok(event) = rx.try_recv() {
+                source.handle(&backend, event);
+            }
+
+            let mut fresh = WindowSource::new();
+            fresh.refresh(&backend, start);
+            assert_eq!(indexed(&fresh), indexed(&source), "step {}", step);
+        }
+
+        // New and focused windows come first
+        let older = backend.add_window(20, "Older", bounds);
+        let newer = backend.add_window(21, "Newer", bounds);
+        while let Ok(event) = rx.try_recv() {
+            source.handle(&backend, event);
+        }
+        assert_eq!(vec![newer, older], source.windows.iter().take(2).map(|&(hwnd, _, _)| hwnd).collect::<Vec<_>>());
+
+        backend.focus_window(older).unwrap();
+        source.handle(&backend, rx.try_recv().unwrap());
+        assert_eq!(older, source.windows[0].0);
+
+        assert!(!source.refresh_if_stale(&backend, start + Duration::from_secs(1)));
+        assert!(source.refresh_if_stale(&backend, start + Duration::from_secs(MAX_INDEX_AGE_SECS)));
+
+        source.invalidate();
+        assert!(source.refresh_if_stale(&backend, start + Duration::from_secs(MAX_INDEX_AGE_SECS + 1)));
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A `COLORREF`, written like `#a3ffa3` in `winman.ini`
+///
+/// The value is laid out as `0x00bbggrr` for GDI.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Color(pub u32);
+
+impl Color {
+    pub fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color((b as u32) << 16 | (g as u32) << 8 | r as u32)
+    }
+}
+
+impl FromStr for Color {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, ()> {
+        if text.len() != 7 || !text.starts_with('#') || !text[1..].chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(());
+        }
+
+        let rgb = try!{ u32::from_str_radix(&text[1..], 16).map_err(|_| ()) };
+        Ok(Color::rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0 & 0xFF, (self.0 >> 8) & 0xFF, (self.0 >> 16) & 0xFF)
+    }
+}
+
+/// Colors of the popup window
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub background: Color,
+    pub text: Color,
+    /// Background of the search box, also used for the border
+    pub edit_background: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            background: Color(0x00222222),
+            text: Color(0x00A3FFA3),
+            edit_background: Color(0x00323232),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Color;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Ok(Color(0x00A3FFA3)), "#a3ffa3".parse());
+        assert_eq!(Ok(Color(0x00332211)), "#112233".parse());
+        assert_eq!("#112233", Color(0x00332211).to_string());
+
+        assert_eq!(Err(()), "112233".parse::<Color>());
+        assert_eq!(Err(()), "#1122".parse::<Color>());
+        assert_eq!(Err(()), "#11223g".parse::<Color>());
+        assert_eq!(Err(()), "#+11223".parse::<Color>());
+    }
+}
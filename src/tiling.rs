@@ -0,0 +1,68 @@
+use winapi::windef::HWND;
+
+use backend::WindowBackend;
+use layout::monitor;
+use layout::tiling::{self, Tile, TilingOptions};
+use utils::Win32Result;
+
+/// Moves a window to `tile` on the monitor that shows most of it
+pub fn tile_window<B: WindowBackend>(backend: &mut B, hwnd: HWND, tile: Tile, options: &TilingOptions) -> Win32Result<()> {
+    let bounds = try!{ backend.window_bounds(hwnd) };
+    let monitors = backend.monitors();
+
+    match monitor::monitor_from_bounds(&monitors, bounds) {
+        Some(monitor) => {
+            let target = tiling::tile_bounds(tile, monitor.work_area, options);
+            backend.set_window_bounds(hwnd, target)
+        },
+        None => Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::fake::{self, FakeBackend};
+    use layout::Rect;
+    use layout::tiling::{Tile, TilingOptions};
+    use super::tile_window;
+
+    fn backend() -> FakeBackend {
+        FakeBackend::new(vec![
+            fake::monitor(0, 0, 1920, 1080, true),
+            fake::monitor(-2560, -360, 0, 1080, false),
+        ])
+    }
+
+    #[test]
+    fn tiles_on_own_monitor() {
+        let mut backend = backend();
+        let primary = backend.add_window(1, "Editor", Rect::from_edges(100, 100, 900, 700));
+        let secondary = backend.add_window(2, "Browser", Rect::from_edges(-1000, 0, 100, 800));
+
+        tile_window(&mut backend, primary, Tile::RightHalf, &TilingOptions::default()).unwrap();
+        tile_window(&mut backend, secondary, Tile::LeftHalf, &TilingOptions::default()).unwrap();
+
+        assert_eq!(Rect::from_edges(960, 0, 1920, 1040), backend.window(primary).unwrap().bounds);
+        assert_eq!(Rect::from_edges(-2560, -360, -1280, 1040), backend.window(secondary).unwrap().bounds);
+    }
+
+    #[test]
+    fn center_uses_configured_size() {
+        let mut backend = backend();
+        let hwnd = backend.add_window(1, "Editor", Rect::from_edges(0, 0, 1920, 1040));
+        let options = TilingOptions { center_size: (1000, 600) };
+
+        tile_window(&mut backend, hwnd, Tile::Center, &options).unwrap();
+
+        assert_eq!(Rect::from_edges(460, 220, 1460, 820), backend.window(hwnd).unwrap().bounds);
+    }
+
+    #[test]
+    fn closed_window() {
+        let mut backend = backend();
+        let hwnd = backend.add_window(1, "Editor", Rect::from_edges(0, 0, 100, 100));
+        backend.close_window(hwnd);
+
+        assert!(tile_window(&mut backend, hwnd, Tile::Maximize, &TilingOptions::default()).is_err());
+    }
+}
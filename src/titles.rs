@@ -0,0 +1,223 @@
+use std::time::Duration;
+
+use winapi::windef::HWND;
+use winapi::winerror::ERROR_INVALID_WINDOW_HANDLE;
+
+use error::Error;
+
+/// How long reading a title may take before WinMan stops waiting for the window
+pub const DEFAULT_TIMEOUT_MS: u64 = 100;
+
+/// Title of a window, see `read_title`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowTitle {
+    Text(String),
+    /// The window has no title
+    Empty,
+    /// The window did not answer and Windows has no text stored for it, it may
+    /// or may not have a title
+    Unavailable,
+}
+
+impl Default for WindowTitle {
+    fn default() -> Self {
+        WindowTitle::Empty
+    }
+}
+
+impl WindowTitle {
+    /// `Text`, or `Empty` for an empty `text`
+    pub fn new(text: String) -> Self {
+        match text {
+            ref text if text.is_empty() => WindowTitle::Empty,
+            text => WindowTitle::Text(text),
+        }
+    }
+
+    /// The title, empty if there is none, `None` if it is not known
+    pub fn text(&self) -> Option<&str> {
+        match *self {
+            WindowTitle::Text(ref text) => Some(text),
+            WindowTitle::Empty => Some(""),
+            WindowTitle::Unavailable => None,
+        }
+    }
+}
+
+/// Answer of a window asked for its text
+#[derive(Debug)]
+pub enum TextReply {
+    Text(String),
+    /// The window did not answer in time, or Windows considers it hung
+    TimedOut,
+    Failed(Error),
+}
+
+/// Calls `read_title` makes, the Win32 ones are in `utils::api_wrappers`
+pub trait TitleApi {
+    fn is_window(&self, hwnd: HWND) -> bool;
+
+    /// Windows that have not processed messages for a while, which are not asked
+    fn is_hung(&self, hwnd: HWND) -> bool;
+
+    /// Asks the window for its text like `GetWindowTextW`, waiting at most `timeout`
+    fn request_text(&self, hwnd: HWND, timeout: Duration) -> TextReply;
+
+    /// Text Windows stores for the window, read without asking it like `InternalGetWindowText`
+    fn internal_text(&self, hwnd: HWND) -> String;
+}
+
+/// Reads the title of a window without blocking on it for more than `timeout`
+///
+/// Hung windows and ones that do not answer in time get the text Windows stored
+/// for them, which may be out of date, or `Unavailable` if there is none. An
+/// answer without text is `Empty`. Only a window that does not exist, or one
+/// that can not be asked, is an error.
+pub fn read_title<A: TitleApi>(api: &A, hwnd: HWND, timeout: Duration) -> Result<WindowTitle, Error> {
+    if !api.is_window(hwnd) {
+        return Err(Error::Win32(ERROR_INVALID_WINDOW_HANDLE));
+    }
+
+    if api.is_hung(hwnd) {
+        return Ok(stored_title(api, hwnd));
+    }
+
+    match api.request_text(hwnd, timeout) {
+        TextReply::Text(ref text) if text.is_empty() => Ok(WindowTitle::Empty),
+        TextReply::Text(text) => Ok(WindowTitle::Text(text)),
+        TextReply::TimedOut => Ok(stored_title(api, hwnd)),
+
+        // Usually the window was destroyed while it was asked
+        TextReply::Failed(_) if !api.is_window(hwnd) => Err(Error::Win32(ERROR_INVALID_WINDOW_HANDLE)),
+        TextReply::Failed(err) => Err(err),
+    }
+}
+
+fn stored_title<A: TitleApi>(api: &A, hwnd: HWND) -> WindowTitle {
+    match api.internal_text(hwnd) {
+        ref text if text.is_empty() => WindowTitle::Unavailable,
+        text => WindowTitle::Text(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    use winapi::windef::HWND;
+    use winapi::winerror::{ERROR_ACCESS_DENIED, ERROR_INVALID_WINDOW_HANDLE};
+
+    use backend::fake::hwnd;
+    use error::Error;
+    use super::{read_title, TextReply, TitleApi, WindowTitle};
+
+    /// One window, answering like a window in each state the contract covers
+    struct FakeApi {
+        exists: Cell<bool>,
+        hung: bool,
+        /// Answer to `request_text`, `None` to time out
+        reply: Option<Result<&'static str, u32>>,
+        stored: &'static str,
+        /// Destroy the window while it is asked
+        destroy: bool,
+        /// Longest timeout the window was asked with
+        asked: Cell<Option<Duration>>,
+    }
+
+    fn api(reply: Option<Result<&'static str, u32>>, stored: &'static str) -> FakeApi {
+        FakeApi {
+            exists: Cell::new(true),
+            hung: false,
+            reply: reply,
+            stored: stored,
+            destroy: false,
+            asked: Cell::new(None),
+        }
+    }
+
+    impl TitleApi for FakeApi {
+        fn is_window(&self, _: HWND) -> bool {
+            self.exists.get()
+        }
+
+        fn is_hung(&self, _: HWND) -> bool {
+            self.hung
+        }
+
+        fn request_text(&self, _: HWND, timeout: Duration) -> TextReply {
+            self.asked.set(Some(timeout));
+
+            if self.destroy {
+                self.exists.set(false);
+            }
+
+            match self.reply {
+                Some(Ok(text)) => TextReply::Text(text.to_string()),
+                Some(Err(code)) => TextReply::Failed(Error::Win32(code)),
+                None => TextReply::TimedOut,
+            }
+        }
+
+        fn internal_text(&self, _: HWND) -> String {
+            self.stored.to_string()
+        }
+    }
+
+    fn read(api: &FakeApi) -> Result<WindowTitle, u32> {
+        read_title(api, hwnd(1), Duration::from_millis(50)).map_err(|err| match err {
+            Error::Win32(code) => code,
+            err => panic!("unexpected error {}", err),
+        })
+    }
+
+    #[test]
+    fn answered() {
+        let titled = api(Some(Ok("Inbox - Mail")), "Inbox");
+        assert_eq!(Ok(WindowTitle::Text("Inbox - Mail".to_string())), read(&titled));
+        assert_eq!(Some(Duration::from_millis(50)), titled.asked.get());
+
+        // Untitled windows are not an error, whatever Windows stored for them
+        assert_eq!(Ok(WindowTitle::Empty), read(&api(Some(Ok("")), "")));
+        assert_eq!(Ok(WindowTitle::Empty), read(&api(Some(Ok("")), "Old title")));
+    }
+
+    #[test]
+    fn not_answered() {
+        // Hung windows are never asked
+        let mut hung = api(Some(Ok("Live title")), "Stored title");
+        hung.hung = true;
+        assert_eq!(Ok(WindowTitle::Text("Stored title".to_string())), read(&hung));
+        assert_eq!(None, hung.asked.get());
+
+        hung.stored = "";
+        assert_eq!(Ok(WindowTitle::Unavailable), read(&hung));
+
+        assert_eq!(Ok(WindowTitle::Text("Stored title".to_string())), read(&api(None, "Stored title")));
+        assert_eq!(Ok(WindowTitle::Unavailable), read(&api(None, "")));
+    }
+
+    #[test]
+    fn errors() {
+        let gone = api(Some(Ok("Inbox")), "Inbox");
+        gone.exists.set(false);
+        assert_eq!(Err(ERROR_INVALID_WINDOW_HANDLE), read(&gone));
+        assert_eq!(None, gone.asked.get());
+
+        let mut closing = api(Some(Err(ERROR_ACCESS_DENIED)), "Inbox");
+        closing.destroy = true;
+        assert_eq!(Err(ERROR_INVALID_WINDOW_HANDLE), read(&closing));
+
+        assert_eq!(Err(ERROR_ACCESS_DENIED), read(&api(Some(Err(ERROR_ACCESS_DENIED)), "Inbox")));
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(WindowTitle::Text("Inbox".to_string()), WindowTitle::new("Inbox".to_string()));
+        assert_eq!(WindowTitle::Empty, WindowTitle::new(String::new()));
+
+        assert_eq!(Some("Inbox"), WindowTitle::Text("Inbox".to_string()).text());
+        assert_eq!(Some(""), WindowTitle::Empty.text());
+        assert_eq!(None, WindowTitle::Unavailable.text());
+    }
+}
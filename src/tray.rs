@@ -0,0 +1,202 @@
+use winapi::minwindef::UINT;
+
+use constants::VK_0;
+
+const ID_SHOW_SWITCHER: UINT = 1;
+const ID_RELOAD_CONFIG: UINT = 2;
+const ID_PAUSE_HOTKEYS: UINT = 3;
+const ID_OPEN_LOG: UINT = 4;
+const ID_QUIT: UINT = 5;
+// Followed by the slot's virtual key code
+const ID_FOCUS_SLOT: UINT = 0x100;
+
+/// What choosing an item in the tray menu does
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TrayCommand {
+    ShowSwitcher,
+    FocusSlot(UINT),
+    ReloadConfig,
+    PauseHotkeys,
+    OpenLog,
+    Quit,
+}
+
+impl TrayCommand {
+    /// Menu item ID, which must not be 0 since that means no choice
+    pub fn id(self) -> UINT {
+        match self {
+            TrayCommand::ShowSwitcher => ID_SHOW_SWITCHER,
+            TrayCommand::FocusSlot(vk) => ID_FOCUS_SLOT + vk,
+            TrayCommand::ReloadConfig => ID_RELOAD_CONFIG,
+            TrayCommand::PauseHotkeys => ID_PAUSE_HOTKEYS,
+            TrayCommand::OpenLog => ID_OPEN_LOG,
+            TrayCommand::Quit => ID_QUIT,
+        }
+    }
+
+    pub fn from_id(id: UINT) -> Option<Self> {
+        match id {
+            ID_SHOW_SWITCHER => Some(TrayCommand::ShowSwitcher),
+            ID_RELOAD_CONFIG => Some(TrayCommand::ReloadConfig),
+            ID_PAUSE_HOTKEYS => Some(TrayCommand::PauseHotkeys),
+            ID_OPEN_LOG => Some(TrayCommand::OpenLog),
+            ID_QUIT => Some(TrayCommand::Quit),
+            id if (ID_FOCUS_SLOT + VK_0..=ID_FOCUS_SLOT + VK_0 + 9).contains(&id) => Some(TrayCommand::FocusSlot(id - ID_FOCUS_SLOT)),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MenuItem {
+    Command {
+        command: TrayCommand,
+        label: String,
+        checked: bool,
+    },
+    /// Greyed out text
+    Label(String),
+    Submenu(String, Vec<MenuItem>),
+    Separator,
+}
+
+impl MenuItem {
+    fn command(command: TrayCommand, label: &str) -> Self {
+        MenuItem::Command {
+            command: command,
+            label: label.to_string(),
+            checked: false,
+        }
+    }
+}
+
+/// Windows in a slot, front first, for listing it in the menu
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotSummary {
+    pub vk: UINT,
+    pub titles: Vec<String>,
+}
+
+/// Tooltip of the tray icon
+pub fn tooltip(paused: bool) -> &'static str {
+    if paused { "WinMan (hotkeys paused)" } else { "WinMan" }
+}
+
+/// Menu shown when the tray icon is right-clicked
+pub fn menu(paused: bool, slots: &[SlotSummary]) -> Vec<MenuItem> {
+    let slot_items: Vec<MenuItem> = slots.iter()
+        .filter(|slot| !slot.titles.is_empty())
+        .map(|slot| {
+            let mut label = format!("&{}  {}", slot.vk - VK_0, escape(&slot.titles[0]));
+            if slot.titles.len() > 1 {
+                label.push_str(&format!("  (+{})", slot.titles.len() - 1));
+            }
+
+            MenuItem::Command {
+                command: TrayCommand::FocusSlot(slot.vk),
+                label: label,
+                checked: false,
+            }
+        })
+        .collect();
+
+    let slot_items = if slot_items.is_empty() {
+        vec![MenuItem::Label("No windows in slots".to_string())]
+    } else {
+        slot_items
+    };
+
+    vec![
+        MenuItem::command(TrayCommand::ShowSwitcher, "&Show switcher"),
+        MenuItem::Submenu("S&lots".to_string(), slot_items),
+        MenuItem::Separator,
+        MenuItem::command(TrayCommand::ReloadConfig, "&Reload config"),
+        MenuItem::Command {
+            command: TrayCommand::PauseHotkeys,
+            label: "&Pause hotkeys".to_string(),
+            checked: paused,
+        },
+        MenuItem::command(TrayCommand::OpenLog, "Open &log"),
+        MenuItem::Separator,
+        MenuItem::command(TrayCommand::Quit, "&Quit"),
+    ]
+}
+
+/// Window titles can contain `&`, which menus would show as an underlined access key
+fn escape(text: &str) -> String {
+    text.replace('&', "&&")
+}
+
+#[cfg(test)]
+mod tests {
+    use constants::{VK_0, VK_3, VK_9};
+    use super::{menu, MenuItem, SlotSummary, TrayCommand};
+
+    fn commands(items: &[MenuItem]) -> Vec<TrayCommand> {
+        items.iter()
+            .flat_map(|item| match *item {
+                MenuItem::Command { command, .. } => vec![command],
+                MenuItem::Submenu(_, ref items) => commands(items),
+                _ => vec![],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ids() {
+        let all = [
+            TrayCommand::ShowSwitcher, TrayCommand::FocusSlot(VK_0), TrayCommand::FocusSlot(VK_9),
+            TrayCommand::ReloadConfig, TrayCommand::PauseHotkeys, TrayCommand::OpenLog, TrayCommand::Quit,
+        ];
+
+        for &command in all.iter() {
+            assert!(command.id() != 0);
+            assert_eq!(Some(command), TrayCommand::from_id(command.id()));
+        }
+
+        assert_eq!(None, TrayCommand::from_id(0));
+        assert_eq!(None, TrayCommand::from_id(TrayCommand::FocusSlot(VK_9).id() + 1));
+    }
+
+    #[test]
+    fn slots() {
+        let slots = vec![
+            SlotSummary { vk: VK_0, titles: vec![] },
+            SlotSummary { vk: VK_3, titles: vec!["Tom & Jerry".to_string(), "Inbox".to_string()] },
+        ];
+
+        let items = menu(false, &slots);
+        assert_eq!(vec![
+            TrayCommand::ShowSwitcher,
+            TrayCommand::FocusSlot(VK_3),
+            TrayCommand::ReloadConfig,
+            TrayCommand::PauseHotkeys,
+            TrayCommand::OpenLog,
+            TrayCommand::Quit,
+        ], commands(&items));
+
+        match items[1] {
+            MenuItem::Submenu(_, ref items) => match items[0] {
+                MenuItem::Command { ref label, .. } => assert_eq!("&3  Tom && Jerry  (+1)", label),
+                ref item => panic!("{:?}", item),
+            },
+            ref item => panic!("{:?}", item),
+        }
+
+        match menu(false, &[])[1] {
+            MenuItem::Submenu(_, ref items) => assert_eq!(vec![MenuItem::Label("No windows in slots".to_string())], *items),
+            ref item => panic!("{:?}", item),
+        }
+    }
+
+    #[test]
+    fn paused() {
+        let checked = |items: Vec<MenuItem>| items.into_iter().any(|item| match item {
+            MenuItem::Command { command: TrayCommand::PauseHotkeys, checked, .. } => checked,
+            _ => false,
+        });
+
+        assert!(checked(menu(true, &[])));
+        assert!(!checked(menu(false, &[])));
+    }
+}
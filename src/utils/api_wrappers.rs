@@ -1,11 +1,53 @@
+use std::time::Duration;
+
+use gdi32;
 use kernel32;
 use user32;
 use winapi::minwindef::*;
 use winapi::windef::*;
+use winapi::winuser::{MONITORINFOEXW, LPMONITORINFO};
+use winapi::wingdi::LOGPIXELSX;
+use winapi::winerror::HRESULT;
+use winapi::{c_int, LPCSTR};
 
+use error::Error;
+use layout::Rect;
+use titles::{self, TextReply, TitleApi, WindowTitle};
 use utils;
 use utils::Win32Result;
 
+// Not covered by the -sys crates
+#[link(name = "dwmapi")]
+extern "system" {
+    fn DwmGetWindowAttribute(hwnd: HWND, dwAttribute: DWORD, pvAttribute: LPVOID, cbAttribute: DWORD) -> HRESULT;
+}
+
+const DWMWA_EXTENDED_FRAME_BOUNDS: DWORD = 9;
+const DWMWA_CLOAKED: DWORD = 14;
+
+/// System description of a Win32 error code or HRESULT, without the trailing newline
+pub fn format_message(code: DWORD) -> Option<String> {
+    use winapi::winbase::{FORMAT_MESSAGE_FROM_SYSTEM, FORMAT_MESSAGE_IGNORE_INSERTS};
+
+    let mut buffer = [0u16; 512];
+
+    let len = unsafe {
+        kernel32::FormatMessageW(
+            FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS,
+            ::std::ptr::null(),
+            code,
+            0,
+            buffer.as_mut_ptr(),
+            buffer.len() as DWORD,
+            ::std::ptr::null_mut())
+    };
+
+    match len {
+        0 => None,
+        len => Some(utils::from_wide_slice(&buffer[..len as usize]).trim_end().to_string())
+    }
+}
+
 // https://github.com/retep998/wio-rs/blob/master/src/apc.rs
 pub fn enum_windows<T>(func: T) -> Win32Result<()>
     where T: FnMut(HWND) -> BOOL {
@@ -25,32 +67,229 @@ pub fn enum_windows<T>(func: T) -> Win32Result<()>
     match result {
         FALSE => match unsafe { kernel32::GetLastError() } {
             0 => Ok(()),
-            err => Err(err)
+            err => Err(Error::Win32(err))
         },
         _ => Ok(())
     }
 }
 
-pub fn get_window_text(hwnd: HWND) -> Win32Result<String> {
-    use ::std::vec::Vec;
+pub fn enum_display_monitors<T>(func: T) -> Win32Result<()>
+    where T: FnMut(HMONITOR) -> BOOL {
 
-    // Get length of text to allocate buffer
-    // The buffer must add space for a trailing null
-    let buffer_size = unsafe {
-        match user32::GetWindowTextLengthW(hwnd) {
-            0 => {
-                return Err(kernel32::GetLastError());
-            },
-            len => (len + 1) as usize
-        }
+    unsafe extern "system" fn helper<T: FnMut(HMONITOR) -> BOOL>(hmonitor: HMONITOR, _: HDC, _: LPRECT, lparam: LPARAM) -> BOOL {
+        let ppfn = lparam as *mut T;
+        let mut func = &mut *ppfn;
+
+        func(hmonitor)
+    }
+
+    let result = unsafe {
+        let ppfn = (&func) as *const T;
+        user32::EnumDisplayMonitors(0 as HDC, ::std::ptr::null(), Some(helper::<T>), ppfn as LPARAM)
     };
 
-    // Allocate buffer and get text
-    let mut buffer: Vec<u16> = Vec::with_capacity(buffer_size);
+    match result {
+        FALSE => Err(Error::last_win32()),
+        _ => Ok(())
+    }
+}
+
+pub fn get_monitor_info(hmonitor: HMONITOR) -> Win32Result<MONITORINFOEXW> {
+    let mut info: MONITORINFOEXW = unsafe { ::std::mem::zeroed() };
+    info.cbSize = ::std::mem::size_of::<MONITORINFOEXW>() as DWORD;
+
+    match unsafe { user32::GetMonitorInfoW(hmonitor, &mut info as *mut _ as LPMONITORINFO) } {
+        FALSE => Err(Error::last_win32()),
+        _ => Ok(info)
+    }
+}
+
+/// `GetDpiForMonitor` of shcore, which only exists on Windows 8.1 and later
+type GetDpiForMonitorFn = unsafe extern "system" fn(HMONITOR, c_int, *mut UINT, *mut UINT) -> HRESULT;
+
+const MDT_EFFECTIVE_DPI: c_int = 0;
+
+/// DPI a monitor is scaled to, or the system DPI where Windows does not report it per monitor
+pub fn get_monitor_dpi(hmonitor: HMONITOR) -> u32 {
+    unsafe {
+        let shcore = kernel32::LoadLibraryW(utils::to_wide_chars("shcore.dll").as_ptr());
+        let mut dpi = None;
+
+        if shcore != 0 as HMODULE {
+            let proc_addr = kernel32::GetProcAddress(shcore, b"GetDpiForMonitor\0".as_ptr() as LPCSTR);
+
+            if !proc_addr.is_null() {
+                let get_dpi_for_monitor: GetDpiForMonitorFn = ::std::mem::transmute(proc_addr);
+                let (mut dpi_x, mut dpi_y): (UINT, UINT) = (0, 0);
+
+                if get_dpi_for_monitor(hmonitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) >= 0 {
+                    dpi = Some(dpi_x);
+                }
+            }
+
+            kernel32::FreeLibrary(shcore);
+        }
+
+        dpi.unwrap_or_else(get_system_dpi)
+    }
+}
+
+fn get_system_dpi() -> u32 {
     unsafe {
-        buffer.set_len(buffer_size);
-        user32::GetWindowTextW(hwnd, buffer.as_mut_ptr(), buffer.len() as i32)
+        let hdc = user32::GetDC(0 as HWND);
+
+        if hdc == 0 as HDC {
+            return 96;
+        }
+
+        let dpi = gdi32::GetDeviceCaps(hdc, LOGPIXELSX);
+        user32::ReleaseDC(0 as HWND, hdc);
+
+        dpi as u32
+    }
+}
+
+pub fn get_cursor_pos() -> Win32Result<(i32, i32)> {
+    let mut point = POINT { x: 0, y: 0 };
+
+    match unsafe { user32::GetCursorPos(&mut point) } {
+        FALSE => Err(Error::last_win32()),
+        _ => Ok((point.x, point.y))
+    }
+}
+
+/// Visible frame of a window, without the invisible resize borders
+pub fn get_extended_frame_bounds(hwnd: HWND) -> Win32Result<Rect> {
+    let mut rect = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+
+    let result = unsafe {
+        DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_EXTENDED_FRAME_BOUNDS,
+            &mut rect as *mut _ as LPVOID,
+            ::std::mem::size_of::<RECT>() as DWORD)
+    };
+
+    match result {
+        0 => Ok(Rect::from_edges(rect.left, rect.top, rect.right, rect.bottom)),
+        hr => Err(Error::Win32(hr as DWORD))
+    }
+}
+
+/// Whether DWM hides a window that is otherwise visible
+pub fn is_cloaked(hwnd: HWND) -> Win32Result<bool> {
+    let mut cloaked: DWORD = 0;
+
+    let result = unsafe {
+        DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_CLOAKED,
+            &mut cloaked as *mut _ as LPVOID,
+            ::std::mem::size_of::<DWORD>() as DWORD)
     };
 
-    Ok(utils::from_wide_slice(&buffer))
+    match result {
+        0 => Ok(cloaked != 0),
+        hr => Err(Error::Win32(hr as DWORD))
+    }
+}
+
+pub fn get_class_name(hwnd: HWND) -> Win32Result<String> {
+    // Class names are limited to 256 characters
+    let mut buffer = [0u16; 257];
+
+    match unsafe { user32::GetClassNameW(hwnd, buffer.as_mut_ptr(), buffer.len() as i32) } {
+        0 => Err(Error::last_win32()),
+        _ => Ok(utils::from_wide_slice(&buffer))
+    }
+}
+
+/// Full path of the executable that owns a window
+pub fn get_process_path(hwnd: HWND) -> Win32Result<String> {
+    use winapi::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+
+    let mut pid: DWORD = 0;
+    unsafe { user32::GetWindowThreadProcessId(hwnd, &mut pid) };
+
+    unsafe {
+        let process = kernel32::OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
+        if process.is_null() {
+            return Err(Error::last_win32());
+        }
+
+        let mut buffer = [0u16; MAX_PATH];
+        let mut size = buffer.len() as DWORD;
+        let result = kernel32::QueryFullProcessImageNameW(process, 0, buffer.as_mut_ptr(), &mut size);
+        let err = kernel32::GetLastError();
+        kernel32::CloseHandle(process);
+
+        match result {
+            FALSE => Err(Error::Win32(err)),
+            _ => Ok(utils::from_wide_slice(&buffer[..size as usize]))
+        }
+    }
+}
+
+/// Title of a window, without waiting longer than `timeout` for it, see `titles::read_title`
+pub fn get_window_title(hwnd: HWND, timeout: Duration) -> Win32Result<WindowTitle> {
+    titles::read_title(&Win32Titles, hwnd, timeout)
+}
+
+/// Title calls of `get_window_title`
+///
+/// `GetWindowTextW` is not used because it waits for windows of other processes
+/// as long as they take to answer, which is forever for a hung one.
+struct Win32Titles;
+
+impl TitleApi for Win32Titles {
+    fn is_window(&self, hwnd: HWND) -> bool {
+        unsafe { user32::IsWindow(hwnd) != FALSE }
+    }
+
+    fn is_hung(&self, hwnd: HWND) -> bool {
+        unsafe { user32::IsHungAppWindow(hwnd) != FALSE }
+    }
+
+    fn request_text(&self, hwnd: HWND, timeout: Duration) -> TextReply {
+        use winapi::DWORD_PTR;
+        use winapi::winerror::ERROR_TIMEOUT;
+        use winapi::winuser::{SMTO_ABORTIFHUNG, SMTO_BLOCK, WM_GETTEXT, WM_GETTEXTLENGTH};
+
+        let timeout_ms = (timeout.as_secs() * 1000 + timeout.subsec_millis() as u64) as UINT;
+        let send = |msg: UINT, wparam: WPARAM, lparam: LPARAM| {
+            let mut result: DWORD_PTR = 0;
+
+            match unsafe { user32::SendMessageTimeoutW(hwnd, msg, wparam, lparam, SMTO_ABORTIFHUNG | SMTO_BLOCK, timeout_ms, &mut result) } {
+                // Windows found to be hung while waiting fail without an error code
+                0 => match unsafe { kernel32::GetLastError() } {
+                    0 | ERROR_TIMEOUT => Err(TextReply::TimedOut),
+                    code => Err(TextReply::Failed(Error::Win32(code)))
+                },
+                _ => Ok(result as usize)
+            }
+        };
+
+        let len = match send(WM_GETTEXTLENGTH, 0, 0) {
+            Ok(0) => return TextReply::Text(String::new()),
+            Ok(len) => len,
+            Err(reply) => return reply
+        };
+
+        // The length may be too large but never too small, leave space for the null
+        let mut buffer = vec![0u16; len + 1];
+
+        match send(WM_GETTEXT, buffer.len() as WPARAM, buffer.as_mut_ptr() as LPARAM) {
+            Ok(copied) => TextReply::Text(utils::from_wide_slice(&buffer[..::std::cmp::min(copied, buffer.len())])),
+            Err(reply) => reply
+        }
+    }
+
+    fn internal_text(&self, hwnd: HWND) -> String {
+        let mut buffer = [0u16; 512];
+
+        match unsafe { user32::InternalGetWindowText(hwnd, buffer.as_mut_ptr(), buffer.len() as i32) } {
+            len if len > 0 => utils::from_wide_slice(&buffer[..len as usize]),
+            _ => String::new()
+        }
+    }
 }
\ No newline at end of file
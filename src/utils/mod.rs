@@ -1,8 +1,21 @@
-use winapi;
+use std::env;
+use std::path::PathBuf;
+
+use error::Error;
 
 pub mod api_wrappers;
 
-pub type Win32Result<T> = Result<T, winapi::DWORD>;
+/// Result of calls into the Windows API
+pub type Win32Result<T> = Result<T, Error>;
+
+/// Directory holding the settings and saved state, `%APPDATA%\WinMan`
+pub fn config_dir() -> PathBuf {
+	let base = env::var_os("APPDATA")
+		.map(PathBuf::from)
+		.unwrap_or(PathBuf::from("."));
+
+	base.join("WinMan")
+}
 
 // https://gist.github.com/sunnyone/e660fe7f73e2becd4b2c
 pub fn from_wide_slice(buffer: &[u16]) -> String {
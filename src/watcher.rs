@@ -0,0 +1,211 @@
+use std::collections::HashSet;
+
+use winapi::windef::HWND;
+
+use backend::{WindowBackend, WindowEvent};
+use eligibility;
+use window_tracking::{Config, Window};
+
+/// Keeps slots in sync with the windows on the desktop
+///
+/// Destroyed windows are dropped from their slots right away instead of when a
+/// hotkey fails to focus them, and rules are applied to windows as they appear or
+/// change their title.
+pub struct WindowWatcher {
+    /// Windows a rule put into a slot, which are not matched again
+    assigned: HashSet<HWND>,
+}
+
+impl WindowWatcher {
+    pub fn new() -> Self {
+        WindowWatcher { assigned: HashSet::new() }
+    }
+
+    /// Applies rules to the windows that already exist
+    pub fn start<B: WindowBackend>(&mut self, backend: &B, config: &mut Config) {
+        for hwnd in eligibility::switchable_windows(backend) {
+            self.apply_rules(backend, config, hwnd);
+        }
+    }
+
+    /// Applies the rules of a newly activated profile or reloaded settings
+    ///
+    /// Windows already in one of the profile's slots stay where they are, all
+    /// others may be matched again.
+    pub fn restart<B: WindowBackend>(&mut self, backend: &B, config: &mut Config) {
+        self.assigned.clear();
+
+        for hwnd in eligibility::switchable_windows(backend) {
+            if config.slots_of(hwnd).is_empty() {
+                self.apply_rules(backend, config, hwnd);
+            }
+        }
+    }
+
+    pub fn handle<B: WindowBackend>(&mut self, backend: &B, config: &mut Config, event: WindowEvent) {
+        match event {
+            WindowEvent::Created(hwnd) | WindowEvent::TitleChanged(hwnd) => {
+                if eligibility::is_switchable(backend, hwnd) {
+                    self.apply_rules(backend, config, hwnd);
+                }
+            },
+
+            WindowEvent::Destroyed(hwnd) => {
+                self.assigned.remove(&hwnd);
+                config.forget_window(hwnd);
+            },
+
+            WindowEvent::Foreground(hwnd) => config.touch_window(hwnd),
+
+            // Hidden windows keep their slots, like minimized ones
+            WindowEvent::Hidden(_) => {},
+        }
+    }
+
+    fn apply_rules<B: WindowBackend>(&mut self, backend: &B, config: &mut Config, hwnd: HWND) {
+        if self.assigned.contains(&hwnd) {
+            return;
+        }
+
+        if let Ok(identity) = backend.window_identity(hwnd) {
+            if let Some(vk) = config.settings().rule_slot(&identity) {
+                info!(slot = vk, title = identity.title.text().unwrap_or(""); "Rule tracking window {:?}", hwnd);
+
+                self.assigned.insert(hwnd);
+                config.track_window(vk, Window::titled(hwnd, identity.title));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::WindowBackend;
+    use backend::fake::{self, FakeBackend};
+    use constants::{VK_1, VK_3, VK_4};
+    use ini::Ini;
+    use layout::Rect;
+    use settings::Settings;
+    use window_tracking::{Config, Window};
+    use super::WindowWatcher;
+
+    fn config() -> Config {
+        let ini = Ini::parse("[slot 3]\nrule = title ~ Jira").unwrap();
+        Config::with_settings(Settings::from_ini(&ini).unwrap())
+    }
+
+    fn slot(config: &mut Config, vk: u32) -> Vec<usize> {
+        let mut hwnds: Vec<_> = config.get_windows(vk)
+            .map(|window_set| window_set.iter().map(|w| w.hwnd() as usize).collect())
+            .unwrap_or_default();
+
+        hwnds.sort();
+        hwnds
+    }
+
+    /// Feeds all pending events to the watcher
+    macro_rules! pump {
+        ($rx:expr, $watcher:expr, $backend:expr, $config:expr) => {
+            while let Ok(event) = $rx.try_recv() {
+                $watcher.handle(&$backend, &mut $config, event);
+            }
+        }
+    }
+
+    #[test]
+    fn rules_at_start() {
+        let mut backend = FakeBackend::new(vec![fake::monitor(0, 0, 1920, 1080, true)]);
+        let mut config = config();
+        let mut watcher = WindowWatcher::new();
+
+        backend.add_window(1, "PROJ-1 - Jira", Rect::default());
+        backend.add_window(2, "Inbox", Rect::default());
+        watcher.start(&backend, &mut config);
+
+        assert_eq!(vec![1], slot(&mut config, VK_3));
+    }
+
+    #[test]
+    fn rules_after_profile_switch() {
+        let mut backend = FakeBackend::new(vec![fake::monitor(0, 0, 1920, 1080, true)]);
+        let ini = Ini::parse("[slot 3]\nrule = title ~ Jira\n[slot 1 @ ops]\nrule = title ~ Grafana").unwrap();
+        let mut config = Config::with_settings(Settings::from_ini(&ini).unwrap());
+        let mut watcher = WindowWatcher::new();
+
+        backend.add_window(1, "PROJ-1 - Jira", Rect::default());
+        backend.add_window(2, "Grafana", Rect::default());
+        watcher.start(&backend, &mut config);
+        assert_eq!(vec![1], slot(&mut config, VK_3));
+
+        assert!(config.set_profile("ops"));
+        watcher.restart(&backend, &mut config);
+        assert_eq!(vec![2], slot(&mut config, VK_1));
+        assert_eq!(vec![1], slot(&mut config, VK_3));
+
+        config.set_profile("default");
+        watcher.restart(&backend, &mut config);
+        assert_eq!(vec![1], slot(&mut config, VK_3));
+        assert!(slot(&mut config, VK_1).is_empty());
+    }
+
+    #[test]
+    fn rules_after_reload() {
+        let mut backend = FakeBackend::new(vec![fake::monitor(0, 0, 1920, 1080, true)]);
+        let mut config = config();
+        let mut watcher = WindowWatcher::new();
+
+        backend.add_window(1, "PROJ-1 - Jira", Rect::default());
+        let grabbed = backend.add_window(2, "Grafana", Rect::default());
+        watcher.start(&backend, &mut config);
+        config.track_window(VK_1, Window::new(grabbed, "Grafana".to_string()));
+
+        // Windows already in a slot are not matched by the new rules
+        backend.add_window(3, "Grafana - ops", Rect::default());
+        let ini = Ini::parse("[slot 3]\nrule = title ~ Jira\n[slot 4]\nrule = title ~ Grafana").unwrap();
+        config.set_settings(Settings::from_ini(&ini).unwrap());
+        watcher.restart(&backend, &mut config);
+
+        assert_eq!(vec![1], slot(&mut config, VK_3));
+        assert_eq!(vec![2], slot(&mut config, VK_1));
+        assert_eq!(vec![3], slot(&mut config, VK_4));
+    }
+
+    #[test]
+    fn rules_on_events() {
+        let mut backend = FakeBackend::new(vec![fake::monitor(0, 0, 1920, 1080, true)]);
+        let mut config = config();
+        let mut watcher = WindowWatcher::new();
+        let rx = backend.watch_windows().unwrap();
+
+        backend.add_window(1, "PROJ-1 - Jira", Rect::default());
+        let tab = backend.add_window(2, "New Tab", Rect::default());
+        pump!(rx, watcher, backend, config);
+        assert_eq!(vec![1], slot(&mut config, VK_3));
+
+        // Matched once the title changes
+        backend.set_title(tab, "PROJ-2 - Jira");
+        backend.set_title(tab, "PROJ-2 - Jira - Edited");
+        pump!(rx, watcher, backend, config);
+        assert_eq!(vec![1, 2], slot(&mut config, VK_3));
+    }
+
+    #[test]
+    fn prunes_destroyed_windows() {
+        let mut backend = FakeBackend::new(vec![fake::monitor(0, 0, 1920, 1080, true)]);
+        let mut config = config();
+        let mut watcher = WindowWatcher::new();
+        let rx = backend.watch_windows().unwrap();
+
+        let jira = backend.add_window(1, "PROJ-1 - Jira", Rect::default());
+        let editor = backend.add_window(2, "Editor", Rect::default());
+        pump!(rx, watcher, backend, config);
+        config.track_window(VK_1, Window::new(editor, "Editor".to_string()));
+
+        backend.close_window(jira);
+        backend.close_window(editor);
+        pump!(rx, watcher, backend, config);
+
+        assert!(slot(&mut config, VK_3).is_empty());
+        assert!(slot(&mut config, VK_1).is_empty());
+    }
+}
@@ -1,10 +1,19 @@
+use std::cmp;
 use std::collections::{HashMap, VecDeque};
+use std::collections::vec_deque;
+use std::mem;
+use std::str::FromStr;
+use std::time::Duration;
 
-use kernel32;
 use user32;
 use winapi::minwindef::*;
 use winapi::windef::*;
 
+use backend::DesktopId;
+use error::Error;
+use layout::tiling::TilingOptions;
+use settings::Settings;
+use titles::{self, WindowTitle};
 use utils;
 use utils::Win32Result;
 
@@ -22,6 +31,14 @@ impl Window {
 		}
 	}
 
+	/// A window whose title is left unknown if it is `Unavailable`
+	pub fn titled(hwnd: HWND, title: WindowTitle) -> Self {
+		Window {
+			hwnd: hwnd,
+			title: title.text().map(String::from),
+		}
+	}
+
 	pub fn hwnd(&self) -> HWND {
 		self.hwnd
 	}
@@ -34,72 +51,394 @@ impl Window {
 	}
 }
 
+/// How focusing a slot picks among its windows
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CyclePolicy {
+	/// Focus the slot's most recently used window, and only move on to the
+	/// next one when a window of the slot is already focused
+	MostRecentFirst,
+	/// Always move on to the next window
+	Cycle,
+	/// Move on to the next window only when the slot's current window is
+	/// focused, otherwise focus the current window again
+	CycleIfFocused,
+}
+
+impl Default for CyclePolicy {
+	fn default() -> Self {
+		CyclePolicy::MostRecentFirst
+	}
+}
+
+impl FromStr for CyclePolicy {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, ()> {
+		match s {
+			"most_recent" => Ok(CyclePolicy::MostRecentFirst),
+			"cycle" => Ok(CyclePolicy::Cycle),
+			"if_focused" => Ok(CyclePolicy::CycleIfFocused),
+			_ => Err(())
+		}
+	}
+}
+
+/// Whether a slot has its own windows on each virtual desktop
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SlotScope {
+	/// The same windows on every desktop
+	Global,
+	/// Windows grabbed on a desktop, falling back to the global ones when there are none
+	Desktop,
+}
+
+impl Default for SlotScope {
+	fn default() -> Self {
+		SlotScope::Global
+	}
+}
+
+impl FromStr for SlotScope {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, ()> {
+		match s {
+			"global" => Ok(SlotScope::Global),
+			"desktop" => Ok(SlotScope::Desktop),
+			_ => Err(())
+		}
+	}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+	Forward,
+	Backward,
+}
+
+/// Windows of a slot, newest first
 pub struct WindowSet {
-	windows: VecDeque<Window>
+	windows: VecDeque<Window>,
+	/// Index of the window the slot focused last
+	current: usize,
+	/// Most recently focused window of the set, however it was focused
+	last_used: Option<HWND>,
 }
 
 impl WindowSet {
 	pub fn new() -> Self {
 		WindowSet {
-			windows: VecDeque::new()
+			windows: VecDeque::new(),
+			current: 0,
+			last_used: None,
 		}
 	}
 
+	/// Adds a window at the front and makes it the current one
 	pub fn add(&mut self, window: Window) {
 		self.remove(&window);
+		self.last_used = Some(window.hwnd);
 		self.windows.push_front(window);
+		self.current = 0;
 	}
 	
 	pub fn remove(&mut self, window: &Window) -> Option<Window> {
-		let index = self.windows
-		                .iter()
-		                .position(|w| w.hwnd == window.hwnd);
+		let index = self.position(window.hwnd);
 
         match index {
         	Some(index) => {
+        		if index < self.current || self.current + 1 == self.windows.len() {
+        			self.current = self.current.saturating_sub(1);
+        		}
+
+        		if self.last_used == Some(window.hwnd) {
+        			self.last_used = None;
+        		}
+
         		self.windows.remove(index)
         	},
         	None => None
         }
 	}
 
-	pub fn cycle(&mut self) -> Option<Window> {
-		if let Some(back) = self.windows.pop_back() {
-			self.windows.push_front(back);
+	pub fn len(&self) -> usize {
+		self.windows.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.windows.is_empty()
+	}
+
+	pub fn iter(&self) -> vec_deque::Iter<'_, Window> {
+		self.windows.iter()
+	}
+
+	pub fn contains(&self, hwnd: HWND) -> bool {
+		self.position(hwnd).is_some()
+	}
+
+	/// Records that one of the set's windows was focused
+	pub fn touch(&mut self, hwnd: HWND) {
+		if self.contains(hwnd) {
+			self.last_used = Some(hwnd);
 		}
+	}
 
-		match self.windows.front() {
-			Some(window) => Some(window.clone()),
-			None => None
+	/// Picks the window to focus and makes it the current one
+	///
+	/// `focused` is the foreground window, which decides whether the policy moves
+	/// on to the next window.
+	pub fn next(&mut self, policy: CyclePolicy, direction: Direction, focused: Option<HWND>) -> Option<Window> {
+		if self.windows.is_empty() {
+			return None;
+		}
+
+		let focused_index = focused.and_then(|hwnd| self.position(hwnd));
+
+		self.current = match policy {
+			CyclePolicy::Cycle => self.step(self.current, direction),
+
+			CyclePolicy::CycleIfFocused => match focused_index {
+				Some(index) if index == self.current => self.step(index, direction),
+				_ => self.current,
+			},
+
+			CyclePolicy::MostRecentFirst => match focused_index {
+				Some(index) => self.step(index, direction),
+				None => self.last_used
+					.and_then(|hwnd| self.position(hwnd))
+					.unwrap_or(self.current),
+			},
+		};
+
+		let window = self.windows[self.current].clone();
+		self.last_used = Some(window.hwnd);
+
+		Some(window)
+	}
+
+	/// Moves a window `offset` places towards the back, or the front if negative
+	///
+	/// Returns false if the window is not in the set.
+	pub fn shift(&mut self, hwnd: HWND, offset: isize) -> bool {
+		let index = match self.position(hwnd) {
+			Some(index) => index,
+			None => return false
+		};
+
+		let target = cmp::max(0, cmp::min(index as isize + offset, self.windows.len() as isize - 1)) as usize;
+		let current = self.windows[self.current].hwnd;
+
+		let window = self.windows.remove(index).unwrap();
+		self.windows.insert(target, window);
+		self.current = self.position(current).unwrap_or(0);
+
+		true
+	}
+
+	fn position(&self, hwnd: HWND) -> Option<usize> {
+		self.windows.iter().position(|w| w.hwnd == hwnd)
+	}
+
+	fn step(&self, index: usize, direction: Direction) -> usize {
+		let len = self.windows.len();
+
+		match direction {
+			Direction::Forward => (index + 1) % len,
+			Direction::Backward => (index + len - 1) % len,
 		}
 	}
 }
 
+type Slots = HashMap<(Option<DesktopId>, UINT), WindowSet>;
+
+/// Slot contents, keyed by desktop for desktop scoped slots and `None` for global ones
+///
+/// Each profile has its own slot contents, the ones of inactive profiles are
+/// put aside until the profile is activated again.
 pub struct Config {
-    windows: HashMap<UINT, WindowSet>
+    windows: Slots,
+    /// Slot contents of the inactive profiles by name
+    profiles: HashMap<String, Slots>,
+    /// Desktop shown right now, if known
+    desktop: Option<DesktopId>,
+    settings: Settings,
 }
 
 impl Config {
 	pub fn new() -> Self {
+		Config::with_settings(Settings::new())
+	}
+
+	pub fn with_settings(settings: Settings) -> Self {
 		Config {
-			windows: HashMap::new()
+			windows: HashMap::new(),
+			profiles: HashMap::new(),
+			desktop: None,
+			settings: settings,
+		}
+	}
+
+	pub fn settings(&self) -> &Settings {
+		&self.settings
+	}
+
+	/// Replaces the settings, windows stay in their slots
+	///
+	/// The active profile stays active, or the default profile is activated if
+	/// the new settings do not have it.
+	pub fn set_settings(&mut self, mut settings: Settings) {
+		let profile = self.settings.profile().to_string();
+
+		if !settings.set_profile(&profile) {
+			self.swap_profile(&profile, settings.profile());
 		}
+
+		self.settings = settings;
+	}
+
+	/// Activates a profile along with its slot contents
+	///
+	/// Returns false if there is no profile named `name`.
+	pub fn set_profile(&mut self, name: &str) -> bool {
+		let previous = self.settings.profile().to_string();
+
+		if !self.settings.set_profile(name) {
+			return false;
+		}
+
+		if previous != name {
+			self.swap_profile(&previous, name);
+		}
+
+		true
 	}
 
+	fn swap_profile(&mut self, previous: &str, next: &str) {
+		let windows = self.profiles.remove(next).unwrap_or_default();
+		let previous_windows = mem::replace(&mut self.windows, windows);
+		self.profiles.insert(previous.to_string(), previous_windows);
+	}
+
+	pub fn tiling_options(&self) -> &TilingOptions {
+		&self.settings.tiling
+	}
+
+	pub fn desktop(&self) -> Option<DesktopId> {
+		self.desktop
+	}
+
+	/// Sets the desktop whose windows desktop scoped slots use
+	///
+	/// While it is `None` all slots use their global windows.
+	pub fn set_desktop(&mut self, desktop: Option<DesktopId>) {
+		self.desktop = desktop;
+	}
+
+	/// Puts a window into slot `vk` on the current desktop, or the global slot
 	pub fn track_window(&mut self, vk: UINT, window: Window) {
+		let key = self.key(vk);
 		let mut window_set = self.windows
-		                         .entry(vk)
+		                         .entry(key)
 		                         .or_insert(WindowSet::new());
 
 		window_set.add(window);		
 	}
 
+	/// Windows of slot `vk` on the current desktop, or the global ones if it has none there
 	pub fn get_windows(&mut self, vk: UINT) -> Option<&mut WindowSet> {
-		self.windows.get_mut(&vk)
+		let key = self.shown_key(vk);
+		self.windows.get_mut(&key)
 	}
 
+	/// Empties the windows `get_windows` returns for slot `vk`
 	pub fn clear_windows(&mut self, vk: UINT) {
-		self.windows.remove(&vk);
+		let key = self.shown_key(vk);
+		self.windows.remove(&key);
+	}
+
+	/// Slots containing `hwnd`, in hotkey order
+	pub fn slots_of(&self, hwnd: HWND) -> Vec<UINT> {
+		let mut slots: Vec<_> = self.windows
+		                            .iter()
+		                            .filter(|&(_, window_set)| window_set.contains(hwnd))
+		                            .map(|(&(_, vk), _)| vk)
+		                            .collect();
+
+		slots.sort();
+		slots.dedup();
+		slots
+	}
+
+	/// Takes a window out of its slots and puts it into slot `vk`
+	///
+	/// Returns false if the window is not in any slot.
+	pub fn move_window(&mut self, hwnd: HWND, vk: UINT) -> bool {
+		let window = self.windows
+		                 .values()
+		                 .find_map(|window_set| window_set.iter().find(|w| w.hwnd == hwnd))
+		                 .cloned();
+
+		match window {
+			Some(window) => {
+				self.remove_window(hwnd);
+				self.track_window(vk, window);
+				true
+			},
+			None => false
+		}
+	}
+
+	/// Reorders a window within every slot containing it, see `WindowSet::shift`
+	pub fn shift_window(&mut self, hwnd: HWND, offset: isize) {
+		for window_set in self.windows.values_mut() {
+			window_set.shift(hwnd, offset);
+		}
+	}
+
+	/// Records a focus change for the slots containing `hwnd`
+	pub fn touch_window(&mut self, hwnd: HWND) {
+		for window_set in self.windows.values_mut() {
+			window_set.touch(hwnd);
+		}
+	}
+
+	/// Drops a window from every slot of the active profile
+	///
+	/// Other profiles keep it, see `forget_window` for windows that are gone.
+	pub fn remove_window(&mut self, hwnd: HWND) {
+		let window = Window { hwnd: hwnd, title: None };
+
+		for window_set in self.windows.values_mut() {
+			window_set.remove(&window);
+		}
+	}
+
+	/// Drops a window from every slot of every profile, after it was destroyed
+	pub fn forget_window(&mut self, hwnd: HWND) {
+		let window = Window { hwnd: hwnd, title: None };
+		let inactive = self.profiles.values_mut().flat_map(|windows| windows.values_mut());
+
+		for window_set in self.windows.values_mut().chain(inactive) {
+			window_set.remove(&window);
+		}
+	}
+
+	fn key(&self, vk: UINT) -> (Option<DesktopId>, UINT) {
+		match self.settings.slot_scope(vk) {
+			SlotScope::Desktop => (self.desktop, vk),
+			SlotScope::Global => (None, vk),
+		}
+	}
+
+	/// Like `key`, but the global slot while the desktop's own one is empty
+	fn shown_key(&self, vk: UINT) -> (Option<DesktopId>, UINT) {
+		let key = self.key(vk);
+
+		match self.windows.get(&key) {
+			Some(window_set) if !window_set.is_empty() => key,
+			_ => (None, vk)
+		}
 	}
 }
 
@@ -108,14 +447,15 @@ pub fn get_foreground_window() -> Win32Result<Window> {
 		let hwnd = unsafe { user32::GetForegroundWindow() };
 
 		if hwnd == 0 as HWND {
-			return Err(unsafe { kernel32::GetLastError() });
+			return Err(Error::last_win32());
 		}
 
 		hwnd
 	};
 
-	let title = utils::api_wrappers::get_window_text(hwnd).unwrap_or("".to_string());
-	let window = Window::new(hwnd, title);
+	let title = utils::api_wrappers::get_window_title(hwnd, Duration::from_millis(titles::DEFAULT_TIMEOUT_MS))
+		.unwrap_or(WindowTitle::Unavailable);
+	let window = Window::titled(hwnd, title);
 
 	Ok(window)
 }
@@ -137,9 +477,193 @@ pub fn set_foreground_window(hwnd: HWND) -> Win32Result<()> {
 
 		user32::ShowWindow(hwnd, sw);
 		if user32::SetForegroundWindow(hwnd) == 0 {
-			return Err(kernel32::GetLastError());
+			return Err(Error::last_win32());
 		}
 	}
 
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use winapi::windef::HWND;
+
+	use constants::{VK_1, VK_2, VK_3};
+	use ini::Ini;
+	use settings::Settings;
+	use super::{Config, CyclePolicy, Direction, Window, WindowSet};
+
+	fn hwnd(id: usize) -> HWND {
+		id as HWND
+	}
+
+	/// Set with windows 1, 2, 3 in slot order, after grabbing 3, 2, 1
+	fn window_set() -> WindowSet {
+		let mut window_set = WindowSet::new();
+		for id in (1..4).rev() {
+			window_set.add(Window::new(hwnd(id), id.to_string()));
+		}
+		window_set
+	}
+
+	fn next(window_set: &mut WindowSet, policy: CyclePolicy, direction: Direction, focused: usize) -> usize {
+		window_set.next(policy, direction, Some(hwnd(focused))).unwrap().hwnd() as usize
+	}
+
+	#[test]
+	fn cycles_in_order() {
+		let mut window_set = window_set();
+
+		let forward: Vec<_> = (0..4).map(|_| next(&mut window_set, CyclePolicy::Cycle, Direction::Forward, 0)).collect();
+		assert_eq!(vec![2, 3, 1, 2], forward);
+
+		let backward: Vec<_> = (0..3).map(|_| next(&mut window_set, CyclePolicy::Cycle, Direction::Backward, 0)).collect();
+		assert_eq!(vec![1, 3, 2], backward);
+	}
+
+	#[test]
+	fn most_recent_first() {
+		let mut window_set = window_set();
+
+		// Window 1 was grabbed last, nothing of the slot is focused
+		assert_eq!(1, next(&mut window_set, CyclePolicy::MostRecentFirst, Direction::Forward, 9));
+
+		// Moves on from the focused window
+		assert_eq!(2, next(&mut window_set, CyclePolicy::MostRecentFirst, Direction::Forward, 1));
+
+		// Window 3 was focused some other way, then another application
+		window_set.touch(hwnd(3));
+		assert_eq!(3, next(&mut window_set, CyclePolicy::MostRecentFirst, Direction::Forward, 9));
+		assert_eq!(2, next(&mut window_set, CyclePolicy::MostRecentFirst, Direction::Backward, 3));
+	}
+
+	#[test]
+	fn cycle_if_focused() {
+		let mut window_set = window_set();
+
+		assert_eq!(1, next(&mut window_set, CyclePolicy::CycleIfFocused, Direction::Forward, 9));
+		assert_eq!(2, next(&mut window_set, CyclePolicy::CycleIfFocused, Direction::Forward, 1));
+
+		// Another window of the slot is focused, return to the current one
+		assert_eq!(2, next(&mut window_set, CyclePolicy::CycleIfFocused, Direction::Forward, 3));
+	}
+
+	fn order(window_set: &WindowSet) -> Vec<usize> {
+		window_set.iter().map(|w| w.hwnd() as usize).collect()
+	}
+
+	#[test]
+	fn shift() {
+		let mut window_set = window_set();
+		assert_eq!(2, next(&mut window_set, CyclePolicy::Cycle, Direction::Forward, 0));
+
+		assert!(window_set.shift(hwnd(1), 1));
+		assert_eq!(vec![2, 1, 3], order(&window_set));
+
+		assert!(window_set.shift(hwnd(3), -5));
+		assert_eq!(vec![3, 2, 1], order(&window_set));
+
+		assert!(window_set.shift(hwnd(3), 5));
+		assert_eq!(vec![2, 1, 3], order(&window_set));
+
+		assert!(!window_set.shift(hwnd(9), 1));
+
+		// Window 2 is still the current one
+		assert_eq!(2, next(&mut window_set, CyclePolicy::CycleIfFocused, Direction::Forward, 0));
+	}
+
+	#[test]
+	fn move_between_slots() {
+		let mut config = Config::new();
+		config.track_window(VK_1, Window::new(hwnd(1), "a".to_string()));
+		config.track_window(VK_1, Window::new(hwnd(2), "b".to_string()));
+		config.track_window(VK_2, Window::new(hwnd(2), "b".to_string()));
+
+		assert_eq!(vec![VK_1, VK_2], config.slots_of(hwnd(2)));
+
+		assert!(config.move_window(hwnd(2), VK_3));
+		assert_eq!(vec![VK_3], config.slots_of(hwnd(2)));
+		assert_eq!(Some("b"), config.get_windows(VK_3).unwrap().iter().next().unwrap().title());
+		assert_eq!(vec![1], order(config.get_windows(VK_1).unwrap()));
+
+		assert!(!config.move_window(hwnd(9), VK_3));
+		assert!(config.slots_of(hwnd(9)).is_empty());
+	}
+
+	#[test]
+	fn remove_from_slots() {
+		let mut config = Config::new();
+		config.track_window(VK_1, Window::new(hwnd(1), "a".to_string()));
+		config.track_window(VK_2, Window::new(hwnd(1), "a".to_string()));
+		config.track_window(VK_2, Window::new(hwnd(2), "b".to_string()));
+
+		config.remove_window(hwnd(1));
+
+		assert!(config.get_windows(VK_1).unwrap().is_empty());
+		assert_eq!(vec![2], order(config.get_windows(VK_2).unwrap()));
+	}
+
+	#[test]
+	fn profiles() {
+		let ini = Ini::parse("[slot 1 @ ops]\n[slot 1 @ meetings]").unwrap();
+		let mut config = Config::with_settings(Settings::from_ini(&ini).unwrap());
+		config.track_window(VK_1, Window::new(hwnd(1), "a".to_string()));
+		config.track_window(VK_2, Window::new(hwnd(2), "b".to_string()));
+
+		assert!(config.set_profile("ops"));
+		assert!(config.get_windows(VK_1).is_none());
+		config.track_window(VK_1, Window::new(hwnd(3), "c".to_string()));
+
+		assert!(!config.set_profile("coding"));
+		assert_eq!(vec![3], order(config.get_windows(VK_1).unwrap()));
+
+		// Destroyed windows are dropped from inactive profiles too
+		config.forget_window(hwnd(2));
+
+		assert!(config.set_profile("default"));
+		assert_eq!(vec![1], order(config.get_windows(VK_1).unwrap()));
+		assert!(config.get_windows(VK_2).unwrap().is_empty());
+
+		// Reloading without the active profile falls back to the default one
+		assert!(config.set_profile("ops"));
+		config.set_settings(Settings::from_ini(&Ini::parse("[slot 1 @ meetings]").unwrap()).unwrap());
+		assert_eq!("default", config.settings().profile());
+		assert_eq!(vec![1], order(config.get_windows(VK_1).unwrap()));
+	}
+
+	#[test]
+	fn release_in_one_profile() {
+		let ini = Ini::parse("[slot 1 @ ops]\n[slot 1 @ meetings]").unwrap();
+		let mut config = Config::with_settings(Settings::from_ini(&ini).unwrap());
+		config.track_window(VK_2, Window::new(hwnd(1), "a".to_string()));
+
+		assert!(config.set_profile("ops"));
+		config.track_window(VK_2, Window::new(hwnd(1), "a".to_string()));
+		config.track_window(VK_1, Window::new(hwnd(1), "a".to_string()));
+
+		// Released and moved windows only leave the slots of the active profile
+		config.remove_window(hwnd(1));
+		assert!(config.slots_of(hwnd(1)).is_empty());
+		config.track_window(VK_1, Window::new(hwnd(2), "b".to_string()));
+		assert!(config.move_window(hwnd(2), VK_3));
+
+		assert!(config.set_profile("default"));
+		assert_eq!(vec![VK_2], config.slots_of(hwnd(1)));
+		assert!(config.slots_of(hwnd(2)).is_empty());
+	}
+
+	#[test]
+	fn remove_keeps_current() {
+		let mut window_set = window_set();
+		assert_eq!(3, next(&mut window_set, CyclePolicy::Cycle, Direction::Backward, 0));
+
+		window_set.remove(&Window::new(hwnd(1), String::new()));
+		assert_eq!(3, next(&mut window_set, CyclePolicy::CycleIfFocused, Direction::Forward, 0));
+
+		window_set.remove(&Window::new(hwnd(3), String::new()));
+		assert_eq!(2, next(&mut window_set, CyclePolicy::CycleIfFocused, Direction::Forward, 0));
+
+		window_set.remove(&Window::new(hwnd(2), String::new()));
+		assert!(window_set.next(CyclePolicy::Cycle, Direction::Forward, None).is_none());
+	}
+}
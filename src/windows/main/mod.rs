@@ -1,38 +1,80 @@
+use std::time::Instant;
+
 use winapi::*;
-use kernel32;
 use user32;
 use spmc;
 
-use constants::*;
+use commands::Command;
+use constants::MOD_NOREPEAT;
+use error::Error;
+use hotkeys::{self, Action, Binding, Chord, HotkeyReport};
+use layout::tiling::Tile;
+use json::Json;
+use leader::{Leader, Step};
+use native_host::TabList;
 use utils;
 use utils::Win32Result;
-use windows::ManagedWindow2;
+use window_tracking::Direction;
+use windows::{self, ManagedWindow2};
 
 const CLASS_NAME: &'static str = "WinmanMainWindow";
 
-const HK_QUIT: i32 = 1;
-const HK_POPUP: i32 = 2;
-const HK_GRAB: i32 = 3;
-const HK_SWITCH: i32 = 4;
-const HK_CLEAR: i32 = 5;
+const TIMER_POLL: UINT_PTR = 1;
+const TIMER_POLL_INTERVAL: UINT = 250;
+const TIMER_LEADER: UINT_PTR = 2;
+const TIMER_CONFIG: UINT_PTR = 3;
+const TIMER_CONFIG_INTERVAL: UINT = 1000;
+
+/// Sent by the tray icon, see `backend::Win32Tray`
+pub const WM_TRAY: UINT = 0x8001; // WM_APP + 1
+
+// `WM_COPYDATA` kinds, see `send_command` and `send_tabs`
+const COPYDATA_COMMAND: ULONG_PTR = 1;
+const COPYDATA_TABS: ULONG_PTR = 2;
 
-const MOD_QUIT: u32 = MOD_CONTROL | MOD_ALT;
-const MOD_POPUP: u32 = MOD_NOREPEAT | MOD_ALT;
-const MOD_GRAB: u32 = MOD_NOREPEAT| MOD_ALT | MOD_SHIFT;
-const MOD_SWITCH: u32 = MOD_NOREPEAT | MOD_ALT;
-const MOD_CLEAR: u32 = MOD_NOREPEAT | MOD_CONTROL | MOD_ALT | MOD_SHIFT;
+// Plain keys are registered as `HK_LEADER_KEY + vk` while a key sequence is typed
+const HK_LEADER_KEY: i32 = 0x1000;
 
 pub enum AppMsg {
     ShowPopup,
     GrabWindow(u32),
-    FocusWindow(u32),
+    FocusWindow(u32, Direction),
     ClearWindow(u32),
+    TileWindow(Tile),
+    /// Remove the foreground window from its slots
+    ReleaseWindow,
+    /// Move the foreground window within its slots, negative is towards the front
+    ShiftWindow(isize),
+    /// Show the virtual desktop with this number, counting from 1
+    SwitchDesktop(usize),
+    /// Activate the profile after the active one
+    NextProfile,
+    /// Run a command from the palette or from another instance
+    RunCommand(Command),
+    /// A native messaging host reported the tabs of its browser, the host
+    /// window is passed as a number because `HWND` can not be sent
+    Tabs(usize, TabList),
+    /// Make the log more verbose, wrapping around to the default
+    CycleLogLevel,
+    /// The tray icon was right-clicked
+    TrayMenu,
+    /// Explorer restarted and the tray icon has to be added again
+    TaskbarCreated,
+    /// Sent periodically while polling is enabled
+    Poll,
+    /// Sent every second to look for changes to `winman.ini`
+    CheckConfig,
 }
 
 pub struct AppWindow {
     pub hwnd: HWND,
     tx: spmc::Sender<AppMsg>,
     rx: spmc::Receiver<AppMsg>,
+    bindings: Vec<Binding>,
+    hotkeys: HotkeyReport,
+    paused: bool,
+    leader: Leader,
+    taskbar_created: UINT,
 }
 
 impl AppWindow {
@@ -46,13 +88,14 @@ impl AppWindow {
 
         unsafe {
             match user32::RegisterClassExW(&window_class) {
-                0 => Err(kernel32::GetLastError()),
+                0 => Err(Error::last_win32()),
                 _ => Ok(())
             }
         }
     }
 
-    pub fn new() -> Win32Result<ManagedWindow2<Self>> {
+    /// Creates the hidden main window and registers `bindings` as its hotkeys
+    pub fn new(bindings: &[Binding], leader: Leader) -> Win32Result<ManagedWindow2<Self>> {
         let class_name = utils::to_wide_chars(CLASS_NAME);
 
         let hwnd = unsafe {
@@ -71,52 +114,240 @@ impl AppWindow {
                 0 as LPVOID);
 
             if hwnd == 0 as HWND {
-                return Err(kernel32::GetLastError());
+                return Err(Error::last_win32());
             }
 
             hwnd
         };
 
-        register_hotkeys(hwnd);
+        let taskbar_created = unsafe {
+            user32::RegisterWindowMessageW(utils::to_wide_chars("TaskbarCreated").as_ptr())
+        };
 
         let (tx, rx) = spmc::channel();
-        let app = AppWindow {
+        let mut app = AppWindow {
             hwnd: hwnd,
             tx: tx,
             rx: rx,
+            bindings: bindings.to_vec(),
+            hotkeys: HotkeyReport::default(),
+            paused: false,
+            leader: leader,
+            taskbar_created: taskbar_created,
+        };
+        app.register_hotkeys();
+
+        unsafe { user32::SetTimer(hwnd, TIMER_CONFIG, TIMER_CONFIG_INTERVAL, None) };
+
+        ManagedWindow2::new(hwnd, Box::new(app))
+    }
+
+    /// Asks the main window of a running WinMan to run a command like `profile ops`
+    ///
+    /// Returns false if WinMan is not running.
+    pub fn send_command(command: &str) -> Result<bool, Error> {
+        let hwnd = match AppWindow::find() {
+            Some(hwnd) => hwnd,
+            None => return Ok(false)
         };
 
-        Ok(ManagedWindow2::new(hwnd, Box::new(app)).unwrap())
+        let text: Vec<u16> = command.encode_utf16().collect();
+        let bytes = unsafe { ::std::slice::from_raw_parts(text.as_ptr() as *const u8, text.len() * 2) };
+
+        match windows::send_copy_data(hwnd, 0, COPYDATA_COMMAND, bytes) {
+            0 => Err(Error::Ipc(format!("WinMan did not accept the command '{}'", command))),
+            _ => Ok(true)
+        }
+    }
+
+    /// Hands the tabs of a browser to a running WinMan, see `native_host`
+    ///
+    /// `host` is the window WinMan sends tabs to activate to. Returns false if
+    /// WinMan is not running.
+    pub fn send_tabs(host: HWND, tabs: &TabList) -> Result<bool, Error> {
+        let hwnd = match AppWindow::find() {
+            Some(hwnd) => hwnd,
+            None => return Ok(false)
+        };
+
+        let json = tabs.to_json().to_string();
+
+        match windows::send_copy_data(hwnd, host as WPARAM, COPYDATA_TABS, json.as_bytes()) {
+            0 => Err(Error::Ipc("WinMan did not accept the tab list".to_string())),
+            _ => Ok(true)
+        }
+    }
+
+    /// Main window of the WinMan that is running, if any
+    fn find() -> Option<HWND> {
+        let class_name = utils::to_wide_chars(CLASS_NAME);
+
+        match unsafe { user32::FindWindowW(class_name.as_ptr(), ::std::ptr::null()) } {
+            hwnd if hwnd == 0 as HWND => None,
+            hwnd => Some(hwnd)
+        }
+    }
+
+    /// Queues a message as if the window had sent it
+    pub fn post(&self, msg: AppMsg) {
+        let _ = self.tx.send(msg);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Unregisters all hotkeys so other programs receive them, or registers them again
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused != self.paused {
+            if paused {
+                if self.leader.is_active() {
+                    self.leader.cancel();
+                    self.end_leader();
+                }
+
+                self.unregister_hotkeys();
+            } else {
+                self.register_hotkeys();
+            }
+
+            self.paused = paused;
+        }
+    }
+
+    /// Switches to new hotkeys, only re-registering the ones that changed
+    ///
+    /// While paused the bindings are only stored.
+    pub fn set_bindings(&mut self, bindings: &[Binding]) {
+        if !self.paused {
+            let hwnd = self.hwnd;
+            let report = ::std::mem::replace(&mut self.hotkeys, HotkeyReport::default());
+
+            self.hotkeys = report.update(&self.bindings, bindings,
+                |id, chord| register_hotkey(hwnd, id, chord),
+                |id| unsafe { user32::UnregisterHotKey(hwnd, id); });
+        }
+
+        self.bindings = bindings.to_vec();
+    }
+
+    /// Replaces the key sequences, cancelling one that is being typed
+    pub fn set_leader(&mut self, leader: Leader) {
+        if self.leader.is_active() {
+            self.leader.cancel();
+            self.end_leader();
+        }
+
+        self.leader = leader;
+    }
+
+    fn register_hotkeys(&mut self) {
+        let hwnd = self.hwnd;
+
+        self.hotkeys = hotkeys::register_all(&self.bindings, |id, chord| register_hotkey(hwnd, id, chord));
+    }
+
+    fn unregister_hotkeys(&mut self) {
+        for &(id, _, _) in self.hotkeys.active() {
+            unsafe { user32::UnregisterHotKey(self.hwnd, id) };
+        }
+
+        self.hotkeys = HotkeyReport::default();
     }
 
     pub fn listen(&self) -> spmc::Receiver<AppMsg> {
         self.rx.clone()
     }
 
-    fn on_hotkey(&self, id: i32, _modifiers: u32, vk: u32) {
-        match (id, vk) {
-            (HK_QUIT, _) => {
+    /// Which hotkeys are registered and which could not be
+    pub fn hotkeys(&self) -> &HotkeyReport {
+        &self.hotkeys
+    }
+
+    /// Starts or stops sending `AppMsg::Poll`
+    pub fn set_polling(&self, enabled: bool) {
+        unsafe {
+            if enabled {
+                user32::SetTimer(self.hwnd, TIMER_POLL, TIMER_POLL_INTERVAL, None);
+            } else {
+                user32::KillTimer(self.hwnd, TIMER_POLL);
+            }
+        }
+    }
+
+    fn on_hotkey(&mut self, id: i32) {
+        if id >= HK_LEADER_KEY {
+            let vk = (id - HK_LEADER_KEY) as UINT;
+
+            match self.leader.key(vk, Instant::now()) {
+                Step::Pending => self.set_leader_timer(),
+                Step::Done(action) => {
+                    self.end_leader();
+                    self.on_action(action);
+                },
+                Step::Cancelled => self.end_leader(),
+            }
+        } else if let Some(action) = self.hotkeys.action(id) {
+            self.on_action(action);
+        }
+    }
+
+    /// Does what a hotkey bound to `action` does
+    pub fn on_action(&mut self, action: Action) {
+        let msg = match action {
+            Action::Quit => {
                 unsafe { user32::PostQuitMessage(0); }
+                return;
             },
-
-            (HK_POPUP, _) => {
-                let _ = self.tx.send(AppMsg::ShowPopup);
+            Action::Leader => {
+                self.start_leader();
+                return;
             },
+            Action::ShowPopup => AppMsg::ShowPopup,
+            Action::Grab(vk) => AppMsg::GrabWindow(vk),
+            Action::Switch(vk) => AppMsg::FocusWindow(vk, Direction::Forward),
+            Action::SwitchBack(vk) => AppMsg::FocusWindow(vk, Direction::Backward),
+            Action::Clear(vk) => AppMsg::ClearWindow(vk),
+            Action::Release => AppMsg::ReleaseWindow,
+            Action::ShiftUp => AppMsg::ShiftWindow(-1),
+            Action::ShiftDown => AppMsg::ShiftWindow(1),
+            Action::CycleLogLevel => AppMsg::CycleLogLevel,
+            Action::Tile(tile) => AppMsg::TileWindow(tile),
+            Action::SwitchDesktop(number) => AppMsg::SwitchDesktop(number),
+            Action::NextProfile => AppMsg::NextProfile,
+        };
 
-            (HK_GRAB, vk) => {
-                let _ = self.tx.send(AppMsg::GrabWindow(vk));
-            },
+        let _ = self.tx.send(msg);
+    }
 
-            (HK_SWITCH, vk) => {
-                let _ = self.tx.send(AppMsg::FocusWindow(vk));
-            },
+    /// Captures the keys of all sequences until one is complete or cancelled
+    fn start_leader(&mut self) {
+        if !self.leader.is_active() {
+            for vk in self.leader.keys() {
+                if unsafe { user32::RegisterHotKey(self.hwnd, HK_LEADER_KEY + vk as i32, MOD_NOREPEAT, vk) } == FALSE {
+                    debug!("Could not capture key {:#04x} for the leader: {}", vk, Error::last_win32());
+                }
+            }
+        }
 
-            (HK_CLEAR, vk) => {
-                let _ = self.tx.send(AppMsg::ClearWindow(vk));
-            },
+        self.leader.start(Instant::now());
+        self.set_leader_timer();
+    }
 
-            _ => {}
+    fn end_leader(&mut self) {
+        for vk in self.leader.keys() {
+            unsafe { user32::UnregisterHotKey(self.hwnd, HK_LEADER_KEY + vk as i32) };
         }
+
+        unsafe { user32::KillTimer(self.hwnd, TIMER_LEADER) };
+    }
+
+    fn set_leader_timer(&self) {
+        let timeout = self.leader.timeout();
+        let ms = timeout.as_secs() as UINT * 1000 + timeout.subsec_millis();
+
+        // Replaces the timer that is already running
+        unsafe { user32::SetTimer(self.hwnd, TIMER_LEADER, ms, None) };
     }
 
     unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
@@ -125,10 +356,79 @@ impl AppWindow {
         if let Some(instance) = instance {
             match msg {
                 WM_HOTKEY => {
-                    let id = wparam as i32;
-                    let modifiers = LOWORD(lparam as DWORD) as u32;
-                    let vk = HIWORD(lparam as DWORD) as u32;
-                    instance.on_hotkey(id, modifiers, vk);
+                    instance.on_hotkey(wparam as i32);
+
+                    return 0;
+                },
+
+                WM_TRAY => {
+                    match LOWORD(lparam as DWORD) as UINT {
+                        WM_LBUTTONUP => instance.post(AppMsg::ShowPopup),
+                        WM_RBUTTONUP => instance.post(AppMsg::TrayMenu),
+                        _ => {}
+                    }
+
+                    return 0;
+                },
+
+                WM_COPYDATA => {
+                    match windows::received_copy_data(lparam) {
+                        (COPYDATA_COMMAND, bytes) => {
+                            let text: Vec<u16> = bytes.chunks(2)
+                                .filter(|pair| pair.len() == 2)
+                                .map(|pair| pair[0] as u16 | (pair[1] as u16) << 8)
+                                .collect();
+                            let text = String::from_utf16_lossy(&text);
+
+                            match text.parse::<Command>() {
+                                Ok(command) => {
+                                    instance.post(AppMsg::RunCommand(command));
+                                    return TRUE as LRESULT;
+                                },
+                                Err(err) => warn!("Ignoring command '{}' from another instance: {}", text, err)
+                            }
+                        },
+
+                        (COPYDATA_TABS, bytes) => {
+                            let tabs = String::from_utf8_lossy(bytes)
+                                .parse::<Json>()
+                                .and_then(|json| TabList::from_json(&json));
+
+                            match tabs {
+                                Ok(tabs) => {
+                                    instance.post(AppMsg::Tabs(wparam as usize, tabs));
+                                    return TRUE as LRESULT;
+                                },
+                                Err(err) => warn!("Ignoring tabs from native messaging host {:#x}: {}", wparam, err)
+                            }
+                        },
+
+                        (kind, _) => debug!("Ignoring WM_COPYDATA of kind {}", kind)
+                    }
+
+                    return FALSE as LRESULT;
+                },
+
+                msg if msg == instance.taskbar_created => {
+                    instance.post(AppMsg::TaskbarCreated);
+                    return 0;
+                },
+
+                WM_TIMER => {
+                    match wparam as UINT_PTR {
+                        TIMER_POLL => {
+                            let _ = instance.tx.send(AppMsg::Poll);
+                        },
+                        TIMER_CONFIG => {
+                            let _ = instance.tx.send(AppMsg::CheckConfig);
+                        },
+                        TIMER_LEADER => {
+                            if instance.leader.expire(Instant::now()) {
+                                instance.end_leader();
+                            }
+                        },
+                        _ => {}
+                    }
 
                     return 0;
                 },
@@ -146,23 +446,9 @@ impl AppWindow {
     }
 }
 
-fn register_hotkeys(hwnd: HWND) {
-    // Virtual key codes: https://msdn.microsoft.com/en-us/library/windows/desktop/dd375731(v=vs.85).aspx
-    // CTRL-ALT-Q to quit
-    unsafe {
-        user32::RegisterHotKey(hwnd, HK_QUIT, MOD_QUIT, VK_Q);
-        user32::RegisterHotKey(hwnd, HK_POPUP, MOD_POPUP, 0x20); // VK_SPACE
+fn register_hotkey(hwnd: HWND, id: i32, chord: Chord) -> Win32Result<()> {
+    match unsafe { user32::RegisterHotKey(hwnd, id, chord.modifiers, chord.vk) } {
+        FALSE => Err(Error::last_win32()),
+        _ => Ok(())
     }
-
-    // ALT-SHIFT-1 to ALT-SHIFT-9 to grab windows,
-    // ALT-1 to ALT-9 to switch windows
-    for i in 0..10 {
-        let vk_n = VK_0 + i;
-
-        unsafe {
-            user32::RegisterHotKey(hwnd, HK_GRAB, MOD_GRAB, vk_n);
-            user32::RegisterHotKey(hwnd, HK_SWITCH, MOD_SWITCH, vk_n);
-            user32::RegisterHotKey(hwnd, HK_CLEAR, MOD_CLEAR, vk_n);
-        }
-    }
-}
\ No newline at end of file
+}
@@ -4,10 +4,14 @@ use winapi::windef::*;
 use winapi::winuser;
 use winapi::*;
 
+use error::Error;
+use layout::monitor::{self, Monitor, Placement};
+use utils::api_wrappers;
 use utils::Win32Result;
 
 pub mod main;
 pub mod popup;
+pub mod tab_host;
 
 pub struct ManagedWindow2<T> {
     hwnd: HWND,
@@ -23,12 +27,12 @@ impl<T> ManagedWindow2<T> {
             if prev_value == 0 {
                 let err = kernel32::GetLastError();
                 if err != 0 {
-                    return Err(err)
+                    return Err(Error::Win32(err))
                 }
             }
         }
 
-        println!("Window {:?} is managed", hwnd);
+        trace!("Window {:?} is managed", hwnd);
 
         Ok(ManagedWindow2 {
             hwnd: hwnd,
@@ -51,7 +55,7 @@ impl<T> ManagedWindow2<T> {
 impl<T> Drop for ManagedWindow2<T> {
     fn drop(&mut self) {
         unsafe { user32::SetWindowLongPtrW(self.hwnd, GWLP_USERDATA, 0); }
-        println!("Window {:?} is kill", self.hwnd);
+        trace!("Window {:?} is no longer managed", self.hwnd);
     }
 }
 
@@ -69,19 +73,42 @@ impl<T> ::std::ops::DerefMut for ManagedWindow2<T> {
     }
 }
 
-pub type Bounds = (i32, i32, i32, i32);
+/// Payload of `WM_COPYDATA`, missing from winapi 0.2
+#[allow(non_snake_case)]
+#[repr(C)]
+struct COPYDATASTRUCT {
+    dwData: ULONG_PTR,
+    cbData: DWORD,
+    lpData: LPVOID,
+}
+
+/// Sends `data` to a window of another process, returns what its window procedure returned
+///
+/// `kind` tells the receiver what the data is, `wparam` is passed on unchanged.
+pub fn send_copy_data(hwnd: HWND, wparam: WPARAM, kind: ULONG_PTR, data: &[u8]) -> LRESULT {
+    let mut data = COPYDATASTRUCT {
+        dwData: kind,
+        cbData: data.len() as DWORD,
+        lpData: data.as_ptr() as LPVOID,
+    };
 
-#[allow(dead_code)]
-pub enum HorizontalAlignment {
-    Left, Center, Right
+    unsafe { user32::SendMessageW(hwnd, WM_COPYDATA, wparam, &mut data as *mut _ as LPARAM) }
 }
 
-#[allow(dead_code)]
-pub enum VerticalAlignment {
-    Top, Center, Bottom
+/// Kind and data of a `WM_COPYDATA` message, only valid while handling it
+pub unsafe fn received_copy_data<'a>(lparam: LPARAM) -> (ULONG_PTR, &'a [u8]) {
+    let data = &*(lparam as *const COPYDATASTRUCT);
+
+    if data.cbData == 0 || data.lpData.is_null() {
+        (data.dwData, &[])
+    } else {
+        (data.dwData, ::std::slice::from_raw_parts(data.lpData as *const u8, data.cbData as usize))
+    }
 }
 
-pub fn get_screen_bounds() -> Bounds {
+pub use layout::{calc_window_pos, HorizontalAlignment, Layout, Rect, Thickness, VerticalAlignment};
+
+pub fn get_screen_bounds() -> Rect {
     let (screen_w, screen_h) = unsafe {
         (
             user32::GetSystemMetrics(winuser::SM_CXSCREEN),
@@ -89,10 +116,46 @@ pub fn get_screen_bounds() -> Bounds {
         )
     };
 
-    (0, 0, screen_w, screen_h)
+    Rect::from_origin_size(0, 0, screen_w, screen_h)
 }
 
-pub fn get_window_bounds(hwnd: HWND) -> Bounds {
+pub fn get_monitors() -> Win32Result<Vec<Monitor>> {
+    let mut monitors = Vec::new();
+
+    try!{ api_wrappers::enum_display_monitors(|hmonitor| {
+        if let Ok(info) = api_wrappers::get_monitor_info(hmonitor) {
+            monitors.push(Monitor {
+                bounds: from_rect(info.rcMonitor),
+                work_area: from_rect(info.rcWork),
+                dpi: api_wrappers::get_monitor_dpi(hmonitor),
+                primary: info.dwFlags & winuser::MONITORINFOF_PRIMARY != 0,
+            });
+        }
+
+        TRUE
+    }) };
+
+    Ok(monitors)
+}
+
+/// Work area of the monitor chosen by `placement`, or the primary screen if
+/// monitors cannot be enumerated
+pub fn get_work_area(placement: Placement) -> Rect {
+    let monitors = get_monitors().unwrap_or(Vec::new());
+    let cursor = api_wrappers::get_cursor_pos().ok();
+    let foreground = match unsafe { user32::GetForegroundWindow() } {
+        hwnd if hwnd == 0 as HWND => None,
+        hwnd => Some(get_window_bounds(hwnd)),
+    };
+
+    match monitor::select_monitor(&monitors, placement, cursor, foreground) {
+        Some(monitor) => monitor.work_area,
+        None => get_screen_bounds(),
+    }
+}
+
+/// Window rectangle in screen coordinates
+pub fn get_window_bounds(hwnd: HWND) -> Rect {
     let mut rect = RECT {
         left: 0,
         top: 0,
@@ -103,47 +166,26 @@ pub fn get_window_bounds(hwnd: HWND) -> Bounds {
     unsafe {
         user32::GetWindowRect(hwnd, &mut rect as *mut _);
     }
-    
-    (rect.left, rect.top, rect.right, rect.bottom)
+
+    from_rect(rect)
 }
 
-pub fn calc_window_pos(
-    parent: Bounds,
-    width: Option<i32>,
-    height: Option<i32>,
-    margin: Option<Bounds>,
-    padding: Option<Bounds>,
-    hor_align: HorizontalAlignment,
-    vert_align: VerticalAlignment) -> Bounds {
-    
-    // Parent bounds
-    let (l, t, r, b) = parent;
-    let (parent_w, parent_h) = (r - l, b - t);
-
-    // Self bounds
-    let w = width.unwrap_or(parent_w);
-    let h = height.unwrap_or(parent_h);
-    let x = match hor_align {
-        HorizontalAlignment::Left => 0,
-        HorizontalAlignment::Center => (parent_w / 2) - (w / 2),
-        HorizontalAlignment::Right => parent_w - w,
-    };
-    let y = match vert_align {
-        VerticalAlignment::Top => 0,
-        VerticalAlignment::Center => (parent_h / 2) - (h / 2),
-        VerticalAlignment::Bottom => parent_h - h,
+/// Client area, the coordinate space of child windows
+pub fn get_client_bounds(hwnd: HWND) -> Rect {
+    let mut rect = RECT {
+        left: 0,
+        top: 0,
+        right: 0,
+        bottom: 0
     };
 
-    // Bounds modifiers (margin, padding)
-    let (margin_left, margin_top, margin_right, margin_bottom) =
-        margin.unwrap_or((0, 0, 0, 0));
-    let (padding_left, padding_top, padding_right, padding_bottom) =
-        padding.unwrap_or((0, 0, 0, 0));
-
-    (
-        x + margin_left - margin_right + padding_left,
-        y + margin_top - margin_bottom + padding_top,
-        w - padding_left - padding_right,
-        h - padding_top - padding_bottom
-    )
-}
\ No newline at end of file
+    unsafe {
+        user32::GetClientRect(hwnd, &mut rect as *mut _);
+    }
+
+    from_rect(rect)
+}
+
+fn from_rect(rect: RECT) -> Rect {
+    Rect::from_edges(rect.left, rect.top, rect.right, rect.bottom)
+}
@@ -1,8 +1,8 @@
 use comctl32;
-use kernel32;
 use user32;
 use winapi::*;
 
+use error::Error;
 use utils;
 use utils::Win32Result;
 use windows::*;
@@ -14,12 +14,11 @@ const MSG_NOTIFY_CHAR: u32 = 3;
 pub struct EditBox { pub hwnd: HWND }
 
 impl EditBox {
-    pub fn new(parent: HWND, bounds: Bounds) -> Win32Result<Self> {
+    pub fn new(parent: HWND, bounds: Rect) -> Win32Result<Self> {
         // Using Edit Controls
         // https://msdn.microsoft.com/en-us/library/windows/desktop/bb775462(v=vs.85).aspx
         let class_name = utils::to_wide_chars("Edit");
 
-        let (x, y, w, h) = bounds;
         let hwnd = unsafe {
             let hwnd = user32::CreateWindowExW(
                 0, //winuser::WS_EX_CLIENTEDGE,
@@ -29,17 +28,17 @@ impl EditBox {
                     | winuser::WS_CHILD
                     | winuser::ES_MULTILINE
                     | winuser::ES_LEFT | winuser::ES_AUTOHSCROLL | ES_AUTOVSCROLL,
-                x,
-                y,
-                w,
-                h,
+                bounds.left,
+                bounds.top,
+                bounds.width(),
+                bounds.height(),
                 parent,
                 0 as HMENU,
                 0 as HINSTANCE,
                 0 as LPVOID);
             
             if hwnd == 0 as HWND {
-                return Err(kernel32::GetLastError());
+                return Err(Error::last_win32());
             }
 
             hwnd
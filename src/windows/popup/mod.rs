@@ -1,9 +1,11 @@
 use winapi::*;
-use kernel32;
 use user32;
 use gdi32;
 use spmc;
 
+use error::Error;
+use layout::monitor::Placement;
+use theme::Theme;
 use utils;
 use utils::Win32Result;
 use windows::*;
@@ -15,9 +17,13 @@ mod edit;
 const CLASS_NAME: &'static str = "WinmanPopupWindow";
 
 const WIN_DIMENSIONS: (i32, i32) = (340, 50);
-const THEME_BG_COLOR: u32 = 0x00222222;
-const THEME_EDIT_COLOR: u32 = 0x00A3FFA3;
-const THEME_EDIT_BG_COLOR: u32 = 0x00323232;
+
+/// Lines of results shown below the search box, more are left out
+pub const MAX_RESULTS: usize = 8;
+const RESULT_LINE_HEIGHT: i32 = 18;
+const RESULT_MARGIN: i32 = 20;
+
+const SS_NOPREFIX: DWORD = 0x80;
 
 const MSG_NOTIFY_RETURN: u32 = 1;
 const MSG_NOTIFY_ESCAPE: u32 = 2;
@@ -31,6 +37,10 @@ pub enum PopupMsg {
 pub struct PopupWindow {
     hwnd: HWND,
     edit_box: EditBox,
+    /// Static control listing the results, empty unless there are some
+    results: HWND,
+    theme: Theme,
+    placement: Placement,
     hbrush_primary: HBRUSH,
     hbrush_secondary: HBRUSH,
     tx: spmc::Sender<PopupMsg>,
@@ -50,13 +60,13 @@ impl PopupWindow {
 
         unsafe {
             match user32::RegisterClassExW(&window_class) {
-                0 => Err(kernel32::GetLastError()),
+                0 => Err(Error::last_win32()),
                 _ => Ok(())
             }
         }
     }
 
-    pub fn new(hwnd_parent: HWND) -> Win32Result<ManagedWindow2<PopupWindow>> {
+    pub fn new(hwnd_parent: HWND, theme: &Theme, placement: Placement) -> Win32Result<ManagedWindow2<PopupWindow>> {
         let (w, h) = WIN_DIMENSIONS;
         let class_name = utils::to_wide_chars(CLASS_NAME);
 
@@ -85,7 +95,7 @@ impl PopupWindow {
         // If we error on step 2, we must destroy the window instance before returning
         // The parent HWND is not managed and will be forgotten on an early return
         // The easiest way to ensure this is to perform the rest of the layout in a separate funcction
-        let create_result = PopupWindow::new_impl(hwnd);
+        let create_result = PopupWindow::new_impl(hwnd, theme, placement);
 
         match create_result {
             Ok(window) => {
@@ -99,29 +109,49 @@ impl PopupWindow {
         }
     }
 
-    fn new_impl(hwnd: HWND) -> Win32Result<PopupWindow> {
+    fn new_impl(hwnd: HWND, theme: &Theme, placement: Placement) -> Win32Result<PopupWindow> {
         // Create controls
-        let bounds_window = get_window_bounds(hwnd);
+        let bounds_client = get_client_bounds(hwnd);
 
         let edit_box = {
-            let height = 22;
-            let padding = (15, 0, 15, 0);
-            let bounds_edit = calc_window_pos(
-                bounds_window,
-                None,
-                Some(height),
-                None,
-                Some(padding),
-                HorizontalAlignment::Center,
-                VerticalAlignment::Center);
+            let layout = Layout {
+                height: Some(22),
+                margin: Thickness::symmetric(15, 0),
+                ..Layout::default()
+            };
+            let bounds_edit = calc_window_pos(bounds_client, &layout).bounds;
 
             try!{ EditBox::new(hwnd, bounds_edit) }
         };
 
-        // Create brush resources
-        // TODO: dispose
-        let hbrush_primary = unsafe { gdi32::CreateSolidBrush(THEME_BG_COLOR) };
-        let hbrush_secondary = unsafe { gdi32::CreateSolidBrush(THEME_EDIT_BG_COLOR) };
+        let results = unsafe {
+            let class_name = utils::to_wide_chars("Static");
+            let (w, h) = WIN_DIMENSIONS;
+
+            let results = user32::CreateWindowExW(
+                0,
+                class_name.as_ptr(),
+                0 as LPCWSTR,
+                winuser::WS_VISIBLE | winuser::WS_CHILD | SS_NOPREFIX,
+                RESULT_MARGIN,
+                h,
+                w - 2 * RESULT_MARGIN,
+                0,
+                hwnd,
+                0 as HMENU,
+                0 as HINSTANCE,
+                0 as LPVOID);
+
+            if results == 0 as HWND {
+                return Err(Error::last_win32());
+            }
+
+            results
+        };
+
+        // Create brush resources, deleted in `set_theme` and on drop
+        let hbrush_primary = unsafe { gdi32::CreateSolidBrush(theme.background.0) };
+        let hbrush_secondary = unsafe { gdi32::CreateSolidBrush(theme.edit_background.0) };
 
         // Open a channel to broadcast UI events
         let (tx, rx) = spmc::channel();
@@ -129,6 +159,9 @@ impl PopupWindow {
         Ok(PopupWindow {
             hwnd: hwnd,
             edit_box: edit_box,
+            results: results,
+            theme: *theme,
+            placement: placement,
             hbrush_primary: hbrush_primary,
             hbrush_secondary: hbrush_secondary,
             tx: tx,
@@ -140,26 +173,71 @@ impl PopupWindow {
         self.rx.clone()
     }
 
+    /// Replaces the brushes with ones in the colors of `theme` and repaints
+    pub fn set_theme(&mut self, theme: &Theme) {
+        if *theme == self.theme {
+            return;
+        }
+
+        unsafe {
+            gdi32::DeleteObject(self.hbrush_primary as HGDIOBJ);
+            gdi32::DeleteObject(self.hbrush_secondary as HGDIOBJ);
+
+            self.hbrush_primary = gdi32::CreateSolidBrush(theme.background.0);
+            self.hbrush_secondary = gdi32::CreateSolidBrush(theme.edit_background.0);
+        }
+
+        self.theme = *theme;
+
+        unsafe {
+            user32::InvalidateRect(self.hwnd, ::std::ptr::null(), TRUE);
+            user32::InvalidateRect(self.edit_box.hwnd, ::std::ptr::null(), TRUE);
+            user32::InvalidateRect(self.results, ::std::ptr::null(), TRUE);
+        }
+    }
+
+    /// Monitor to open on from the next `show`
+    pub fn set_placement(&mut self, placement: Placement) {
+        self.placement = placement;
+    }
+
     pub fn show(&self) {
-        let screen_bounds = get_screen_bounds();
+        let work_area = get_work_area(self.placement);
         let (w, h) = WIN_DIMENSIONS;
-        let (x, y, w, h) = calc_window_pos(
-            screen_bounds,
-            Some(w),
-            Some(h),
-            None,
-            None,
-            HorizontalAlignment::Center,
-            VerticalAlignment::Center);
+        let layout = Layout {
+            width: Some(w),
+            height: Some(h),
+            ..Layout::default()
+        };
+        let bounds = calc_window_pos(work_area, &layout).bounds;
 
         unsafe {
-            user32::SetWindowPos(self.hwnd, winuser::HWND_TOPMOST, x, y, w, h, 0);
+            user32::SetWindowPos(self.hwnd, winuser::HWND_TOPMOST, bounds.left, bounds.top, bounds.width(), bounds.height(), 0);
             user32::ShowWindow(self.hwnd, SW_SHOWNORMAL);
             user32::SetForegroundWindow(self.hwnd);
             user32::SetFocus(self.edit_box.hwnd);
         }
 
         self.edit_box.clear();
+        self.set_results(&[]);
+    }
+
+    /// Lists results below the search box, growing the window to fit them
+    pub fn set_results(&self, lines: &[String]) {
+        let lines = &lines[..lines.len().min(MAX_RESULTS)];
+        let text = utils::to_wide_chars(&lines.join("\r\n"));
+        let (w, h) = WIN_DIMENSIONS;
+        let height = lines.len() as i32 * RESULT_LINE_HEIGHT;
+        let padding = if lines.is_empty() { 0 } else { RESULT_MARGIN / 2 };
+
+        unsafe {
+            user32::SetWindowTextW(self.results, text.as_ptr());
+            user32::SetWindowPos(self.results, 0 as HWND, 0, 0, w - 2 * RESULT_MARGIN, height,
+                winuser::SWP_NOMOVE | winuser::SWP_NOZORDER);
+            user32::SetWindowPos(self.hwnd, 0 as HWND, 0, 0, w, h + height + padding,
+                winuser::SWP_NOMOVE | winuser::SWP_NOZORDER);
+            user32::InvalidateRect(self.hwnd, ::std::ptr::null(), TRUE);
+        }
     }
 
     pub fn _hide(&self) {
@@ -184,13 +262,22 @@ impl PopupWindow {
 
     fn wm_ctlcoloredit(&self, hdc: HDC) -> Option<HBRUSH> {
         unsafe {
-            gdi32::SetBkColor(hdc, THEME_EDIT_BG_COLOR);
-            gdi32::SetTextColor(hdc, THEME_EDIT_COLOR);
+            gdi32::SetBkColor(hdc, self.theme.edit_background.0);
+            gdi32::SetTextColor(hdc, self.theme.text.0);
         }
 
         Some(self.hbrush_secondary)
     }
 
+    fn wm_ctlcolorstatic(&self, hdc: HDC) -> Option<HBRUSH> {
+        unsafe {
+            gdi32::SetBkColor(hdc, self.theme.background.0);
+            gdi32::SetTextColor(hdc, self.theme.text.0);
+        }
+
+        Some(self.hbrush_primary)
+    }
+
     fn wm_notify(&self, nmhdr: &winuser::NMHDR) {
         match nmhdr.code {
             MSG_NOTIFY_ESCAPE => {
@@ -244,6 +331,14 @@ impl PopupWindow {
                     return dc_brush as LRESULT;
                 },
 
+                WM_CTLCOLORSTATIC => {
+                    let hdc: HDC = wparam as HDC;
+                    let dc_brush = instance.wm_ctlcolorstatic(hdc);
+                    let dc_brush = dc_brush.unwrap_or(0 as HBRUSH);
+
+                    return dc_brush as LRESULT;
+                },
+
                 WM_NOTIFY => {
                     let nmhdr = lparam as *const winuser::NMHDR;
                     instance.wm_notify(&*nmhdr);
@@ -270,4 +365,13 @@ impl PopupWindow {
 
         user32::DefWindowProcW(hwnd, msg, wparam, lparam)
     }
-}
\ No newline at end of file
+}
+
+impl Drop for PopupWindow {
+    fn drop(&mut self) {
+        unsafe {
+            gdi32::DeleteObject(self.hbrush_primary as HGDIOBJ);
+            gdi32::DeleteObject(self.hbrush_secondary as HGDIOBJ);
+        }
+    }
+}
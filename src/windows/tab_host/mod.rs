@@ -0,0 +1,139 @@
+use std::io;
+use std::thread;
+
+use winapi::*;
+use user32;
+
+use error::Error;
+use json::Json;
+use native_host::{self, Tab, TabList};
+use utils;
+use utils::Win32Result;
+use windows;
+use windows::main::AppWindow;
+
+const CLASS_NAME: &'static str = "WinmanTabHost";
+
+// `WM_COPYDATA` kind of an activate message, see `activate_tab`
+const COPYDATA_ACTIVATE_TAB: ULONG_PTR = 3;
+
+/// Runs as the native messaging host of a browser until the browser disconnects
+///
+/// Tab lists the extension writes to stdin are passed on to the running WinMan.
+/// WinMan sends tabs to activate to a hidden window, which writes them to stdout.
+/// Errors go to stderr, which browsers show in their own log.
+pub fn run() -> Result<(), Error> {
+    let hwnd = try!{ create_window() };
+    // `HWND` can not be moved to another thread
+    let host = hwnd as usize;
+
+    thread::spawn(move || {
+        let host = host as HWND;
+        let stdin = io::stdin();
+
+        let result = native_host::serve(&mut stdin.lock(), |tabs| {
+            match AppWindow::send_tabs(host, &tabs) {
+                Ok(true) => {},
+                Ok(false) => eprintln!("WinMan is not running, dropping {} tabs", tabs.tabs.len()),
+                Err(err) => eprintln!("Could not pass on {} tabs: {}", tabs.tabs.len(), err),
+            }
+        });
+
+        if let Err(err) = result {
+            eprintln!("Lost the browser: {}", err);
+        }
+
+        // WinMan forgets the tabs of a host that sends none
+        let _ = AppWindow::send_tabs(host, &TabList::default());
+        unsafe { user32::PostMessageW(host, WM_CLOSE, 0, 0) };
+    });
+
+    let mut msg = unsafe { ::std::mem::zeroed() };
+    while unsafe { user32::GetMessageW(&mut msg, 0 as HWND, 0, 0) } > 0 {
+        unsafe {
+            user32::TranslateMessage(&mut msg);
+            user32::DispatchMessageW(&mut msg);
+        }
+    }
+
+    Ok(())
+}
+
+/// Asks the host that reported `tab` to have its browser show it
+///
+/// Fails if the host has exited since, which happens when the browser closes.
+pub fn activate_tab(host: HWND, tab: &Tab) -> Result<(), Error> {
+    let message = native_host::activate_message(tab).to_string();
+
+    match windows::send_copy_data(host, 0, COPYDATA_ACTIVATE_TAB, message.as_bytes()) {
+        0 => Err(Error::Ipc(format!("the browser of tab {} is gone", tab.id))),
+        _ => Ok(())
+    }
+}
+
+fn create_window() -> Win32Result<HWND> {
+    let class_name = utils::to_wide_chars(CLASS_NAME);
+
+    let mut window_class: WNDCLASSEXW = unsafe { ::std::mem::zeroed() };
+    window_class.cbSize = ::std::mem::size_of::<WNDCLASSEXW>() as u32;
+    window_class.lpfnWndProc = Some(window_proc);
+    window_class.lpszClassName = class_name.as_ptr();
+
+    unsafe {
+        if user32::RegisterClassExW(&window_class) == 0 {
+            return Err(Error::last_win32());
+        }
+
+        let hwnd = user32::CreateWindowExW(
+            0,
+            class_name.as_ptr(),
+            0 as LPCWSTR,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0 as HWND,
+            0 as HMENU,
+            0 as HINSTANCE,
+            0 as LPVOID);
+
+        match hwnd {
+            hwnd if hwnd == 0 as HWND => Err(Error::last_win32()),
+            hwnd => Ok(hwnd)
+        }
+    }
+}
+
+unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_COPYDATA => {
+            if let (COPYDATA_ACTIVATE_TAB, bytes) = windows::received_copy_data(lparam) {
+                let written = String::from_utf8_lossy(bytes)
+                    .parse::<Json>()
+                    .map_err(Error::Ipc)
+                    .and_then(|message| {
+                        let stdout = io::stdout();
+                        let mut stdout = stdout.lock();
+                        native_host::write_message(&mut stdout, &message)
+                    });
+
+                match written {
+                    Ok(()) => return TRUE as LRESULT,
+                    Err(err) => eprintln!("Could not activate a tab: {}", err)
+                }
+            }
+
+            return FALSE as LRESULT;
+        },
+
+        WM_DESTROY => {
+            user32::PostQuitMessage(0);
+            return 0;
+        },
+
+        _ => {}
+    }
+
+    user32::DefWindowProcW(hwnd, msg, wparam, lparam)
+}
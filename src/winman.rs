@@ -3,39 +3,204 @@ extern crate comctl32;
 extern crate kernel32;
 extern crate user32;
 extern crate gdi32;
+extern crate shell32;
 extern crate spmc;
 extern crate fuzzy;
+extern crate layout;
+#[macro_use]
+extern crate log;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+use std::time::Instant;
 
 use winapi::minwindef::*;
 use winapi::windef::*;
+use winapi::winuser::WM_NULL;
+
+pub use error::Error;
 
-use window_tracking::Config;
-use windows::main::{AppWindow, AppMsg};
-use windows::popup::{PopupWindow, PopupMsg};
+use backend::{DesktopBackend, DesktopId, TrayBackend, WindowBackend, WindowEvent, Win32Backend, Win32Desktops, Win32Tray};
+use commands::Command;
+use file_watch::FileWatch;
+use hotkeys::HotkeyReport;
+use ini::{Ini, Section};
+use launcher::{LaunchEvent, Launcher};
+use layouts::Layouts;
+use leader::Leader;
+use settings::Settings;
+use search_worker::{Request, SearchWorker};
+use sources::ResultAction;
+use titles::WindowTitle;
+use tray::{SlotSummary, TrayCommand};
+use watcher::WindowWatcher;
+use window_tracking::{Config, Direction, Window};
+use windows::main::{AppWindow, AppMsg, WM_TRAY};
+use windows::popup::{self, PopupWindow, PopupMsg};
+use windows::tab_host;
 
+mod apps;
+mod backend;
+mod commands;
 mod constants;
+mod desktops;
+mod eligibility;
+mod error;
+mod file_watch;
+mod hotkeys;
+mod ini;
+mod json;
+mod launcher;
+mod layouts;
+mod leader;
+mod logging;
+mod native_host;
+mod rules;
+mod search_worker;
+mod settings;
+mod sources;
+mod theme;
+mod tiling;
+mod titles;
+mod tray;
 mod utils;
+mod watcher;
 mod window_tracking;
 mod windows;
 
 pub fn main() {
-	println!("Hello Windows!");
+    // Browsers start the host with the extension that connects, see `native_host`
+    if native_host_arg() {
+        if let Err(err) = tab_host::run() {
+            eprintln!("Native messaging host stopped: {}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Err(err) = run() {
+        error!("WinMan stopped: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    // Config errors are logged once logging is set up with the defaults
+    let config = load_config();
+    let log_options = match config {
+        Ok(ref config) => config.settings().log.clone(),
+        Err(_) => Default::default(),
+    };
+
+    if let Err(err) = logging::init(&log_options, &utils::config_dir()) {
+        eprintln!("Could not open the log file: {}", err);
+    }
+
+    let command_arg = command_arg();
+
+    // A running WinMan runs the command instead of a second one starting
+    if let Some((ref text, _)) = command_arg {
+        if try!{ AppWindow::send_command(text) } {
+            return Ok(());
+        }
+    }
+
+    info!(version = env!("CARGO_PKG_VERSION"); "WinMan started");
+
+    let mut config = config.unwrap_or_else(|err| {
+        error!("Could not load winman.ini: {}", err);
+        Config::new()
+    });
+
+    // The profile named on the command line, or the one active when WinMan last ran
+    match command_arg {
+        Some((_, Command::Profile(Some(ref name)))) => {
+            if activate_profile(&mut config, name) {
+                if let Err(err) = save_profile(name) {
+                    error!("Could not save the active profile: {}", err);
+                }
+            }
+        },
+        _ => {
+            if let Some(name) = load_profile() {
+                activate_profile(&mut config, &name);
+            }
+        }
+    }
 
     // Register window classes
-    AppWindow::register_classes().expect("Could not register AppWindow class");
-    PopupWindow::register_classes().expect("Could not register PopupWindow class");
+    try!{ AppWindow::register_classes() };
+    try!{ PopupWindow::register_classes() };
 
     // Main window
-    let app_window = AppWindow::new().expect("Could not create AppWindow");
+    let leader = Leader::new(config.settings().leader.clone());
+    let mut app_window = try!{ AppWindow::new(&config.settings().hotkeys(), leader) };
     let app_rx = app_window.listen();
 
+    for failure in app_window.hotkeys().failures() {
+        warn!("Could not register hotkey {}", failure);
+    }
+
+    // Other commands from the command line run once everything is set up
+    match command_arg {
+        Some((_, Command::Profile(Some(_)))) | None => {},
+        Some((_, command)) => app_window.post(AppMsg::RunCommand(command))
+    }
+
+    let mut tray = Win32Tray::new(app_window.hwnd, WM_TRAY);
+    if let Err(err) = tray.show_icon(tray::tooltip(false)) {
+        warn!("Could not add the tray icon: {}", err);
+    }
+
     // Popup window
-    let popup = PopupWindow::new(app_window.hwnd).expect("Could not create PopupWindow");
+    let mut popup = try!{ PopupWindow::new(app_window.hwnd, &config.settings().theme, config.settings().popup_placement) };
     let popup_rx = popup.listen();
 
     // Persistent state
-    let mut config = load_config().unwrap_or(Config::new());
-    let mut window_list: Vec<(HWND, String)> = Vec::new();
+    let mut backend = Win32Backend;
+    let mut layouts = load_layouts();
+    let mut launcher = Launcher::new();
+    // Search of an accepted query, acted on once its results arrive
+    let mut accepting: Option<usize> = None;
+    // Foreground window when the popup opened, for commands acting on it
+    let mut popup_target: Option<HWND> = None;
+
+    let mut desktops = match Win32Desktops::new() {
+        Ok(desktops) => Some(desktops),
+        Err(err) => {
+            info!("Virtual desktops are not available, all slots are global: {}", err);
+            None
+        }
+    };
+
+    if let Some(ref desktops) = desktops {
+        desktops::sync(desktops, &mut config);
+    }
+
+    let mut watcher = WindowWatcher::new();
+    watcher.start(&backend, &mut config);
+
+    let mut config_watch = FileWatch::new(config_path());
+
+    // Searches run on their own thread, which wakes the message loop when they are done
+    let app_hwnd = app_window.hwnd as usize;
+    let search_worker = SearchWorker::spawn(|| Win32Backend, move || {
+        unsafe { user32::PostMessageW(app_hwnd as HWND, WM_NULL, 0, 0) };
+    });
+
+    search_worker.send(Request::Settings(config.settings().clone()));
+    search_worker.send(Request::AppDirs(config.settings().apps.dirs()));
+
+    let window_rx = match backend.watch_windows() {
+        Ok(rx) => Some(rx),
+        Err(err) => {
+            warn!("Could not watch windows, slots are only checked on use: {}", err);
+            None
+        }
+    };
 
     let mut msg = unsafe { ::std::mem::zeroed() };
     while unsafe { user32::GetMessageW(&mut msg, 0 as HWND, 0, 0) } > 0 {
@@ -48,9 +213,11 @@ pub fn main() {
         while let Ok(event) = app_rx.try_recv() {
             match event {
                 AppMsg::ShowPopup => {
-                    window_list.clear();
-                    get_window_list(&mut window_list);
-                    println!("Grabbed {} window titles", window_list.len());
+                    popup_target = backend.foreground_window();
+
+                    // Without events the index is only as current as its last refresh.
+                    // Programs are scanned again once that scan is a few minutes old.
+                    search_worker.send(Request::Refresh(window_rx.is_none()));
 
                     popup.show();
                 },
@@ -59,37 +226,264 @@ pub fn main() {
                     let window = window_tracking::get_foreground_window();
 
                     if let Ok(window) = window {
-                        println!("Tracking foreground window {:?}: {}",
-                            window.hwnd(),
-                            window.title().unwrap_or("No title"));
+                        info!(slot = vk, title = window.title().unwrap_or("");
+                            "Tracking foreground window {:?}", window.hwnd());
                         
                         config.track_window(vk, window);
                     }
                 },
 
-                AppMsg::FocusWindow(vk) => {
+                AppMsg::FocusWindow(vk, direction) => {
+                    let policy = config.settings().cycle_policy(vk);
+                    let foreground = backend.foreground_window();
                     let window_set = config.get_windows(vk);
+                    let mut focused = false;
 
                     if let Some(window_set) = window_set {
-                        while let Some(window) = window_set.cycle() {
-                            println!("Switching to window {:?}: {}",
-                                window.hwnd(),
-                                window.title().unwrap_or("No title"));
+                        while let Some(window) = window_set.next(policy, direction, foreground) {
+                            debug!(slot = vk, title = window.title().unwrap_or("");
+                                "Switching to window {:?}", window.hwnd());
 
                             match window_tracking::set_foreground_window(window.hwnd()) {
-                                Ok(_) => break,
-                                Err(_) => {
+                                Ok(_) => {
+                                    focused = true;
+                                    break;
+                                },
+                                Err(err) => {
+                                    debug!("Dropping window {:?} from hotkey {}: {}", window.hwnd(), vk, err);
                                     window_set.remove(&window);
                                 }
                             }
                         }
                     }
+
+                    // Empty slots start their program, the first window is grabbed on AppMsg::Poll
+                    if !focused {
+                        if let Some(command) = config.settings().launch_command(vk).cloned() {
+                            info!(slot = vk; "Launching {}", command.exe);
+
+                            match launcher.launch(&mut backend, vk, &command, Instant::now()) {
+                                Ok(_) => app_window.set_polling(true),
+                                Err(err) => error!("Could not launch {}: {}", command.exe, err)
+                            }
+                        }
+                    }
                 },
 
                 AppMsg::ClearWindow(vk) => {
-                    println!("Clearing windows on hotkey {}", vk);
+                    info!("Clearing windows on hotkey {}", vk);
                     config.clear_windows(vk);
                 },
+
+                AppMsg::Poll => {
+                    for event in launcher.poll(&backend, Instant::now()) {
+                        match event {
+                            LaunchEvent::Grabbed(vk, hwnd) => {
+                                let title = backend.window_identity(hwnd)
+                                    .map(|identity| identity.title)
+                                    .unwrap_or(WindowTitle::Unavailable);
+                                info!(slot = vk, title = title.text().unwrap_or(""); "Tracking launched window {:?}", hwnd);
+
+                                config.track_window(vk, Window::titled(hwnd, title));
+                                let _ = backend.focus_window(hwnd);
+                            },
+
+                            LaunchEvent::TimedOut(vk) => {
+                                warn!("No window appeared for hotkey {}", vk);
+                            }
+                        }
+                    }
+
+                    if !launcher.has_pending() {
+                        app_window.set_polling(false);
+                    }
+                },
+
+                AppMsg::ReleaseWindow => {
+                    if let Some(hwnd) = backend.foreground_window() {
+                        release_window(&mut config, hwnd);
+                    }
+                },
+
+                AppMsg::ShiftWindow(offset) => {
+                    if let Some(hwnd) = backend.foreground_window() {
+                        shift_window(&mut config, hwnd, offset);
+                    }
+                },
+
+                AppMsg::SwitchDesktop(number) => {
+                    if let Some(ref mut desktops) = desktops {
+                        if let Some(desktop) = find_desktop(desktops, number) {
+                            info!("Switching to desktop {}", number);
+
+                            if let Err(err) = desktops::switch(desktops, &mut config, desktop) {
+                                warn!("Could not switch to desktop {}: {}", number, err);
+                            }
+                        }
+                    }
+                },
+
+                AppMsg::NextProfile => {
+                    let next = {
+                        let profiles = config.settings().profiles();
+                        let index = profiles.iter().position(|&name| name == config.settings().profile()).unwrap_or(0);
+                        profiles[(index + 1) % profiles.len()].to_string()
+                    };
+
+                    switch_profile(&backend, &mut config, &mut watcher, &next);
+                },
+
+                AppMsg::RunCommand(command) => {
+                    debug!("Running {:?}", command);
+
+                    match command {
+                        Command::Action(action) => app_window.on_action(action),
+
+                        Command::Move(vk) => {
+                            if let Some(hwnd) = backend.foreground_window() {
+                                if !config.move_window(hwnd, vk) {
+                                    warn!("Window {:?} is not in a slot", hwnd);
+                                }
+                            }
+                        },
+
+                        Command::SaveLayout(name) => {
+                            let layout = layouts::capture(&backend);
+                            info!("Saving layout {} with {} windows", name, layout.len());
+
+                            layouts.insert(&name, layout);
+                            if let Err(err) = save_layouts(&layouts) {
+                                error!("Could not save layouts: {}", err);
+                            }
+                        },
+
+                        Command::RestoreLayout(name) => {
+                            match layouts.get(&name) {
+                                Some(layout) => {
+                                    let restored = layouts::restore(&mut backend, layout);
+                                    info!("Restored {} of {} windows from layout {}", restored, layout.len(), name);
+                                },
+                                None => warn!("No layout named {}", name)
+                            }
+                        },
+
+                        Command::ReloadConfig => {
+                            reload_config(&backend, &mut config, &mut watcher, &search_worker, &mut app_window, &mut popup, &mut tray);
+                        },
+
+                        Command::Profile(Some(name)) => {
+                            switch_profile(&backend, &mut config, &mut watcher, &name);
+                        },
+
+                        Command::Profile(None) => {
+                            for name in config.settings().profiles() {
+                                let marker = if name == config.settings().profile() { "*" } else { " " };
+                                info!("  {} {}", marker, name);
+                            }
+                        },
+
+                        Command::ListSlots(slot) => {
+                            let slots: Vec<UINT> = match slot {
+                                Some(vk) => vec![vk],
+                                None => (0..10).map(|digit| constants::VK_0 + digit).collect()
+                            };
+
+                            list_slots(&mut config, &slots);
+                        },
+
+                        Command::ListHotkeys => {
+                            list_hotkeys(app_window.hotkeys());
+                        },
+
+                        Command::LogFilter(spec) => {
+                            match spec.parse::<logging::LogFilter>() {
+                                Ok(filter) => {
+                                    warn!("Log filter is now {}", filter);
+                                    logging::set_filter(filter);
+                                },
+                                Err(err) => warn!("Usage: log <level>[, <module>=<level>...]: {}", err)
+                            }
+                        },
+                    }
+                },
+
+                AppMsg::Tabs(host, list) => {
+                    debug!("Native messaging host {:#x} reported {} tabs of {}", host, list.tabs.len(), list.browser);
+                    search_worker.send(Request::Tabs(host as HWND, list));
+                },
+
+                AppMsg::CycleLogLevel => {
+                    // Logged at warn so the change shows up even at the quietest level
+                    warn!("Log level is now {}", logging::cycle_level());
+                },
+
+                AppMsg::TrayMenu => {
+                    let menu = tray::menu(app_window.is_paused(), &slot_summaries(&mut config));
+
+                    match tray.show_menu(&menu) {
+                        Some(TrayCommand::ShowSwitcher) => app_window.post(AppMsg::ShowPopup),
+                        Some(TrayCommand::FocusSlot(vk)) => app_window.post(AppMsg::FocusWindow(vk, Direction::Forward)),
+                        Some(TrayCommand::ReloadConfig) => {
+                            reload_config(&backend, &mut config, &mut watcher, &search_worker, &mut app_window, &mut popup, &mut tray);
+                        },
+                        Some(TrayCommand::PauseHotkeys) => {
+                            let paused = !app_window.is_paused();
+                            app_window.set_paused(paused);
+                            info!("Hotkeys {}", if paused { "paused" } else { "resumed" });
+
+                            for failure in app_window.hotkeys().failures() {
+                                warn!("Could not register hotkey {}", failure);
+                            }
+
+                            let _ = tray.show_icon(tray::tooltip(paused));
+                        },
+                        Some(TrayCommand::OpenLog) => {
+                            if let Err(err) = tray.open_file(&utils::config_dir().join(logging::FILE_NAME)) {
+                                warn!("Could not open the log: {}", err);
+                            }
+                        },
+                        Some(TrayCommand::Quit) => unsafe { user32::PostQuitMessage(0) },
+                        None => {}
+                    }
+                },
+
+                AppMsg::TaskbarCreated => {
+                    if let Err(err) = tray.show_icon(tray::tooltip(app_window.is_paused())) {
+                        warn!("Could not add the tray icon: {}", err);
+                    }
+                },
+
+                AppMsg::CheckConfig => {
+                    if config_watch.changed() {
+                        debug!("{} changed", config_watch.path().display());
+                        reload_config(&backend, &mut config, &mut watcher, &search_worker, &mut app_window, &mut popup, &mut tray);
+                    }
+                },
+
+                AppMsg::TileWindow(tile) => {
+                    if let Some(hwnd) = backend.foreground_window() {
+                        debug!("Tiling window {:?}: {:?}", hwnd, tile);
+
+                        if let Err(err) = tiling::tile_window(&mut backend, hwnd, tile, config.tiling_options()) {
+                            warn!("Could not tile window {:?}: {}", hwnd, err);
+                        }
+                    }
+                },
+            }
+        }
+
+        // Window events
+        if let Some(ref window_rx) = window_rx {
+            while let Ok(event) = window_rx.try_recv() {
+                // Showing another desktop moves the focus, slots follow to its windows
+                if let WindowEvent::Foreground(_) = event {
+                    if let Some(ref desktops) = desktops {
+                        desktops::sync(desktops, &mut config);
+                    }
+                }
+
+                search_worker.send(Request::Event(event));
+                watcher.handle(&backend, &mut config, event);
             }
         }
 
@@ -97,45 +491,321 @@ pub fn main() {
         while let Ok(event) = popup_rx.try_recv() {
             match event {
                 PopupMsg::Search(Some(s)) => {
-                    println!("Search: {}", s);
+                    trace!("Search: {}", s);
+                    search_worker.search(&s);
                 },
 
                 PopupMsg::Search(None) => {
-                    println!("Search: <null>");
+                    trace!("Search: <null>");
+                    search_worker.cancel();
+                    popup.set_results(&[]);
                 },
 
                 PopupMsg::Accept(s) => {
-                    println!("Accept: {}", s);
+                    debug!("Accept: {}", s);
+                    accepting = Some(search_worker.search(&s));
+                }
+            }
+        }
 
-                    let finder = fuzzy::Finder::new(&s).unwrap();
+        // Search results, only the ones of the latest search arrive
+        while let Some(found) = search_worker.try_recv() {
+            if accepting != Some(found.id) {
+                let lines: Vec<String> = found.results
+                    .iter()
+                    .take(popup::MAX_RESULTS)
+                    .map(|result| format!("{}  {}", result.label, result.subtitle))
+                    .collect();
 
-                    let xx = window_list.iter().find(|w| finder.is_match(&w.1));
-                    match xx {
-                        Some(&(hwnd, ref title)) => {
-                            println!("match! {:?} {}", hwnd, title);
-                            let _ = window_tracking::set_foreground_window(hwnd);
-                            popup._hide();
-                        },
-                        None => println!("no match!")
-                    }
+                popup.set_results(&lines);
+                continue;
+            }
+
+            accepting = None;
+            let s = found.query;
+
+            let result = match found.results.into_iter().next() {
+                Some(result) => result,
+                None => {
+                    debug!("Nothing matches {}", s);
+                    continue;
                 }
+            };
+
+            popup._hide();
+
+            match result.action {
+                ResultAction::Focus(hwnd) => {
+                    debug!("Focusing match {:?}: {}", hwnd, result.label);
+                    let _ = window_tracking::set_foreground_window(hwnd);
+                },
+
+                ResultAction::Open(path) => {
+                    info!("Starting {} from {}", result.label, path.display());
+
+                    if let Err(err) = tray.open_file(&path) {
+                        warn!("Could not start {}: {}", result.label, err);
+                    }
+                },
+
+                ResultAction::ActivateTab(host, tab) => {
+                    debug!("Activating tab {} in window {}: {}", tab.id, tab.window_id, tab.title);
+
+                    if let Err(err) = tab_host::activate_tab(host, &tab) {
+                        warn!("Could not activate tab {}: {}", tab.title, err);
+                        search_worker.send(Request::Tabs(host, Default::default()));
+                    }
+                },
+
+                ResultAction::Run(command) => {
+                    // Commands act on the window that was in front before the popup
+                    if let Some(hwnd) = popup_target {
+                        let _ = window_tracking::set_foreground_window(hwnd);
+                    }
+
+                    app_window.post(AppMsg::RunCommand(command));
+                },
+
+                ResultAction::Usage(usage) => warn!("{}: {}", s, usage)
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn release_window(config: &mut Config, hwnd: HWND) {
+    let slots = config.slots_of(hwnd);
+    info!("Removing window {:?} from hotkeys {:?}", hwnd, slots);
+
+    config.remove_window(hwnd);
+}
+
+fn shift_window(config: &mut Config, hwnd: HWND, offset: isize) {
+    config.shift_window(hwnd, offset);
+
+    let slots = config.slots_of(hwnd);
+    list_slots(config, &slots);
+}
+
+/// Logs the members of each slot in `slots`, in cycle order
+fn list_slots(config: &mut Config, slots: &[UINT]) {
+    for &vk in slots {
+        if let Some(window_set) = config.get_windows(vk) {
+            if window_set.is_empty() {
+                continue;
+            }
+
+            info!("Hotkey {}:", vk);
+            for window in window_set.iter() {
+                info!("    {:?}: {}", window.hwnd(), window.title().unwrap_or("No title"));
+            }
+        }
+    }
+}
+
+/// Logs the registered hotkeys followed by the ones that could not be registered
+fn list_hotkeys(hotkeys: &HotkeyReport) {
+    for &(_, action, chord) in hotkeys.active() {
+        info!("    {}: {}", chord, action);
+    }
+
+    for failure in hotkeys.failures() {
+        warn!("    {}", failure);
+    }
+}
+
+/// Activates a profile at startup, returns false if there is none named `name`
+fn activate_profile(config: &mut Config, name: &str) -> bool {
+    if config.set_profile(name) {
+        info!("Profile {} is active", name);
+        true
+    } else {
+        warn!("No profile named {}, using {}", name, config.settings().profile());
+        false
+    }
+}
+
+/// Activates a profile, applies its rules and remembers it for the next start
+fn switch_profile<B: WindowBackend>(backend: &B, config: &mut Config, watcher: &mut WindowWatcher, name: &str) {
+    if !config.set_profile(name) {
+        warn!("No profile named {}, there are {}", name, config.settings().profiles().join(", "));
+        return;
+    }
+
+    info!("Switched to profile {}", name);
+    watcher.restart(backend, config);
+
+    if let Err(err) = save_profile(name) {
+        error!("Could not save the active profile: {}", err);
+    }
+}
+
+/// Desktop `number`, logging why there is none
+fn find_desktop<D: DesktopBackend>(desktops: &D, number: usize) -> Option<DesktopId> {
+    match desktops::nth(desktops, number) {
+        Ok(Some(desktop)) => Some(desktop),
+        Ok(None) => {
+            warn!("There is no desktop {}", number);
+            None
+        },
+        Err(err) => {
+            warn!("Could not list desktops: {}", err);
+            None
+        }
+    }
+}
+
+/// Front window titles of the slots for the tray menu
+fn slot_summaries(config: &mut Config) -> Vec<SlotSummary> {
+    (0..10)
+        .map(|digit| constants::VK_0 + digit)
+        .map(|vk| SlotSummary {
+            vk: vk,
+            titles: config.get_windows(vk)
+                .map(|window_set| window_set.iter().map(|w| w.title().unwrap_or("No title").to_string()).collect())
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Applies changes to `winman.ini` while windows stay in their slots
+///
+/// Only hotkeys that changed are registered again, rules only apply to windows
+/// that are not in a slot yet and the app directories are scanned again. If the
+/// file is invalid the current settings are kept and the error is shown next to
+/// the tray icon.
+fn reload_config<B: WindowBackend, T: TrayBackend>(backend: &B, config: &mut Config, watcher: &mut WindowWatcher, search_worker: &SearchWorker,
+                                                    app_window: &mut AppWindow, popup: &mut PopupWindow, tray: &mut T) {
+    let settings = match load_config() {
+        Ok(loaded) => loaded.settings().clone(),
+        Err(err) => {
+            error!("Keeping the current settings, could not load winman.ini: {}", err);
+
+            if let Err(err) = tray.notify_error("Could not load winman.ini", &err.to_string()) {
+                warn!("Could not show the error: {}", err);
             }
+
+            return;
         }
+    };
+
+    app_window.set_bindings(&settings.hotkeys());
+    for failure in app_window.hotkeys().failures() {
+        warn!("Could not register hotkey {}", failure);
+    }
+
+    if settings.leader != config.settings().leader {
+        app_window.set_leader(Leader::new(settings.leader.clone()));
     }
+
+    popup.set_theme(&settings.theme);
+    popup.set_placement(settings.popup_placement);
+
+    if settings.log != config.settings().log {
+        if let Err(err) = logging::init(&settings.log, &utils::config_dir()) {
+            warn!("Could not open the log file: {}", err);
+        }
+    }
+
+    let profile = config.settings().profile().to_string();
+    config.set_settings(settings);
+    info!("Reloaded winman.ini");
+
+    if config.settings().profile() != profile {
+        warn!("Profile {} is gone, switched to {}", profile, config.settings().profile());
+    }
+
+    watcher.restart(backend, config);
+    search_worker.send(Request::Settings(config.settings().clone()));
+    search_worker.send(Request::AppDirs(config.settings().apps.dirs()));
+}
+
+/// True when started by a browser as the native messaging host of the WinMan extension
+fn native_host_arg() -> bool {
+    let args: Vec<String> = env::args().skip(1).collect();
+    native_host::is_host_command_line(&args)
 }
 
-fn get_window_list(vec: &mut Vec<(HWND, String)>) {
-    utils::api_wrappers::enum_windows(|hwnd| {
-        let text = utils::api_wrappers::get_window_text(hwnd);
+/// Command given with `--run <command>`, or `--profile <name>` for `--run "profile <name>"`
+fn command_arg() -> Option<(String, Command)> {
+    let mut args = env::args().skip(1);
+    let mut text = None;
 
-        if let Ok(text) = text {
-            vec.push((hwnd, text));
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--run" => text = args.next(),
+            "--profile" => text = args.next().map(|name| format!("profile {}", name)),
+            _ => warn!("Ignoring unknown argument {}", arg)
         }
+    }
+
+    text.and_then(|text| match text.parse::<Command>() {
+        Ok(command) => Some((text, command)),
+        Err(err) => {
+            warn!("Ignoring command '{}': {}", text, err);
+            None
+        }
+    })
+}
+
+/// Profile that was active when WinMan last ran, from `state.ini`
+fn load_profile() -> Option<String> {
+    fs::read_to_string(utils::config_dir().join("state.ini"))
+        .ok()
+        .and_then(|text| Ini::parse(&text).ok())
+        .and_then(|ini| ini.section("profile").and_then(|section| section.get("active")).map(String::from))
+}
+
+fn save_profile(name: &str) -> io::Result<()> {
+    let dir = utils::config_dir();
+    let mut section = Section::new("profile");
+    section.push("active", name);
 
-        TRUE
-    }).expect("Callback does not SetLastError");
+    let mut ini = Ini::new();
+    ini.push(section);
+
+    try!{ fs::create_dir_all(&dir) };
+    fs::write(dir.join("state.ini"), ini.to_string())
+}
+
+fn config_path() -> PathBuf {
+    utils::config_dir().join("winman.ini")
+}
+
+/// Config from `winman.ini`, or the defaults if there is none
+fn load_config() -> Result<Config, Error> {
+    let text = match fs::read_to_string(config_path()) {
+        Ok(text) => text,
+        Err(_) => return Ok(Config::new())
+    };
+
+    let ini = try!{ Ini::parse(&text) };
+    let settings = try!{ Settings::from_ini(&ini) };
+
+    Ok(Config::with_settings(settings))
 }
 
-fn load_config() -> Option<Config> {
-    Some(Config::new())
+fn load_layouts() -> Layouts {
+    let path = utils::config_dir().join("layouts.ini");
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(_) => return Layouts::new()
+    };
+
+    match Ini::parse(&text).and_then(|ini| Layouts::from_ini(&ini)) {
+        Ok(layouts) => layouts,
+        Err(err) => {
+            error!("Could not load {}: {}", path.display(), err);
+            Layouts::new()
+        }
+    }
+}
+
+fn save_layouts(layouts: &Layouts) -> io::Result<()> {
+    let dir = utils::config_dir();
+
+    try!{ fs::create_dir_all(&dir) };
+    fs::write(dir.join("layouts.ini"), layouts.to_ini().to_string())
 }
\ No newline at end of file